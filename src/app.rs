@@ -1,9 +1,19 @@
 #![allow(unreachable_code)]
 
-use crate::gameboy::GameBoy;
-use crate::gui::{DebugCommandQueries, DebugResponse, KeyInput, WatchedAdresses};
+use crate::gameboy::{FrameExit, GameBoy, SgbBorder};
+use crate::gdb::GdbStub;
+use crate::cpu::registers::R8;
+use crate::gui::{
+    CpuFlag, DebugCommandQueries, DebugResponse, EditableRegister, KeyInput, MemoryDump,
+    IoSnapshot, MemoryView, PokeTarget, RamSnapshot, RegistersSnapshot, VideoSnapshot, WatchedAdresses,
+};
+use crate::mmu::cheats::CheatCode;
+use crate::mmu::compat_palettes::PaletteChoice;
+use crate::mmu::inspector::MemoryInspector;
 use crate::mmu::mbc::Mbc;
+use crate::model::BootSetup;
 use std::sync::Mutex;
+use std::time::Duration;
 use std::sync::{
     Arc,
     atomic::{AtomicBool, Ordering},
@@ -19,6 +29,9 @@ pub struct GameApp<T: Mbc> {
     nb_next_intruction: u8,
     is_sending_registers: bool,
     watched_adress: WatchedAdresses,
+    memory_view: Option<MemoryView>, // view shown by the hex viewer, refreshed each time we pause
+    gdb: Option<GdbStub>,
+    gdb_breakpoints: Vec<u16>, // last list sent to the debug panel
     // image_to_change: Arc<Mutex<Vec<u8>>>,
 }
 
@@ -27,25 +40,37 @@ impl<T: Mbc> GameApp<T> {
         self.gameboy.simulate_boot_rom_effect()
     }
 
+    pub fn set_cheats(&mut self, codes: Vec<CheatCode>) {
+        self.gameboy.set_cheats(codes)
+    }
+
+    pub fn set_compat_palette(&mut self, choice: PaletteChoice) {
+        self.gameboy.set_compat_palette(choice)
+    }
+
+    pub fn set_strict_access(&mut self, enabled: bool) {
+        self.gameboy.set_strict_access(enabled)
+    }
+
+    pub fn set_sgb_border(&mut self, border: SgbBorder) {
+        self.gameboy.set_sgb_border(border)
+    }
+
     pub fn new(
         rom: Vec<u8>,
         receiver: Receiver<DebugCommandQueries>,
         sender: Sender<DebugResponse>,
         global_bool: Arc<AtomicBool>,
         image_to_change: Arc<Mutex<Vec<u8>>>,
-        boot_with_nintendo: bool,
+        boot: &BootSetup,
+        gdb_port: Option<u16>,
     ) -> Result<Self, String> {
-        let boot_rom = if boot_with_nintendo {
-            let boot_bytes = std::fs::read("boot-roms/dmg.bin").expect("cannot read boot rom");
-            assert!(boot_bytes.len() == 0x100, "boot rom must be 256 bytes");
+        let boot_rom = boot.boot_rom.as_ref()
+            .map(|path| std::fs::read(path).map_err(|e| format!("Cannot read boot ROM {path:?}: {e}")))
+            .transpose()?;
 
-            let mut boot_rom = [0u8; 0x0100];
-            boot_rom.copy_from_slice(&boot_bytes);
-            Some(boot_rom)
-        } else { None };
-
-
-        let gameboy = GameBoy::<T>::new(rom, boot_rom, image_to_change.clone())?;
+        let gameboy = GameBoy::<T>::new(rom, boot.model, boot_rom, image_to_change.clone())?;
+        let gdb = gdb_port.map(GdbStub::bind).transpose()?;
         Ok(Self {
             gameboy,
             debug_receiver: receiver,
@@ -57,18 +82,35 @@ impl<T: Mbc> GameApp<T> {
             watched_adress: WatchedAdresses {
                 addresses_n_values: Vec::new(),
             },
+            memory_view: None,
+            gdb,
+            gdb_breakpoints: Vec::new(),
             // image_to_change,
         })
     }
 
+    // Only sent when gdb added or removed one, they can't change otherwise
+    fn send_gdb_breakpoints(&mut self) {
+        let mut breakpoints: Vec<u16> = self
+            .gdb
+            .as_ref()
+            .map(|gdb| gdb.breakpoints().iter().copied().collect())
+            .unwrap_or_default();
+        breakpoints.sort_unstable();
+        if breakpoints != self.gdb_breakpoints {
+            self.gdb_breakpoints = breakpoints.clone();
+            let _ = self.debug_sender.try_send(DebugResponse::Breakpoints(breakpoints));
+        }
+    }
+
     fn send_watched_address(&mut self) {
         if !self.watched_adress.addresses_n_values.is_empty() {
             let mut values = WatchedAdresses {
                 addresses_n_values: Vec::new(),
             };
             for (addr, _) in self.watched_adress.addresses_n_values.iter() {
-                let bus = self.gameboy.bus.borrow_mut();
-                let value = bus.read_byte(*addr);
+                let bus = self.gameboy.bus.borrow();
+                let value = bus.peek(*addr);
                 values.addresses_n_values.push((*addr, value as u16));
             }
             let _ = self
@@ -79,17 +121,151 @@ impl<T: Mbc> GameApp<T> {
     }
 
     fn send_registers(&mut self) {
-        let _ = self.debug_sender.try_send(DebugResponse::Registers(
-            self.gameboy.cpu.registers.get_a(),
-            self.gameboy.cpu.registers.get_b(),
-            self.gameboy.cpu.registers.get_c(),
-            self.gameboy.cpu.registers.get_d(),
-            self.gameboy.cpu.registers.get_e(),
-            self.gameboy.cpu.registers.get_h(),
-            self.gameboy.cpu.registers.get_l(),
-            self.gameboy.cpu.registers.get_flags_u8() as u16,
-            self.gameboy.cpu.registers.get_sp(),
-            self.gameboy.cpu.pc,
+        let cpu = &self.gameboy.cpu;
+        let _ = self.debug_sender.try_send(DebugResponse::Registers(RegistersSnapshot {
+            a: cpu.registers.get_a(),
+            f: cpu.registers.get_flags_u8(),
+            b: cpu.registers.get_b(),
+            c: cpu.registers.get_c(),
+            d: cpu.registers.get_d(),
+            e: cpu.registers.get_e(),
+            h: cpu.registers.get_h(),
+            l: cpu.registers.get_l(),
+            sp: cpu.registers.get_sp(),
+            pc: cpu.pc,
+            ime: cpu.ime,
+            halted: cpu.halted,
+            rom_bank: self.gameboy.bus.borrow().mapped_rom_bank() as u16,
+        }));
+    }
+
+    fn set_register(&mut self, register: EditableRegister, value: u16) {
+        let registers = &mut self.gameboy.cpu.registers;
+        let byte = value as u8;
+        match register {
+            EditableRegister::A => registers.set_r8_value(R8::A, byte),
+            EditableRegister::F => registers.set_af(((registers.get_a() as u16) << 8) | byte as u16),
+            EditableRegister::B => registers.set_r8_value(R8::B, byte),
+            EditableRegister::C => registers.set_r8_value(R8::C, byte),
+            EditableRegister::D => registers.set_r8_value(R8::D, byte),
+            EditableRegister::E => registers.set_r8_value(R8::E, byte),
+            EditableRegister::H => registers.set_r8_value(R8::H, byte),
+            EditableRegister::L => registers.set_r8_value(R8::L, byte),
+            EditableRegister::AF => registers.set_af(value),
+            EditableRegister::BC => registers.set_bc(value),
+            EditableRegister::DE => registers.set_de(value),
+            EditableRegister::HL => registers.set_hl(value),
+            EditableRegister::SP => registers.set_sp(value),
+            EditableRegister::PC => self.gameboy.cpu.pc = value,
+        }
+    }
+
+    fn set_flag(&mut self, flag: CpuFlag, value: bool) {
+        let registers = &mut self.gameboy.cpu.registers;
+        match flag {
+            CpuFlag::Zero => registers.set_zero_flag(value),
+            CpuFlag::Subtract => registers.set_subtract_flag(value),
+            CpuFlag::HalfCarry => registers.set_half_carry_flag(value),
+            CpuFlag::Carry => registers.set_carry_flag(value),
+        }
+    }
+
+    fn write_memory(&mut self, target: PokeTarget, address: u16, value: u8) -> Result<(), String> {
+        let mut bus = self.gameboy.bus.borrow_mut();
+        match target {
+            PokeTarget::Bus => {
+                bus.write_byte(address, value);
+                Ok(())
+            }
+            PokeTarget::RomBank(bank) => bus.write_rom_bank(bank, address as usize, value),
+            PokeTarget::RamBank(bank) => bus.write_ram_bank(bank, address as usize, value),
+        }
+    }
+
+    fn send_memory_dump(&mut self, view: MemoryView) {
+        let bus = self.gameboy.bus.borrow();
+        let bank_bytes = |bank: Option<&[u8]>, kind: &str, number: usize| {
+            bank.map(|bytes| bytes.to_vec())
+                .ok_or(format!("The cartridge has no {} bank {}", kind, number))
+        };
+        let peek_range = |start: u16, end: u16| (start..=end).map(|addr| bus.peek(addr)).collect::<Vec<u8>>();
+
+        let bytes = match view {
+            MemoryView::Bus => Ok(peek_range(0x0000, 0xFFFF)),
+            MemoryView::RomBank(bank) => bank_bytes(bus.rom_bank(bank), "ROM", bank),
+            MemoryView::RamBank(bank) => bank_bytes(bus.ram_bank(bank), "RAM", bank),
+            MemoryView::Vram => Ok(peek_range(0x8000, 0x9FFF)),
+            MemoryView::Oam => Ok(peek_range(0xFE00, 0xFE9F)),
+            MemoryView::HRam => Ok(peek_range(0xFF80, 0xFFFE)),
+        };
+
+        let response = match bytes {
+            Ok(bytes) => DebugResponse::MemoryDump(MemoryDump {
+                view,
+                bytes,
+                rom_bank_count: bus.rom_bank_count(),
+                ram_bank_count: bus.ram_bank_count(),
+            }),
+            Err(message) => DebugResponse::CommandFailed(message),
+        };
+        let _ = self.debug_sender.try_send(response);
+    }
+
+    fn send_ram_snapshot(&mut self) {
+        let bus = self.gameboy.bus.borrow();
+        let peek_region = |start: u16, end: u16| (start, (start..=end).map(|addr| bus.peek(addr)).collect::<Vec<u8>>());
+
+        let mut regions = vec![peek_region(0xC000, 0xDFFF), peek_region(0xFF80, 0xFFFE)];
+        if bus.ram_bank_count() > 0 {
+            regions.insert(0, peek_region(0xA000, 0xBFFF));
+        }
+        let _ = self.debug_sender.try_send(DebugResponse::RamSnapshot(RamSnapshot { regions }));
+    }
+
+    fn send_video(&mut self) {
+        let bus = self.gameboy.bus.borrow();
+        let peek_range = |start: u16, end: u16| (start..=end).map(|addr| bus.peek(addr)).collect::<Vec<u8>>();
+        let _ = self.debug_sender.try_send(DebugResponse::Video(VideoSnapshot {
+            vram: peek_range(0x8000, 0x9FFF),
+            oam: peek_range(0xFE00, 0xFE9F),
+            lcdc: bus.peek(0xFF40),
+            scy: bus.peek(0xFF42),
+            scx: bus.peek(0xFF43),
+            ly: bus.peek(0xFF44),
+            bgp: bus.peek(0xFF47),
+            obp0: bus.peek(0xFF48),
+            obp1: bus.peek(0xFF49),
+            wy: bus.peek(0xFF4A),
+            wx: bus.peek(0xFF4B),
+        }));
+    }
+
+    fn send_io_registers(&mut self) {
+        let bus = self.gameboy.bus.borrow();
+        let _ = self.debug_sender.try_send(DebugResponse::IoRegisters(IoSnapshot {
+            bytes: (0xFF00..=0xFF7F).map(|addr| bus.peek(addr)).collect(),
+            ie: bus.peek(0xFFFF),
+        }));
+    }
+
+    fn send_events(&mut self) {
+        let events = self.gameboy.board.borrow_mut().events.drain();
+        if !events.is_empty() {
+            let _ = self.debug_sender.try_send(DebugResponse::Events(events));
+        }
+    }
+
+    fn send_ppu_timeline(&mut self) {
+        if let Some(frame) = self.gameboy.board.borrow().ppu.timeline.last_frame() {
+            let _ = self.debug_sender.try_send(DebugResponse::PpuTimeline(frame.clone()));
+        }
+    }
+
+    fn send_call_stack(&mut self) {
+        let call_stack = &self.gameboy.cpu.call_stack;
+        let _ = self.debug_sender.try_send(DebugResponse::CallStack(
+            call_stack.frames().to_vec(),
+            call_stack.history().copied().collect(),
         ));
     }
 
@@ -99,8 +275,8 @@ impl<T: Mbc> GameApp<T> {
             v.push(
                 self.gameboy
                     .bus
-                    .borrow_mut()
-                    .read_byte(self.gameboy.cpu.pc.wrapping_add(current_instruction as u16))
+                    .borrow()
+                    .peek(self.gameboy.cpu.pc.wrapping_add(current_instruction as u16))
                     as u16,
             );
         }
@@ -138,6 +314,9 @@ impl<T: Mbc> GameApp<T> {
                         let _ = self
                             .debug_sender
                             .try_send(DebugResponse::StepModeSet(self.is_step_mode));
+                        if let (true, Some(view)) = (self.is_step_mode, self.memory_view) {
+                            self.send_memory_dump(view);
+                        }
                     }
                     DebugCommandQueries::WatchAddress(addr) => {
                         if !self
@@ -164,10 +343,64 @@ impl<T: Mbc> GameApp<T> {
                     DebugCommandQueries::GetAddresses => {
                         self.send_watched_address();
                     }
+                    DebugCommandQueries::SetRegister(register, value) => {
+                        self.set_register(register, value);
+                        self.send_registers();
+                    }
+                    DebugCommandQueries::SetFlag(flag, value) => {
+                        self.set_flag(flag, value);
+                        self.send_registers();
+                    }
+                    DebugCommandQueries::SetIme(value) => {
+                        self.gameboy.cpu.ime = value;
+                        self.gameboy.cpu.ime_delay = false;
+                        self.send_registers();
+                    }
+                    DebugCommandQueries::WriteMemory(target, address, value) => {
+                        if let Err(message) = self.write_memory(target, address, value) {
+                            let _ = self
+                                .debug_sender
+                                .try_send(DebugResponse::CommandFailed(message));
+                        }
+                        self.send_watched_address();
+                        self.send_io_registers();
+                    }
+                    DebugCommandQueries::DumpMemory(view) => {
+                        self.memory_view = Some(view);
+                        self.send_memory_dump(view);
+                    }
+                    DebugCommandQueries::RecordPpuTimeline(enabled) => {
+                        self.gameboy.board.borrow_mut().ppu.set_timeline_enabled(enabled);
+                    }
+                    DebugCommandQueries::SetLayers(layers) => {
+                        self.gameboy.board.borrow_mut().ppu.layers = layers;
+                    }
+                    DebugCommandQueries::RecordEvents(enabled) => {
+                        self.gameboy.set_event_recording(enabled);
+                    }
+                    DebugCommandQueries::SetCheats(codes) => {
+                        self.gameboy.set_cheats(codes);
+                    }
+                    DebugCommandQueries::SnapshotRam => self.send_ram_snapshot(),
+                    DebugCommandQueries::SetCompatPalette(choice) => {
+                        self.gameboy.set_compat_palette(choice);
+                    }
+                    DebugCommandQueries::SetStrictAccess(enabled) => {
+                        self.gameboy.set_strict_access(enabled);
+                    }
                 }
             }
         }
 
+        if let Some(gdb) = self.gdb.as_mut() {
+            gdb.accept_client();
+            let is_attached = gdb.is_attached();
+            self.send_gdb_breakpoints();
+            if is_attached {
+                return self.update_with_gdb(keys_down);
+            }
+        }
+
         let mut frame_was_edited = false;
         if is_debug {
             for _ in 0..instruction_to_execute {
@@ -175,6 +408,14 @@ impl<T: Mbc> GameApp<T> {
                 self.send_next_instructions();
                 self.send_watched_address();
                 self.send_registers();
+                self.send_call_stack();
+                self.send_video();
+                self.send_ppu_timeline();
+                self.send_io_registers();
+                self.send_events();
+            }
+            if let (true, true, Some(view)) = (self.is_step_mode, instruction_to_execute > 0, self.memory_view) {
+                self.send_memory_dump(view);
             }
             frame_was_edited
         } else {
@@ -182,6 +423,26 @@ impl<T: Mbc> GameApp<T> {
         }
     }
 
+    // While gdb is attached it decides when the game runs, the debug panel only observes
+    fn update_with_gdb(&mut self, keys_down: &KeyInput) -> bool {
+        let Some(gdb) = self.gdb.as_mut() else {
+            return false;
+        };
+
+        let mut frame_was_edited = gdb.poll(&mut self.gameboy);
+        if gdb.is_halted() {
+            std::thread::sleep(Duration::from_millis(1));
+            return frame_was_edited;
+        }
+
+        match self.gameboy.run_frame_until_breakpoint(keys_down, gdb.breakpoints()) {
+            FrameExit::VBlank => frame_was_edited = true,
+            FrameExit::Breakpoint => gdb.notify_breakpoint(),
+            FrameExit::EndOfFrame => {}
+        }
+        frame_was_edited
+    }
+
     // fn rgb_to_rgba(rgb_frame: &[u8]) -> Vec<u8> {
     //     let mut rgba_frame = Vec::with_capacity(ppu::WIN_SIZE_X * ppu::WIN_SIZE_Y * 4);
     //     for chunk in rgb_frame.chunks(3) {
@@ -1,8 +1,17 @@
-use clap::{Arg, ArgAction, command};
+use std::path::PathBuf;
+
+use clap::{Arg, ArgAction, command, value_parser};
+
+use crate::model::Model;
 
 pub struct EmulatorArguments {
     pub rom_path: Option<String>,
     pub boot_rom: bool,
+    pub gdb_port: Option<u16>,
+    pub cheats: Vec<String>,
+    pub model: Model,
+    pub boot_rom_dir: PathBuf,
+    pub strict_access: bool,
 }
 
 impl EmulatorArguments {
@@ -18,7 +27,46 @@ impl EmulatorArguments {
                     .long("boot_rom")
                     .action(ArgAction::SetTrue)
                     .required(false)
-                    .help("If set, nintendo basic boot rom will boot first.")
+                    .help("If set, the boot rom of the selected model will boot first.")
+            )
+            .arg(
+                Arg::new("gdb_port")
+                    .long("gdb-port")
+                    .value_name("PORT")
+                    .value_parser(value_parser!(u16))
+                    .required(false)
+                    .help("Listen for a gdb remote connection on localhost at this port.")
+            )
+            .arg(
+                Arg::new("cheat")
+                    .long("cheat")
+                    .value_name("CODE")
+                    .action(ArgAction::Append)
+                    .required(false)
+                    .help("Enable a Game Genie (ABC-DEF-GHI) or GameShark (01VVAAAA) code, can be repeated.")
+            )
+            .arg(
+                Arg::new("model")
+                    .long("model")
+                    .value_name("MODEL")
+                    .value_parser(Model::from_name)
+                    .default_value("dmg")
+                    .help("Console to emulate: dmg0, dmg, mgb, sgb, sgb2, cgb0, cgb or agb.")
+            )
+            .arg(
+                Arg::new("boot_rom_dir")
+                    .long("boot-rom-dir")
+                    .value_name("DIR")
+                    .value_parser(value_parser!(PathBuf))
+                    .default_value("boot-roms")
+                    .help("Folder with the boot ROMs, named after the model (dmg.bin, cgb.bin...).")
+            )
+            .arg(
+                Arg::new("strict_access")
+                    .long("strict-access")
+                    .action(ArgAction::SetTrue)
+                    .required(false)
+                    .help("Block VRAM and OAM while the PPU uses them, and the bus during OAM DMA, like the hardware.")
             )
             .get_matches();
 
@@ -33,9 +81,25 @@ impl EmulatorArguments {
         // boot_with_nintendo_room
         let boot_rom = matches.get_flag("boot_rom");
 
+        let gdb_port = matches.get_one::<u16>("gdb_port").copied();
+
+        let cheats = matches
+            .get_many::<String>("cheat")
+            .map(|codes| codes.cloned().collect())
+            .unwrap_or_default();
+
+        let model = *matches.get_one::<Model>("model").expect("model has a default value");
+        let boot_rom_dir = matches.get_one::<PathBuf>("boot_rom_dir").expect("boot_rom_dir has a default value").clone();
+        let strict_access = matches.get_flag("strict_access");
+
         Self {
             rom_path,
             boot_rom,
+            gdb_port,
+            cheats,
+            model,
+            boot_rom_dir,
+            strict_access,
         }
     }
 }
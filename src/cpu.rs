@@ -6,6 +6,7 @@ pub mod block1;
 pub mod block2;
 pub mod block3;
 pub mod block_prefix;
+pub mod call_stack;
 pub mod conditions;
 pub mod flags_registers;
 pub mod registers;
@@ -16,6 +17,7 @@ use std::cell::RefCell;
 use std::fmt;
 use std::rc::Rc;
 
+use crate::cpu::call_stack::{CallKind, CallStack};
 use crate::cpu::registers::{R8, R16, Registers};
 use crate::mmu::mbc::Mbc;
 use crate::mmu::Mmu;
@@ -28,6 +30,11 @@ enum StepStatus {
     Halted,
 }
 
+// Everything on the board but the CPU, which drives it one T-cycle at a time
+pub trait Clock {
+    fn tick(&mut self, cycles: u64);
+}
+
 pub struct Cpu<T: Mbc> {
     pub registers: Registers,
     pub pc: u16,
@@ -36,7 +43,9 @@ pub struct Cpu<T: Mbc> {
     pub ime_delay: bool, // mimic hardware delay in EI
     pub halted: bool,    // for HALT instruction
     pub halt_bug: bool,
-    tick_to_wait: u8,
+    pub call_stack: CallStack, // shadow stack and jump history for the debugger
+    pub cycles: u64,           // T-cycles elapsed since power on
+    clock: Option<Rc<RefCell<dyn Clock>>>, // None when the CPU runs alone, in tests
 }
 
 impl<T: Mbc> Default for Cpu<T> {
@@ -58,11 +67,17 @@ impl<T: Mbc> Cpu<T> {
             ime_delay: false,
             halted: false,
             halt_bug: false,
-            tick_to_wait: 0,
+            call_stack: CallStack::default(),
+            cycles: 0,
+            clock: None,
         }
     }
 
-    pub fn execute_instruction(&mut self, instruction: u8) -> u8 {
+    pub fn attach_clock(&mut self, clock: Rc<RefCell<dyn Clock>>) {
+        self.clock = Some(clock);
+    }
+
+    pub fn execute_instruction(&mut self, instruction: u8) {
         let block = (instruction & BLOCK_MASK) >> 6;
         match block {
             0b00 => block0::execute_instruction_block0(self, instruction),
@@ -73,12 +88,51 @@ impl<T: Mbc> Cpu<T> {
         }
     }
 
-    pub fn tick(&mut self) {
-        if self.tick_to_wait > 0 {
-            self.tick_to_wait -= 1;
-        } else {
-            self.tick_to_wait = self.step();
+    fn tick_clock(&mut self) {
+        self.cycles = self.cycles.wrapping_add(1);
+        if let Some(clock) = &self.clock {
+            clock.borrow_mut().tick(self.cycles);
+        }
+    }
+
+    /* One M-cycle: the access lands after the first T-cycle, where the whole
+    instruction used to run, and the board catches up on the other three */
+    fn m_cycle<R>(&mut self, access: impl FnOnce(&mut Mmu<T>) -> R) -> R {
+        self.tick_clock();
+        let result = access(&mut self.bus.borrow_mut());
+        for _ in 0..3 {
+            self.tick_clock();
         }
+        result
+    }
+
+    pub fn read_cycle(&mut self, addr: u16) -> u8 {
+        self.m_cycle(|bus| bus.read_byte(addr))
+    }
+
+    pub fn write_cycle(&mut self, addr: u16, value: u8) {
+        self.m_cycle(|bus| bus.write_byte(addr, value))
+    }
+
+    // An M-cycle spent on internal work, nothing on the bus
+    pub fn idle_cycle(&mut self) {
+        self.m_cycle(|_| ())
+    }
+
+    pub fn push_cycles(&mut self, value: u16) {
+        let sp = self.registers.get_sp().wrapping_sub(1);
+        self.write_cycle(sp, (value >> 8) as u8);
+        let sp = sp.wrapping_sub(1);
+        self.write_cycle(sp, (value & 0xFF) as u8);
+        self.registers.set_sp(sp);
+    }
+
+    pub fn pop_cycles(&mut self) -> u16 {
+        let sp = self.registers.get_sp();
+        let low = self.read_cycle(sp) as u16;
+        let high = self.read_cycle(sp.wrapping_add(1)) as u16;
+        self.registers.set_sp(sp.wrapping_add(2));
+        (high << 8) | low
     }
 
     fn handle_halt_state(&mut self) -> StepStatus {
@@ -101,30 +155,35 @@ impl<T: Mbc> Cpu<T> {
     }
 
     fn handle_ime_state(&mut self) -> StepStatus {
-        if self.ime {
-            let mut bus = self.bus.borrow_mut();
-            if let Some(interrupt) = bus.interrupts_next_request() {
-                self.ime = false;
-                bus.interrupts_clear_request(interrupt);
+        if !self.ime || self.bus.borrow().interrupts_next_request().is_none() {
+            return StepStatus::Continue;
+        }
+        self.ime = false;
+        self.idle_cycle();
+        self.idle_cycle();
 
-                let ret_addr = self.pc;
+        let ret_addr = self.pc;
 
-                let sp1 = self.registers.get_sp().wrapping_sub(1);
-                self.registers.set_sp(sp1);
-                bus.write_byte(sp1, (ret_addr >> 8) as u8);
+        let sp1 = self.registers.get_sp().wrapping_sub(1);
+        self.write_cycle(sp1, (ret_addr >> 8) as u8);
 
-                let sp2 = sp1.wrapping_sub(1);
-                self.registers.set_sp(sp2);
-                bus.write_byte(sp2, (ret_addr & 0xFF) as u8);
+        // The high byte can land on IE, the vector is only picked after it
+        let interrupt = self.bus.borrow().interrupts_next_request();
 
+        let sp2 = sp1.wrapping_sub(1);
+        self.write_cycle(sp2, (ret_addr & 0xFF) as u8);
+        self.registers.set_sp(sp2);
+
+        match interrupt {
+            Some(interrupt) => {
+                self.bus.borrow_mut().interrupts_clear_request(interrupt);
                 self.pc = interrupt.vector();
-                StepStatus::Halted
-            } else {
-                StepStatus::Continue
+                self.call_stack.push(CallKind::Interrupt(interrupt), ret_addr, self.pc, ret_addr, self.cycles);
             }
-        } else {
-            StepStatus::Continue
+            None => self.pc = 0x0000,
         }
+        self.idle_cycle();
+        StepStatus::Halted
     }
 
     fn handle_halt_bug(&mut self) {
@@ -141,44 +200,50 @@ impl<T: Mbc> Cpu<T> {
         }
     }
 
-    pub fn step(&mut self) -> u8 {
+    // Runs one instruction (or one halted M-cycle, or an interrupt dispatch), the board ticks along
+    pub fn step(&mut self) {
+        // The CPU is kept off the bus while an HDMA block is copied
+        while self.bus.borrow().is_hdma_copying() {
+            self.idle_cycle();
+        }
         if self.handle_halt_state() == StepStatus::Halted {
-            return 4;
+            self.idle_cycle();
+            return;
         }
         if self.handle_ime_state() == StepStatus::Halted {
-            return 20;
+            return;
         }
 
-        let instruction_byte = self.bus.borrow_mut().read_byte(self.pc);
-        let tick_to_wait = self.execute_instruction(instruction_byte);
+        let instruction_byte = self.read_cycle(self.pc);
+        self.execute_instruction(instruction_byte);
 
         self.handle_halt_bug();
         self.handle_ime_delay();
-
-        tick_to_wait
     }
 
-    pub fn debug_step(&mut self, instruction: u8) -> u8 {
+    pub fn debug_step(&mut self, instruction: u8) {
         if self.handle_halt_state() == StepStatus::Halted {
-            return 4;
+            self.idle_cycle();
+            return;
         }
         if self.handle_ime_state() == StepStatus::Halted {
-            return 20;
+            return;
         }
 
-        let tick_to_wait = self.execute_instruction(instruction);
+        // The opcode comes from the debugger, its fetch still takes an M-cycle
+        self.idle_cycle();
+        self.execute_instruction(instruction);
 
         self.handle_halt_bug();
         self.handle_ime_delay();
-
-        tick_to_wait
     }
 
-    pub fn get_r8_value(&self, register: R8) -> u8 {
+    // (HL) costs an M-cycle on the bus, the other registers are free
+    pub fn get_r8_value(&mut self, register: R8) -> u8 {
         match register {
             R8::HLIndirect => {
                 let addr = self.registers.get_r16_value(R16::HL);
-                self.bus.borrow_mut().read_byte(addr)
+                self.read_cycle(addr)
             }
             _ => self.registers.get_r8_value(register),
         }
@@ -188,7 +253,7 @@ impl<T: Mbc> Cpu<T> {
         match register {
             R8::HLIndirect => {
                 let addr = self.registers.get_r16_value(R16::HL);
-                self.bus.borrow_mut().write_byte(addr, value);
+                self.write_cycle(addr, value);
             }
             _ => self.registers.set_r8_value(register, value),
         }
@@ -273,6 +338,82 @@ mod tests {
         assert_eq!(mmu.read_byte(0xFF0F) & (1 << (Interrupt::Timer as u8)), 0);
     }
 
+    // M-cycle tests
+    fn cpu_running(program: &[u8]) -> Cpu<RomOnly> {
+        let mut cpu = Cpu::<RomOnly>::default();
+        for (i, byte) in program.iter().enumerate() {
+            cpu.bus.borrow_mut().write_byte(0xC000 + i as u16, *byte);
+        }
+        cpu.bus.borrow_mut().write_byte(0xD000, 0x00);
+        cpu.pc = 0xC000;
+        cpu.registers.set_sp(0xDFFE);
+        cpu.registers.set_r16_value(R16::HL, 0xD000);
+        cpu
+    }
+
+    #[test]
+    fn instructions_take_one_m_cycle_per_access() {
+        let cases: [(&[u8], u64); 12] = [
+            (&[0x00], 4),              // nop
+            (&[0x34], 12),             // inc (hl)
+            (&[0x36, 0x42], 12),       // ld (hl), n
+            (&[0x70], 8),              // ld (hl), b
+            (&[0x20, 0x05], 8),        // jr nz, e not taken, Z is set below
+            (&[0x18, 0x05], 12),       // jr e
+            (&[0xCD, 0x00, 0xD0], 24), // call nn
+            (&[0xC5], 16),             // push bc
+            (&[0xC1], 12),             // pop bc
+            (&[0xE8, 0x01], 16),       // add sp, e
+            (&[0xCB, 0x46], 12),       // bit 0, (hl)
+            (&[0xCB, 0x06], 16),       // rlc (hl)
+        ];
+        for (program, expected) in cases {
+            let mut cpu = cpu_running(program);
+            cpu.registers.set_zero_flag(true);
+            cpu.step();
+            assert_eq!(cpu.cycles, expected, "opcode {:#04x}", program[0]);
+        }
+    }
+
+    struct WatchClock {
+        bus: Rc<RefCell<Mmu<RomOnly>>>,
+        changed_at: Option<u64>,
+    }
+
+    impl Clock for WatchClock {
+        fn tick(&mut self, cycles: u64) {
+            if self.changed_at.is_none() && self.bus.borrow().read_byte(0xD000) != 0 {
+                self.changed_at = Some(cycles);
+            }
+        }
+    }
+
+    #[test]
+    fn memory_access_lands_on_its_own_m_cycle() {
+        // inc (hl): fetch, read, then the write on the first T-cycle of M3
+        let mut cpu = cpu_running(&[0x34]);
+        let clock = Rc::new(RefCell::new(WatchClock { bus: cpu.bus.clone(), changed_at: None }));
+        cpu.attach_clock(clock.clone());
+
+        cpu.step();
+
+        assert_eq!(clock.borrow().changed_at, Some(10));
+        assert_eq!(cpu.cycles, 12);
+    }
+
+    #[test]
+    fn interrupt_dispatch_takes_five_m_cycles() {
+        let mut cpu = cpu_running(&[0x00]);
+        cpu.bus.borrow_mut().write_byte(0xFFFF, Interrupt::Timer as u8);
+        cpu.bus.borrow_mut().write_byte(0xFF0F, Interrupt::Timer as u8);
+        cpu.ime = true;
+
+        cpu.step();
+
+        assert_eq!(cpu.cycles, 20);
+        assert_eq!(cpu.pc, Interrupt::Timer.vector());
+    }
+
     // HALT tests
     #[test]
     fn test_halt_opcode_sets_halted_and_advances_pc() {
@@ -70,7 +70,7 @@ fn get_instruction_block0(instruction: u8) -> u8 {
     }
 }
 
-pub fn execute_instruction_block0<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
+pub fn execute_instruction_block0<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) {
     let opcode = get_instruction_block0(instruction);
 
     match opcode {
@@ -100,9 +100,8 @@ pub fn execute_instruction_block0<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) ->
     }
 }
 
-fn noop<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
+fn noop<T: Mbc>(cpu: &mut Cpu<T>) {
     cpu.pc += 1;
-    4
 }
 
 fn convert_index_to_cond(instruction: u8) -> Cond {
@@ -110,34 +109,31 @@ fn convert_index_to_cond(instruction: u8) -> Cond {
     Cond::from(cond_index)
 }
 
-fn load_r16_imm16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
+fn load_r16_imm16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) {
     let imm16 = utils::get_imm16(cpu);
     let r16 = R16::from((instruction & utils::R16_MASK) >> 4);
 
     cpu.registers.set_r16_value(r16, imm16);
     cpu.pc = cpu.pc.wrapping_add(3);
-    12
 }
 
-fn load_r16mem_a<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
+fn load_r16mem_a<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) {
     let r16_mem = utils::convert_index_to_r16_mem(instruction);
     let a_value = cpu.registers.get_a();
+    let address = cpu.registers.get_r16_value(R16::from(r16_mem));
 
-    cpu.registers
-        .set_r16_mem_value(&mut cpu.bus.borrow_mut(), R16::from(r16_mem), a_value);
+    cpu.write_cycle(address, a_value);
     if r16_mem == R16Mem::HLincrement || r16_mem == R16Mem::HLdecrement {
         utils::modify_hl(cpu, r16_mem);
     }
 
     cpu.pc = cpu.pc.wrapping_add(1);
-    8
 }
 
-fn load_a_r16mem<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
+fn load_a_r16mem<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) {
     let r16_mem = utils::convert_index_to_r16_mem(instruction);
-    let value = cpu
-        .registers
-        .get_r16_mem_value(&cpu.bus.borrow_mut(), R16::from(r16_mem));
+    let address = cpu.registers.get_r16_value(R16::from(r16_mem));
+    let value = cpu.read_cycle(address);
 
     cpu.set_r8_value(R8::A, value);
     if r16_mem == R16Mem::HLincrement || r16_mem == R16Mem::HLdecrement {
@@ -145,50 +141,48 @@ fn load_a_r16mem<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
     }
 
     cpu.pc = cpu.pc.wrapping_add(1);
-    8
 }
 
-fn load_mem_imm16_sp<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
+fn load_mem_imm16_sp<T: Mbc>(cpu: &mut Cpu<T>) {
     let sp_msb = (cpu.registers.get_sp() >> 8) as u8;
     let sp_lsb = (cpu.registers.get_sp() & 0xFF) as u8;
 
     let imm16 = utils::get_imm16(cpu);
 
-    cpu.bus.borrow_mut().write_byte(imm16, sp_lsb);
-    cpu.bus.borrow_mut().write_byte(imm16 + 1, sp_msb);
+    cpu.write_cycle(imm16, sp_lsb);
+    cpu.write_cycle(imm16.wrapping_add(1), sp_msb);
 
     cpu.pc = cpu.pc.wrapping_add(3);
-    20
 }
 
-fn inc_r16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
+fn inc_r16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) {
     let r16 = utils::convert_index_to_r16(instruction);
     let value = cpu.registers.get_r16_value(r16);
 
     cpu.registers.set_r16_value(r16, value.wrapping_add(1));
+    cpu.idle_cycle();
     cpu.pc += 1;
-    8
 }
 
-fn dec_r16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
+fn dec_r16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) {
     let r16 = utils::convert_index_to_r16(instruction);
     let value = cpu.registers.get_r16_value(r16);
 
     cpu.registers.set_r16_value(r16, value.wrapping_sub(1));
+    cpu.idle_cycle();
     cpu.pc += 1;
-    8
 }
 
-fn add_hl_r16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
+fn add_hl_r16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) {
     let r16 = utils::convert_index_to_r16(instruction);
     let value = cpu.registers.get_r16_value(r16);
     cpu.registers.add_to_r16(R16::HL, value);
+    cpu.idle_cycle();
 
     cpu.pc = cpu.pc.wrapping_add(1);
-    8
 }
 
-fn inc_r8<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
+fn inc_r8<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) {
     let r8 = utils::convert_dest_index_to_r8(instruction);
     let value = cpu.get_r8_value(r8);
     let new_value = value.wrapping_add(1);
@@ -199,10 +193,9 @@ fn inc_r8<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
 
     cpu.set_r8_value(r8, new_value);
     cpu.pc = cpu.pc.wrapping_add(1);
-    4
 }
 
-fn dec_r8<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
+fn dec_r8<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) {
     let r8 = utils::convert_dest_index_to_r8(instruction);
     let value = cpu.get_r8_value(r8);
     let new_value = value.wrapping_sub(1);
@@ -212,31 +205,27 @@ fn dec_r8<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
     cpu.registers.set_half_carry_flag((value & 0x0F) == 0x00);
     cpu.set_r8_value(r8, new_value);
     cpu.pc = cpu.pc.wrapping_add(1);
-    4
 }
 
-fn ld_r8_imm8<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
-    let imm8 = cpu.bus.borrow_mut().read_byte(cpu.pc + 1);
+fn ld_r8_imm8<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) {
+    let imm8 = utils::get_imm8(cpu);
     let r8 = utils::convert_dest_index_to_r8(instruction);
 
     cpu.set_r8_value(r8, imm8);
     cpu.pc = cpu.pc.wrapping_add(2);
-    8
 }
 
-fn rotate_left<T: Mbc>(cpu: &mut Cpu<T>, carry: bool) -> u8 {
+fn rotate_left<T: Mbc>(cpu: &mut Cpu<T>, carry: bool) {
     cpu.registers.rotate_left(R8::A, carry, true);
     cpu.pc = cpu.pc.wrapping_add(1);
-    4
 }
 
-fn rotate_right<T: Mbc>(cpu: &mut Cpu<T>, carry: bool) -> u8 {
+fn rotate_right<T: Mbc>(cpu: &mut Cpu<T>, carry: bool) {
     cpu.registers.rotate_right(R8::A, carry, true);
     cpu.pc = cpu.pc.wrapping_add(1);
-    4
 }
 
-fn daa<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
+fn daa<T: Mbc>(cpu: &mut Cpu<T>) {
     let mut adjust: u8 = 0;
     let mut a = cpu.registers.get_a();
     if cpu.registers.get_subtract_flag() {
@@ -262,53 +251,56 @@ fn daa<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
     cpu.registers.set_zero_flag(a == 0);
     cpu.registers.set_half_carry_flag(false);
     cpu.pc = cpu.pc.wrapping_add(1);
-    4
 }
 
-fn cpl<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
+fn cpl<T: Mbc>(cpu: &mut Cpu<T>) {
     let a = cpu.get_r8_value(R8::A);
     let new_value = !a;
     cpu.set_r8_value(R8::A, new_value);
     cpu.registers.set_subtract_flag(true);
     cpu.registers.set_half_carry_flag(true);
     cpu.pc = cpu.pc.wrapping_add(1);
-    4
 }
 
-fn scf<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
+fn scf<T: Mbc>(cpu: &mut Cpu<T>) {
     cpu.registers.set_subtract_flag(false);
     cpu.registers.set_half_carry_flag(false);
     cpu.registers.set_carry_flag(true);
     cpu.pc = cpu.pc.wrapping_add(1);
-    4
 }
 
-fn ccf<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
+fn ccf<T: Mbc>(cpu: &mut Cpu<T>) {
     let carry_value = cpu.registers.get_carry_flag();
     cpu.registers.set_subtract_flag(false);
     cpu.registers.set_half_carry_flag(false);
     cpu.registers.set_carry_flag(!carry_value);
     cpu.pc = cpu.pc.wrapping_add(1);
-    4
 }
 
-fn jr<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8, has_cond: bool) -> u8 {
+fn jr<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8, has_cond: bool) {
+    // The offset is read even when the jump isn't taken
+    let offset = utils::get_imm8(cpu) as i8;
     if has_cond {
         let cond = convert_index_to_cond(instruction);
         if !cond.test(&mut cpu.registers) {
             cpu.pc = cpu.pc.wrapping_add(2);
-            return 8;
+            return;
         }
     }
-    let offset = cpu.bus.borrow_mut().read_byte(cpu.pc + 1) as i8;
-    cpu.pc = ((cpu.pc as i32) + 2 + (offset as i32)) as u16;
-    12
+    let target = ((cpu.pc as i32) + 2 + (offset as i32)) as u16;
+    cpu.idle_cycle();
+    cpu.call_stack.record_jump(cpu.pc, target, cpu.cycles);
+    cpu.pc = target;
 }
 
-fn stop<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
-    // TODO implement stop for real
+fn stop<T: Mbc>(cpu: &mut Cpu<T>) {
+    // TODO implement stop for real, only the CGB speed switch is done
+    let mut bus = cpu.bus.borrow_mut();
+    if bus.switch_speed() {
+        bus.write_byte(0xFF04, 0);
+    }
+    drop(bus);
     cpu.pc = cpu.pc.wrapping_add(1);
-    4
 }
 
 #[cfg(test)]
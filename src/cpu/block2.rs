@@ -36,7 +36,7 @@ fn get_instruction_block2(instruction: u8) -> u8 {
     }
 }
 
-pub fn execute_instruction_block2<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
+pub fn execute_instruction_block2<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) {
     let opcode = get_instruction_block2(instruction);
 
     match opcode {
@@ -50,11 +50,6 @@ pub fn execute_instruction_block2<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) ->
         0b10111000 => cp_a_r8(cpu, instruction),
         _ => unreachable!(),
     }
-
-    match opcode & 0b111 {
-        0b110 => 8,
-        _ => 4,
-    }
 }
 
 fn add_a_r8<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8, with_carry: bool) {
@@ -3,6 +3,7 @@
 
 use crate::cpu::Cpu;
 use crate::cpu::block_prefix;
+use crate::cpu::call_stack::CallKind;
 use crate::cpu::conditions::Cond;
 use crate::cpu::registers::{R8, R16};
 use crate::cpu::utils;
@@ -106,7 +107,7 @@ fn get_instruction_block3(instruction: u8) -> u8 {
     }
 }
 
-pub fn execute_instruction_block3<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
+pub fn execute_instruction_block3<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) {
     let opcode = get_instruction_block3(instruction);
 
     match opcode {
@@ -147,24 +148,22 @@ pub fn execute_instruction_block3<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) ->
     }
 }
 
-fn add_a_imm8<T: Mbc>(cpu: &mut Cpu<T>, with_carry: bool) -> u8 {
-    let imm8 = cpu.bus.borrow_mut().read_byte(cpu.pc + 1);
+fn add_a_imm8<T: Mbc>(cpu: &mut Cpu<T>, with_carry: bool) {
+    let imm8 = utils::get_imm8(cpu);
 
     cpu.registers.add_to_r8(R8::A, imm8, with_carry);
     cpu.pc = cpu.pc.wrapping_add(2);
-    8
 }
 
-fn sub_a_imm8<T: Mbc>(cpu: &mut Cpu<T>, with_carry: bool) -> u8 {
-    let imm8 = cpu.bus.borrow_mut().read_byte(cpu.pc + 1);
+fn sub_a_imm8<T: Mbc>(cpu: &mut Cpu<T>, with_carry: bool) {
+    let imm8 = utils::get_imm8(cpu);
 
     cpu.registers.sub_to_r8(R8::A, imm8, with_carry);
     cpu.pc = cpu.pc.wrapping_add(2);
-    8
 }
 
-fn and_a_imm8<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
-    let imm8 = cpu.bus.borrow_mut().read_byte(cpu.pc + 1);
+fn and_a_imm8<T: Mbc>(cpu: &mut Cpu<T>) {
+    let imm8 = utils::get_imm8(cpu);
     let a_value = cpu.get_r8_value(R8::A);
 
     let new_value = a_value & imm8;
@@ -176,11 +175,10 @@ fn and_a_imm8<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
     cpu.registers.set_carry_flag(false);
 
     cpu.pc = cpu.pc.wrapping_add(2);
-    8
 }
 
-fn xor_a_imm8<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
-    let imm8 = cpu.bus.borrow_mut().read_byte(cpu.pc + 1);
+fn xor_a_imm8<T: Mbc>(cpu: &mut Cpu<T>) {
+    let imm8 = utils::get_imm8(cpu);
     let a_value = cpu.get_r8_value(R8::A);
 
     let new_value = a_value ^ imm8;
@@ -192,11 +190,10 @@ fn xor_a_imm8<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
     cpu.registers.set_carry_flag(false);
 
     cpu.pc = cpu.pc.wrapping_add(2);
-    8
 }
 
-fn or_a_imm8<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
-    let imm8 = cpu.bus.borrow_mut().read_byte(cpu.pc + 1);
+fn or_a_imm8<T: Mbc>(cpu: &mut Cpu<T>) {
+    let imm8 = utils::get_imm8(cpu);
     let a_value = cpu.get_r8_value(R8::A);
 
     let new_value = a_value | imm8;
@@ -208,11 +205,10 @@ fn or_a_imm8<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
     cpu.registers.set_carry_flag(false);
 
     cpu.pc = cpu.pc.wrapping_add(2);
-    8
 }
 
-fn cp_a_imm8<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
-    let imm8 = cpu.bus.borrow_mut().read_byte(cpu.pc + 1);
+fn cp_a_imm8<T: Mbc>(cpu: &mut Cpu<T>) {
+    let imm8 = utils::get_imm8(cpu);
     let a_value = cpu.get_r8_value(R8::A);
 
     let value = a_value.wrapping_sub(imm8);
@@ -224,35 +220,37 @@ fn cp_a_imm8<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
     cpu.registers.set_carry_flag(a_value < imm8);
 
     cpu.pc = cpu.pc.wrapping_add(2);
-    8
 }
 
-fn ret<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8, with_cond: bool) -> u8 {
-    let mut ticking_value = 4;
+fn ret<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8, with_cond: bool) {
     let cond = if with_cond {
-        ticking_value += 4;
+        // Checking the condition costs an M-cycle of its own
+        cpu.idle_cycle();
         utils::convert_index_to_cond(instruction)
     } else {
         Cond::None
     };
 
     if cpu.registers.check_condition(cond) || !with_cond {
-        ticking_value += 12;
-        cpu.pc = cpu.registers.pop_sp(&cpu.bus.borrow_mut());
+        let from = cpu.pc;
+        cpu.pc = cpu.pop_cycles();
+        cpu.idle_cycle();
+        cpu.call_stack.pop(from, cpu.pc, false, cpu.cycles);
     } else {
         cpu.pc = cpu.pc.wrapping_add(1);
     }
-    ticking_value
 }
 
-fn reti<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
-    cpu.pc = cpu.registers.pop_sp(&cpu.bus.borrow_mut());
+fn reti<T: Mbc>(cpu: &mut Cpu<T>) {
+    let from = cpu.pc;
+    cpu.pc = cpu.pop_cycles();
+    cpu.idle_cycle();
+    cpu.call_stack.pop(from, cpu.pc, true, cpu.cycles);
     cpu.ime = true;
     cpu.ime_delay = false;
-    16
 }
 
-fn jp_imm16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8, with_cond: bool) -> u8 {
+fn jp_imm16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8, with_cond: bool) {
     let cond = if with_cond {
         utils::convert_index_to_cond(instruction)
     } else {
@@ -262,21 +260,21 @@ fn jp_imm16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8, with_cond: bool) -> u8 {
     let imm16 = utils::get_imm16(cpu);
 
     if cpu.registers.check_condition(cond) || !with_cond {
+        cpu.idle_cycle();
+        cpu.call_stack.record_jump(cpu.pc, imm16, cpu.cycles);
         cpu.pc = imm16;
-        16
     } else {
         cpu.pc = cpu.pc.wrapping_add(3);
-        12
     }
 }
 
-fn jp_hl<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
+fn jp_hl<T: Mbc>(cpu: &mut Cpu<T>) {
     let hl_value = cpu.registers.get_r16_value(R16::HL);
+    cpu.call_stack.record_jump(cpu.pc, hl_value, cpu.cycles);
     cpu.pc = hl_value;
-    4
 }
 
-fn call_imm16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8, with_cond: bool) -> u8 {
+fn call_imm16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8, with_cond: bool) {
     let cond = if with_cond {
         utils::convert_index_to_cond(instruction)
     } else {
@@ -286,133 +284,129 @@ fn call_imm16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8, with_cond: bool) -> u8
     let imm16 = utils::get_imm16(cpu);
 
     if cpu.registers.check_condition(cond) || !with_cond {
-        cpu.registers
-            .push_sp(&mut cpu.bus.borrow_mut(), cpu.pc.wrapping_add(3));
+        let return_address = cpu.pc.wrapping_add(3);
+        cpu.idle_cycle();
+        cpu.push_cycles(return_address);
+        cpu.call_stack.push(CallKind::Call, cpu.pc, imm16, return_address, cpu.cycles);
         cpu.pc = imm16;
-        20
     } else {
         cpu.pc = cpu.pc.wrapping_add(3);
-        12
     }
 }
 
-fn rst_tgt3<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
+fn rst_tgt3<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) {
     let tgt3_index = (instruction & TGT3_MASK) >> 3;
     let tgt3_address = RST_VEC[tgt3_index as usize] as u16;
 
-    cpu.registers.push_sp(&mut cpu.bus.borrow_mut(), cpu.pc.wrapping_add(1));
+    let return_address = cpu.pc.wrapping_add(1);
+    cpu.idle_cycle();
+    cpu.push_cycles(return_address);
+    cpu.call_stack.push(CallKind::Rst, cpu.pc, tgt3_address, return_address, cpu.cycles);
     cpu.pc = tgt3_address;
-    16
 }
 
-fn pop_r16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
+fn pop_r16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) {
     let r16 = utils::convert_index_to_r16(instruction);
-    let value = cpu.registers.pop_sp(&cpu.bus.borrow_mut());
+    let value = cpu.pop_cycles();
     cpu.registers.set_r16_value(r16, value);
     cpu.pc = cpu.pc.wrapping_add(1);
-    12
 }
 
-fn pop_af<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
-    let value = cpu.registers.pop_sp(&cpu.bus.borrow_mut());
+fn pop_af<T: Mbc>(cpu: &mut Cpu<T>) {
+    let value = cpu.pop_cycles();
     cpu.registers.set_af(value);
     cpu.pc = cpu.pc.wrapping_add(1);
-    12
 }
 
-fn push_r16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) -> u8 {
+fn push_r16<T: Mbc>(cpu: &mut Cpu<T>, instruction: u8) {
     let r16 = utils::convert_index_to_r16(instruction);
     let value = cpu.registers.get_r16_value(r16);
-    cpu.registers.push_sp(&mut cpu.bus.borrow_mut(), value);
+    cpu.idle_cycle();
+    cpu.push_cycles(value);
     cpu.pc = cpu.pc.wrapping_add(1);
-    16
 }
 
-fn push_af<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
+fn push_af<T: Mbc>(cpu: &mut Cpu<T>) {
     let value = cpu.registers.get_af();
-    cpu.registers.push_sp(&mut cpu.bus.borrow_mut(), value);
+    cpu.idle_cycle();
+    cpu.push_cycles(value);
     cpu.pc = cpu.pc.wrapping_add(1);
-    16
 }
 
-fn prefix<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
-    let next_instruction = cpu.bus.borrow_mut().read_byte(cpu.pc + 1);
+fn prefix<T: Mbc>(cpu: &mut Cpu<T>) {
+    let next_instruction = utils::get_imm8(cpu);
     block_prefix::execute_instruction_block_prefix(cpu, next_instruction)
     // cpu.pc = cpu.pc.wrapping_add(1);
 }
 
-fn ldh_c_a<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
+fn ldh_c_a<T: Mbc>(cpu: &mut Cpu<T>) {
     let a_value = cpu.get_r8_value(R8::A);
     let c_value = cpu.get_r8_value(R8::C);
 
     let address = 0xFF00 + (c_value as u16);
-    cpu.bus.borrow_mut().write_byte(address, a_value);
+    cpu.write_cycle(address, a_value);
     cpu.pc = cpu.pc.wrapping_add(1);
-    8
 }
 
-fn ldh_imm8_a<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
+fn ldh_imm8_a<T: Mbc>(cpu: &mut Cpu<T>) {
     let a_value = cpu.get_r8_value(R8::A);
-    let imm8 = cpu.bus.borrow_mut().read_byte(cpu.pc + 1);
+    let imm8 = utils::get_imm8(cpu);
 
     let address = 0xFF00 + (imm8 as u16);
-    cpu.bus.borrow_mut().write_byte(address, a_value);
+    cpu.write_cycle(address, a_value);
     cpu.pc = cpu.pc.wrapping_add(2);
-    12
 }
 
-fn ld_imm16_a<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
+fn ld_imm16_a<T: Mbc>(cpu: &mut Cpu<T>) {
     let a_value = cpu.get_r8_value(R8::A);
     let imm16 = utils::get_imm16(cpu);
 
-    cpu.bus.borrow_mut().write_byte(imm16, a_value);
+    cpu.write_cycle(imm16, a_value);
     cpu.pc = cpu.pc.wrapping_add(3);
-    16
 }
 
-fn ldh_a_c<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
+fn ldh_a_c<T: Mbc>(cpu: &mut Cpu<T>) {
     let c_value = cpu.get_r8_value(R8::C);
     let address = 0xFF00 + (c_value as u16);
-    let value = cpu.bus.borrow_mut().read_byte(address);
+    let value = cpu.read_cycle(address);
 
     cpu.set_r8_value(R8::A, value);
     cpu.pc = cpu.pc.wrapping_add(1);
-    12
 }
 
-fn ldh_a_imm8<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
-    let imm8 = cpu.bus.borrow_mut().read_byte(cpu.pc + 1);
+fn ldh_a_imm8<T: Mbc>(cpu: &mut Cpu<T>) {
+    let imm8 = utils::get_imm8(cpu);
     let address = 0xFF00 + (imm8 as u16);
-    let value = cpu.bus.borrow_mut().read_byte(address);
+    let value = cpu.read_cycle(address);
 
     cpu.set_r8_value(R8::A, value);
     cpu.pc = cpu.pc.wrapping_add(2);
-    12
 }
 
-fn ld_a_imm16<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
+fn ld_a_imm16<T: Mbc>(cpu: &mut Cpu<T>) {
     let imm16 = utils::get_imm16(cpu);
-    let value = cpu.bus.borrow_mut().read_byte(imm16);
+    let value = cpu.read_cycle(imm16);
 
     cpu.set_r8_value(R8::A, value);
     cpu.pc = cpu.pc.wrapping_add(3);
-    16
 }
 
-fn add_sp_imm8<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
-    let offset = cpu.bus.borrow_mut().read_byte(cpu.pc + 1) as i8;
+fn add_sp_imm8<T: Mbc>(cpu: &mut Cpu<T>) {
+    let offset = utils::get_imm8(cpu) as i8;
 
     cpu.registers.add_sp_i8(offset);
+    cpu.idle_cycle();
+    cpu.idle_cycle();
     cpu.pc = cpu.pc.wrapping_add(2);
-    16
 }
 
-fn ld_hl_sp_add_imm8<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
-    let imm8 = cpu.bus.borrow_mut().read_byte(cpu.pc + 1) as i8;
+fn ld_hl_sp_add_imm8<T: Mbc>(cpu: &mut Cpu<T>) {
+    let imm8 = utils::get_imm8(cpu) as i8;
     let sp = cpu.registers.get_sp();
 
     let result = sp.wrapping_add(imm8 as u16);
     cpu.registers.set_r16_value(R16::HL, result);
+    cpu.idle_cycle();
 
     cpu.registers.set_zero_flag(false);
     cpu.registers.set_subtract_flag(false);
@@ -425,26 +419,23 @@ fn ld_hl_sp_add_imm8<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
     cpu.registers.set_carry_flag(sp_lo + imm_u8 as u16 > 0xFF);
 
     cpu.pc = cpu.pc.wrapping_add(2);
-    12
 }
 
-fn ld_sp_hl<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
+fn ld_sp_hl<T: Mbc>(cpu: &mut Cpu<T>) {
     let hl_value = cpu.registers.get_r16_value(R16::HL);
     cpu.registers.set_sp(hl_value);
+    cpu.idle_cycle();
     cpu.pc = cpu.pc.wrapping_add(1);
-    8
 }
 
-fn di<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
+fn di<T: Mbc>(cpu: &mut Cpu<T>) {
     cpu.ime = false;
     cpu.pc = cpu.pc.wrapping_add(1);
-    4
 }
 
-fn ei<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
+fn ei<T: Mbc>(cpu: &mut Cpu<T>) {
     cpu.ime_delay = true;
     cpu.pc = cpu.pc.wrapping_add(1);
-    4
 }
 
 #[cfg(test)]
@@ -736,6 +727,22 @@ mod tests {
         assert_eq!(cpu.pc, 0x8000 + 1);
     }
 
+    #[test]
+    fn test_call_then_ret_updates_call_stack() {
+        let mut cpu = Cpu::<RomOnly> { pc: 0x8000, ..Default::default() };
+        cpu.bus.borrow_mut().write_byte(cpu.pc + 1, 0x34); // LSB
+        cpu.bus.borrow_mut().write_byte(cpu.pc + 2, 0x12); // MSB
+        execute_instruction_block3(&mut cpu, 0xCD); // CALL imm16
+
+        assert_eq!(cpu.call_stack.frames().len(), 1);
+        assert_eq!(cpu.call_stack.frames()[0].return_address, 0x8003);
+
+        execute_instruction_block3(&mut cpu, 0xC9); // RET
+
+        assert_eq!(cpu.pc, 0x8003);
+        assert!(cpu.call_stack.frames().is_empty());
+    }
+
     #[test]
     fn test_rst_38h() {
         let mut cpu = Cpu::<RomOnly>::default();
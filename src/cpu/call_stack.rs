@@ -0,0 +1,185 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+
+use crate::mmu::interrupt::Interrupt;
+
+const MAX_FRAMES: usize = 256; // games that never return (e.g. jumping out of an interrupt) must not grow it forever
+pub const DEFAULT_HISTORY_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CallKind {
+    Call,
+    Rst,
+    Interrupt(Interrupt),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CallFrame {
+    pub kind: CallKind,
+    pub call_site: u16,      // PC of the CALL/RST, or the interrupted PC
+    pub target: u16,         // first instruction of the called routine
+    pub return_address: u16, // address pushed on the stack
+    pub cycle: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceKind {
+    Jump,
+    Call,
+    Rst,
+    Ret,
+    Reti,
+    Interrupt(Interrupt),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceEvent {
+    pub kind: TraceKind,
+    pub from: u16,
+    pub to: u16,
+    pub cycle: u64,
+}
+
+/*
+    Shadow call stack maintained next to the real one.
+    It is only fed by CALL/RST/RET/RETI and the interrupt dispatch,
+    so it never reads the emulated memory and can't be corrupted by the game.
+*/
+pub struct CallStack {
+    frames: Vec<CallFrame>,
+    history: VecDeque<TraceEvent>,
+    history_capacity: usize,
+}
+
+impl Default for CallStack {
+    fn default() -> Self {
+        CallStack::new(DEFAULT_HISTORY_CAPACITY)
+    }
+}
+
+impl CallStack {
+    pub fn new(history_capacity: usize) -> Self {
+        CallStack {
+            frames: Vec::new(),
+            history: VecDeque::with_capacity(history_capacity),
+            history_capacity,
+        }
+    }
+
+    pub fn push(&mut self, kind: CallKind, call_site: u16, target: u16, return_address: u16, cycle: u64) {
+        if self.frames.len() >= MAX_FRAMES {
+            self.frames.remove(0);
+        }
+        self.frames.push(CallFrame { kind, call_site, target, return_address, cycle });
+
+        let trace_kind = match kind {
+            CallKind::Call => TraceKind::Call,
+            CallKind::Rst => TraceKind::Rst,
+            CallKind::Interrupt(interrupt) => TraceKind::Interrupt(interrupt),
+        };
+        self.record(trace_kind, call_site, target, cycle);
+    }
+
+    pub fn pop(&mut self, from: u16, to: u16, is_reti: bool, cycle: u64) {
+        // Some games drop or rewrite return addresses, so unwind up to the frame we return into
+        if let Some(index) = self.frames.iter().rposition(|frame| frame.return_address == to) {
+            self.frames.truncate(index);
+        }
+
+        let kind = if is_reti { TraceKind::Reti } else { TraceKind::Ret };
+        self.record(kind, from, to, cycle);
+    }
+
+    pub fn record_jump(&mut self, from: u16, to: u16, cycle: u64) {
+        self.record(TraceKind::Jump, from, to, cycle);
+    }
+
+    fn record(&mut self, kind: TraceKind, from: u16, to: u16, cycle: u64) {
+        if self.history_capacity == 0 {
+            return;
+        }
+        if self.history.len() >= self.history_capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(TraceEvent { kind, from, to, cycle });
+    }
+
+    pub fn frames(&self) -> &[CallFrame] {
+        &self.frames
+    }
+
+    pub fn history(&self) -> impl Iterator<Item = &TraceEvent> {
+        self.history.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.history.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_and_pop_matching_frame() {
+        let mut stack = CallStack::default();
+        stack.push(CallKind::Call, 0x0150, 0x2000, 0x0153, 10);
+        stack.push(CallKind::Rst, 0x2005, 0x0038, 0x2006, 20);
+
+        assert_eq!(stack.frames().len(), 2);
+
+        stack.pop(0x0039, 0x2006, false, 30);
+        assert_eq!(stack.frames().len(), 1);
+        assert_eq!(stack.frames()[0].target, 0x2000);
+    }
+
+    #[test]
+    fn pop_unwinds_to_matching_return_address() {
+        let mut stack = CallStack::default();
+        stack.push(CallKind::Call, 0x0150, 0x2000, 0x0153, 0);
+        stack.push(CallKind::Call, 0x2000, 0x3000, 0x2003, 0);
+        stack.push(CallKind::Call, 0x3000, 0x4000, 0x3003, 0);
+
+        // returning straight into the first caller drops the two inner frames
+        stack.pop(0x2010, 0x0153, false, 0);
+        assert!(stack.frames().is_empty());
+    }
+
+    #[test]
+    fn pop_without_matching_frame_keeps_stack() {
+        let mut stack = CallStack::default();
+        stack.push(CallKind::Call, 0x0150, 0x2000, 0x0153, 0);
+
+        stack.pop(0x2010, 0x1234, false, 0);
+        assert_eq!(stack.frames().len(), 1);
+        assert_eq!(stack.history().last().unwrap().kind, TraceKind::Ret);
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut stack = CallStack::new(4);
+        for i in 0..10 {
+            stack.record_jump(i, i + 1, i as u64);
+        }
+
+        let history: Vec<&TraceEvent> = stack.history().collect();
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[0].from, 6);
+        assert_eq!(history[3].from, 9);
+    }
+
+    #[test]
+    fn interrupt_frames_are_traced() {
+        let mut stack = CallStack::default();
+        stack.push(CallKind::Interrupt(Interrupt::VBlank), 0x0200, 0x0040, 0x0200, 5);
+        stack.pop(0x0050, 0x0200, true, 50);
+
+        let kinds: Vec<TraceKind> = stack.history().map(|event| event.kind).collect();
+        assert_eq!(kinds, vec![TraceKind::Interrupt(Interrupt::VBlank), TraceKind::Reti]);
+        assert!(stack.frames().is_empty());
+    }
+}
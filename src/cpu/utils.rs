@@ -8,12 +8,17 @@ pub const DEST_R8_MASK: u8 = 0b00111000;
 pub const SOURCE_R8_MASK: u8 = 0b00000111;
 pub const COND_MASK: u8 = 0b00011000;
 
+// Two M-cycles, low byte first
 pub fn get_imm16<T: Mbc>(cpu: &mut Cpu<T>) -> u16 {
-    let lsb = cpu.bus.borrow_mut().read_byte(cpu.pc + 1) as u16;
-    let msb = cpu.bus.borrow_mut().read_byte(cpu.pc + 2) as u16;
+    let lsb = get_imm8(cpu) as u16;
+    let msb = cpu.read_cycle(cpu.pc.wrapping_add(2)) as u16;
     (msb << 8) | lsb
 }
 
+pub fn get_imm8<T: Mbc>(cpu: &mut Cpu<T>) -> u8 {
+    cpu.read_cycle(cpu.pc.wrapping_add(1))
+}
+
 pub fn convert_index_to_r16(instruction: u8) -> R16 {
     let r16_index = (instruction & R16_MASK) >> 4;
     R16::from(r16_index)
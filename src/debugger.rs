@@ -3,7 +3,13 @@
 
 pub mod debbuger {
 
-    use crate::gui::{DebugCommandQueries, DebugResponse, DebuggingDevice};
+    use crate::gui::{
+        CpuFlag, DebugCommandQueries, DebugResponse, DebuggingDevice, EditableRegister, MemoryView,
+        PokeTarget,
+    };
+    use crate::mmu::cheats::CheatCode;
+    use crate::mmu::compat_palettes::PaletteChoice;
+    use crate::ppu::layers::LayerToggles;
 
     pub fn update_info_struct(game: &mut DebuggingDevice) {
         let count = 0;
@@ -29,9 +35,43 @@ pub mod debbuger {
                     println!("DebugResponse::InstructionsExecuted=> {s}");
                     todo!();
                 }
-                DebugResponse::Registers(a, b, c, d, e, h, l, hl, sp, pc) => {
-                    println!("DebugResponse::registers=> {a}");
-                    game.registers = (a, b, c, d, e, h, l, hl, sp, pc);
+                DebugResponse::Registers(registers) => {
+                    println!("DebugResponse::registers=> {}", registers.a);
+                    game.registers = registers;
+                }
+                DebugResponse::CommandFailed(message) => {
+                    game.error_message = Some(message);
+                }
+                DebugResponse::CallStack(frames, history) => {
+                    game.call_stack = frames;
+                    game.jump_history = history;
+                }
+                DebugResponse::Breakpoints(addresses) => {
+                    game.breakpoints = addresses;
+                }
+                DebugResponse::MemoryDump(dump) => {
+                    let viewer = &mut game.memory_viewer;
+                    viewer.previous_bytes = if dump.view == viewer.dump.view {
+                        std::mem::take(&mut viewer.dump.bytes)
+                    } else {
+                        Vec::new()
+                    };
+                    viewer.dump = dump;
+                }
+                DebugResponse::Video(video) => {
+                    game.video = video;
+                }
+                DebugResponse::PpuTimeline(frame) => {
+                    game.timeline_viewer.frame = Some(frame);
+                }
+                DebugResponse::IoRegisters(io) => {
+                    game.io = io;
+                }
+                DebugResponse::Events(events) => {
+                    game.event_viewer.push(events);
+                }
+                DebugResponse::RamSnapshot(snapshot) => {
+                    game.ram_search.apply_snapshot(snapshot);
                 }
             }
         }
@@ -80,6 +120,90 @@ pub mod debbuger {
                 .try_send(DebugCommandQueries::WatchAddress(address));
         }
 
+        pub fn request_set_register(&self, register: EditableRegister, value: u16) {
+            let _ = self
+                .core_game
+                .command_query_sender
+                .try_send(DebugCommandQueries::SetRegister(register, value));
+        }
+
+        pub fn request_set_flag(&self, flag: CpuFlag, value: bool) {
+            let _ = self
+                .core_game
+                .command_query_sender
+                .try_send(DebugCommandQueries::SetFlag(flag, value));
+        }
+
+        pub fn request_set_ime(&self, value: bool) {
+            let _ = self
+                .core_game
+                .command_query_sender
+                .try_send(DebugCommandQueries::SetIme(value));
+        }
+
+        pub fn request_write_memory(&self, target: PokeTarget, address: u16, value: u8) {
+            let _ = self
+                .core_game
+                .command_query_sender
+                .try_send(DebugCommandQueries::WriteMemory(target, address, value));
+        }
+
+        pub fn request_memory_dump(&self, view: MemoryView) {
+            let _ = self
+                .core_game
+                .command_query_sender
+                .try_send(DebugCommandQueries::DumpMemory(view));
+        }
+
+        pub fn request_ppu_timeline(&self, enabled: bool) {
+            let _ = self
+                .core_game
+                .command_query_sender
+                .try_send(DebugCommandQueries::RecordPpuTimeline(enabled));
+        }
+
+        pub fn request_layers(&self, layers: LayerToggles) {
+            let _ = self
+                .core_game
+                .command_query_sender
+                .try_send(DebugCommandQueries::SetLayers(layers));
+        }
+
+        pub fn request_ram_snapshot(&self) {
+            let _ = self
+                .core_game
+                .command_query_sender
+                .try_send(DebugCommandQueries::SnapshotRam);
+        }
+
+        pub fn request_compat_palette(&self, choice: PaletteChoice) {
+            let _ = self
+                .core_game
+                .command_query_sender
+                .try_send(DebugCommandQueries::SetCompatPalette(choice));
+        }
+
+        pub fn request_strict_access(&self, enabled: bool) {
+            let _ = self
+                .core_game
+                .command_query_sender
+                .try_send(DebugCommandQueries::SetStrictAccess(enabled));
+        }
+
+        pub fn request_cheats(&self, codes: Vec<CheatCode>) {
+            let _ = self
+                .core_game
+                .command_query_sender
+                .try_send(DebugCommandQueries::SetCheats(codes));
+        }
+
+        pub fn request_event_recording(&self, enabled: bool) {
+            let _ = self
+                .core_game
+                .command_query_sender
+                .try_send(DebugCommandQueries::RecordEvents(enabled));
+        }
+
         fn get_watched_addresses(&self) {
             let _ = self
                 .core_game
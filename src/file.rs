@@ -4,6 +4,9 @@ use std::io::ErrorKind;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::mmu::cheats::CheatCode;
+use crate::mmu::compat_palettes::PaletteChoice;
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct PlayedRom {
     pub last_launched: DateTime<Utc>,
@@ -75,13 +78,132 @@ impl GbmuFile {
     // }
 
     fn persist(&self) {
-        match serde_json::to_string_pretty(self) {
-            Ok(json) => {
-                if let Err(e) = fs::write(&self.path, json) {
-                    eprintln!("Warning: Could not write to {:?}: {e}", self.path);
-                }
+        write_json(&self.path, self);
+    }
+}
+
+fn write_json<T: Serialize>(path: &PathBuf, value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => {
+            if let Err(e) = fs::write(path, json) {
+                eprintln!("Warning: Could not write to {path:?}: {e}");
             }
-            Err(e) => eprintln!("Warning: Could not serialize config: {e}"),
         }
+        Err(e) => eprintln!("Warning: Could not serialize config: {e}"),
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedCheat {
+    pub code: String,
+    pub description: String,
+    pub enabled: bool,
+}
+
+// One file per ROM in ~/.gbmu/cheats/, named after the ROM file
+#[derive(Serialize, Deserialize, Default)]
+pub struct CheatFile {
+    pub cheats: Vec<SavedCheat>,
+
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+// ~/.gbmu/<folder>/<rom file stem>.json
+fn rom_config_path(folder: &str, rom_path: &str) -> Option<PathBuf> {
+    let home = dirs::home_dir()?;
+    let rom_name = PathBuf::from(rom_path).file_stem().unwrap_or_default().to_string_lossy().to_string();
+    Some(home.join(".gbmu").join(folder).join(format!("{rom_name}.json")))
+}
+
+fn persist_in_folder<T: Serialize>(path: &PathBuf, value: &T) {
+    if let Some(dir) = path.parent()
+        && let Err(e) = fs::create_dir_all(dir)
+    {
+        eprintln!("Warning: Could not create {dir:?}: {e}");
+        return;
+    }
+    write_json(path, value);
+}
+
+impl CheatFile {
+    pub fn load_for_rom(rom_path: &str) -> Self {
+        let Some(path) = rom_config_path("cheats", rom_path) else {
+            eprintln!("Warning: Could not find home directory, cheats won't be saved");
+            return CheatFile::default();
+        };
+
+        let mut cheats = match File::open(&path) {
+            Ok(file) => serde_json::from_reader(file).unwrap_or_else(|e| {
+                eprintln!("Warning: Could not parse {path:?}, starting without cheats: {e}");
+                CheatFile::default()
+            }),
+            Err(_) => CheatFile::default(),
+        };
+        cheats.path = path;
+        cheats
+    }
+
+    pub fn add(&mut self, code: &str, description: &str) -> Result<(), String> {
+        let code = code.trim().to_uppercase();
+        CheatCode::parse(&code)?;
+        if self.cheats.iter().any(|cheat| cheat.code == code) {
+            return Err(format!("{code} is already in the list"));
+        }
+
+        self.cheats.push(SavedCheat { code, description: description.trim().to_string(), enabled: true });
+        Ok(())
+    }
+
+    // Codes are checked when added, one edited by hand in the file is skipped if it doesn't parse
+    pub fn enabled_codes(&self) -> Vec<CheatCode> {
+        self.cheats
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .filter_map(|cheat| CheatCode::parse(&cheat.code).ok())
+            .collect()
+    }
+
+    pub fn persist(&self) {
+        persist_in_folder(&self.path, self);
+    }
+}
+
+// Per ROM choices made in the GUI, one file per ROM in ~/.gbmu/settings/
+#[derive(Serialize, Deserialize, Default)]
+pub struct RomSettings {
+    #[serde(default)]
+    pub compat_palette: String,
+
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+impl RomSettings {
+    pub fn load_for_rom(rom_path: &str) -> Self {
+        let Some(path) = rom_config_path("settings", rom_path) else {
+            eprintln!("Warning: Could not find home directory, settings won't be saved");
+            return RomSettings::default();
+        };
+
+        let mut settings = match File::open(&path) {
+            Ok(file) => serde_json::from_reader(file).unwrap_or_else(|e| {
+                eprintln!("Warning: Could not parse {path:?}, using default settings: {e}");
+                RomSettings::default()
+            }),
+            Err(_) => RomSettings::default(),
+        };
+        settings.path = path;
+        settings
+    }
+
+    // An unknown name (or none yet) keeps the DMG shades
+    pub fn compat_palette(&self) -> PaletteChoice {
+        PaletteChoice::from_name(&self.compat_palette).unwrap_or_default()
+    }
+
+    pub fn set_compat_palette(&mut self, choice: PaletteChoice) {
+        self.compat_palette = choice.name().to_string();
+        persist_in_folder(&self.path, self);
     }
 }
\ No newline at end of file
@@ -2,53 +2,157 @@
 #![allow(dead_code)]
 
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::rc::Rc;
 use std::sync::Arc;
 
 use std::sync::Mutex;
 
-use crate::cpu::Cpu;
+use crate::cpu::{Clock, Cpu};
 use crate::cpu::registers::{R8};
 use crate::gui::KeyInput;
+use crate::mmu::cgb::HardwareMode;
+use crate::mmu::cheats::CheatCode;
+use crate::mmu::compat_palettes::PaletteChoice;
+use crate::mmu::events::EventLog;
 use crate::mmu::mbc::Mbc;
 use crate::mmu::Mmu;
+use crate::mmu::inspector::MemoryInspector;
+use crate::model::Model;
 use crate::ppu::Ppu;
+use crate::ppu::colors_palette::Color;
 
-const FRAME_CYCLES: u32 = 70224;
+const FRAME_DOTS: u64 = 70224;
 const WIN_SIZE_X: usize = 160; // Window size in X direction
 const WIN_SIZE_Y: usize = 144; // Window size in Y direction
 const VBLANK_SIZE: usize = 10; // VBlank size in lines
 
+// 256x224 RGB border shared with the GUI, None until the game uploads one
+pub type SgbBorder = Arc<Mutex<Option<Vec<u8>>>>;
+
+#[derive(Debug, PartialEq)]
+pub enum FrameExit {
+    VBlank,
+    Breakpoint,
+    EndOfFrame,
+}
+
 pub struct GameBoy<T: Mbc> {
     pub cpu: Cpu<T>,
-    pub ppu: Ppu<T>,
     pub bus: Rc<RefCell<Mmu<T>>>,
-    pub image: Arc<Mutex<Vec<u8>>>,
+    pub board: Rc<RefCell<Board<T>>>,
+    pub model: Model,
+}
+
+// Everything the CPU clocks through its M-cycles: timers, DMA and the PPU
+pub struct Board<T: Mbc> {
+    pub ppu: Ppu<T>,
+    bus: Rc<RefCell<Mmu<T>>>,
+    image: Arc<Mutex<Vec<u8>>>,
+    pub events: EventLog, // timer and interrupt events, only filled while the debugger asks for them
+    sgb_border: Option<SgbBorder>,
+    vblank_reached: bool,
+    half_dot: bool, // in CGB double speed, a dot lasts two CPU T-cycles
+    dots: u64,      // PPU dots elapsed, frames are paced on these and not on CPU cycles
+}
+
+impl<T: Mbc> Clock for Board<T> {
+    fn tick(&mut self, cycles: u64) {
+        let double_speed = {
+            let mut bus = self.bus.borrow_mut();
+            bus.tick_timers();
+
+            // OAM DMA and HDMA move a byte on the first T-cycle of each M-cycle
+            if cycles % 4 == 1 {
+                if bus.dma_index != 0xFF {
+                    bus.tick_dma();
+                }
+                bus.tick_hdma();
+            }
+            bus.is_double_speed()
+        };
+
+        if double_speed {
+            self.half_dot = !self.half_dot;
+            if self.half_dot {
+                return;
+            }
+        }
+
+        self.dots += 1;
+        let vblank = self.ppu.tick(&mut self.image);
+
+        if self.events.is_enabled() {
+            self.stamp_events(cycles);
+        }
+        if vblank {
+            self.events.next_frame();
+            self.bus.borrow_mut().apply_ram_cheats();
+            self.publish_sgb_border();
+            self.vblank_reached = true;
+        }
+    }
+}
+
+impl<T: Mbc> Board<T> {
+    fn stamp_events(&mut self, cycles: u64) {
+        let mut bus = self.bus.borrow_mut();
+        let line = bus.peek(0xFF44);
+        for kind in bus.take_events() {
+            self.events.push(kind, cycles, line, self.ppu.dots);
+        }
+    }
+
+    fn publish_sgb_border(&mut self) {
+        let Some(output) = &self.sgb_border else { return };
+        if let Some(border) = self.bus.borrow_mut().take_sgb_border() {
+            let rgb = border.into_iter().flat_map(|color| Color::Rgb555(color).to_rgb()).collect();
+            *output.lock().unwrap() = Some(rgb);
+        }
+    }
 }
 
 impl<T: Mbc>  GameBoy<T> {
-    pub fn new(rom: Vec<u8>, boot_rom: Option<[u8; 0x0100]>, image: Arc<Mutex<Vec<u8>>>) -> Result<GameBoy<T>, String> {
-        let bus_ref = Rc::new(RefCell::new(Mmu::<T>::new(&rom)?));
+    pub fn new(rom: Vec<u8>, model: Model, boot_rom: Option<Vec<u8>>, image: Arc<Mutex<Vec<u8>>>) -> Result<GameBoy<T>, String> {
+        let bus_ref = Rc::new(RefCell::new(Mmu::<T>::with_mode(&rom, model.hardware_mode())?));
 
         if let Some(boot_rom) = boot_rom {
             let mut mmu = bus_ref.borrow_mut();
-            mmu.load_boot_rom(boot_rom);
+            mmu.load_boot_rom(boot_rom)?;
         }
 
-        let cpu = Cpu::<T>::new(bus_ref.clone());
         let ppu = Ppu::<T>::new(bus_ref.clone());
+        let board = Rc::new(RefCell::new(Board {
+            ppu,
+            bus: bus_ref.clone(),
+            image,
+            events: EventLog::default(),
+            sgb_border: None,
+            vblank_reached: false,
+            half_dot: false,
+            dots: 0,
+        }));
+
+        let mut cpu = Cpu::<T>::new(bus_ref.clone());
+        cpu.attach_clock(board.clone());
 
-        Ok(GameBoy { cpu, bus: bus_ref, ppu, image })
+        Ok(GameBoy { cpu, bus: bus_ref, board, model })
     }
 
+    // Games check A (and sometimes B) to know which model they run on
     pub fn simulate_boot_rom_effect(&mut self) {
-        self.cpu.set_r8_value(R8::A, 0x01);
-        self.cpu.set_r8_value(R8::B, 0xFF);
-        self.cpu.set_r8_value(R8::C, 0x13);
-        self.cpu.set_r8_value(R8::D, 0x00);
-        self.cpu.set_r8_value(R8::E, 0xC1);
-        self.cpu.set_r8_value(R8::H, 0x84);
-        self.cpu.set_r8_value(R8::L, 0x03);
+        let (mode, header_checksum) = {
+            let bus = self.bus.borrow();
+            (bus.mode(), bus.rom_bank(0).and_then(|bank| bank.get(0x014D).copied()).unwrap_or(0))
+        };
+        let [a, f, b, c, d, e, h, l] = self.model.post_boot_registers(mode, header_checksum);
+        self.cpu.registers.set_af(u16::from_be_bytes([a, f]));
+        self.cpu.set_r8_value(R8::B, b);
+        self.cpu.set_r8_value(R8::C, c);
+        self.cpu.set_r8_value(R8::D, d);
+        self.cpu.set_r8_value(R8::E, e);
+        self.cpu.set_r8_value(R8::H, h);
+        self.cpu.set_r8_value(R8::L, l);
         self.cpu.pc = 0x0100;
         self.cpu.registers.set_sp(0xFFFE);
 
@@ -56,8 +160,6 @@ impl<T: Mbc>  GameBoy<T> {
 
         bus.write_byte(0xFF00, 0xCF);
         bus.write_byte(0xFF01, 0x00);
-        bus.write_byte(0xFF02, 0x7E);
-        bus.write_byte(0xFF04, 0x18);
         bus.write_byte(0xFF05, 0x00);
         bus.write_byte(0xFF06, 0x00);
         bus.write_byte(0xFF07, 0xF8);
@@ -82,18 +184,29 @@ impl<T: Mbc>  GameBoy<T> {
         bus.write_byte(0xFF23, 0xBF);
         bus.write_byte(0xFF24, 0x77);
         bus.write_byte(0xFF25, 0xF3);
-        bus.write_byte(0xFF26, 0xF1);
         bus.write_byte(0xFF40, 0x91);
         bus.write_byte(0xFF41, 0x81);
         bus.write_byte(0xFF42, 0x00);
         bus.write_byte(0xFF43, 0x00);
-        bus.write_byte(0xFF44, 0x91);
         bus.write_byte(0xFF45, 0x00);
-        bus.write_byte(0xFF46, 0xFF);
+        bus.poke(0xFF46, 0xFF); // a write would start an OAM DMA
         bus.write_byte(0xFF47, 0xFC);
         bus.write_byte(0xFF4A, 0x00);
         bus.write_byte(0xFF4B, 0x00);
         bus.write_byte(0xFFFF, 0x00);
+        // DIV only resets on a write
+        for (addr, val) in self.model.post_boot_io() {
+            bus.poke(addr, val);
+        }
+
+        // The CGB boot ROM leaves every background color white for CGB carts
+        if mode == HardwareMode::Cgb {
+            bus.write_byte(0xFF68, 0x80);
+            for _ in 0..32 {
+                bus.write_byte(0xFF69, 0xFF);
+                bus.write_byte(0xFF69, 0x7F);
+            }
+        }
     }
 
 
@@ -117,34 +230,141 @@ impl<T: Mbc>  GameBoy<T> {
     }
 
 
+    pub fn set_cheats(&mut self, codes: Vec<CheatCode>) {
+        self.bus.borrow_mut().cheats.set(codes);
+    }
+
+    pub fn set_compat_palette(&mut self, choice: PaletteChoice) {
+        self.bus.borrow_mut().set_compat_palette(choice);
+    }
+
+    pub fn set_strict_access(&mut self, enabled: bool) {
+        self.bus.borrow_mut().set_strict_access(enabled);
+    }
+
+    // On a Super Game Boy, the border is written in `border` when the game sends one
+    pub fn set_sgb_border(&mut self, border: SgbBorder) {
+        self.board.borrow_mut().sgb_border = Some(border);
+    }
+
+    pub fn set_event_recording(&mut self, enabled: bool) {
+        self.board.borrow_mut().events.set_enabled(enabled);
+        self.bus.borrow_mut().record_events(enabled);
+    }
+
     pub fn run_frame(&mut self, key_input: &KeyInput) -> bool {
-        let mut cycles_elapsed = 0;
+        self.run_frame_until_breakpoint(key_input, &HashSet::new()) == FrameExit::VBlank
+    }
+
+    // Same as run_frame, but stops right before the CPU executes an instruction at one of the breakpoints
+    pub fn run_frame_until_breakpoint(&mut self, key_input: &KeyInput, breakpoints: &HashSet<u16>) -> FrameExit {
+        let start = self.cpu.cycles;
+        let start_dot = self.board.borrow().dots;
 
         self.manage_input(key_input);
-        while cycles_elapsed < FRAME_CYCLES {
-            // 1. Tick Timers
-            self.bus.borrow_mut().tick_timers();
+        while self.board.borrow().dots - start_dot < FRAME_DOTS {
+            if self.cpu.cycles != start && breakpoints.contains(&self.cpu.pc) {
+                return FrameExit::Breakpoint;
+            }
 
-            // 2. Tick OAM DMA en M-Cycles
-            if cycles_elapsed % 4 == 0 {
-                let mut bus = self.bus.borrow_mut();
-                if bus.dma_index != 0xFF {
-                    bus.tick_dma();
-                }
+            if self.step_instruction() {
+                return FrameExit::VBlank;
             }
+        }
+        FrameExit::EndOfFrame
+    }
 
-            // 3. Tick CPU
-            self.cpu.tick();
+    // Runs the whole machine until the CPU has executed exactly one instruction
+    pub fn step_instruction(&mut self) -> bool {
+        self.cpu.step();
+        std::mem::take(&mut self.board.borrow_mut().vblank_reached)
+    }
+}
 
-            // 4. Tick PPU
-            let vblank = self.ppu.tick(&mut self.image);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mmu::mbc::RomOnly;
 
-            if vblank {
-                return true;
-            }
+    fn game_boy(program: &[u8]) -> GameBoy<RomOnly> {
+        game_boy_with(Model::Dmg, program)
+    }
+
+    fn game_boy_with(model: Model, program: &[u8]) -> GameBoy<RomOnly> {
+        let mut rom = vec![0x00; 0x8000];
+        rom[0x0100..0x0100 + program.len()].copy_from_slice(program);
+        rom[0x0143] = 0x80; // dual-mode cart
+        let image = Arc::new(Mutex::new(vec![0u8; WIN_SIZE_X * WIN_SIZE_Y * 3]));
+        let mut gameboy = GameBoy::<RomOnly>::new(rom, model, None, image).unwrap();
+        gameboy.simulate_boot_rom_effect();
+        gameboy
+    }
+
+    #[test]
+    fn skipped_boot_leaves_div_of_the_model() {
+        assert_eq!(game_boy(&[]).bus.borrow().peek(0xFF04), 0xAB);
+        assert_eq!(game_boy_with(Model::Dmg0, &[]).bus.borrow().peek(0xFF04), 0x18);
+        assert_eq!(game_boy_with(Model::Cgb, &[]).bus.borrow().peek(0xFF04), 0x00);
+    }
+
+    #[test]
+    fn double_speed_frames_last_twice_the_cpu_cycles() {
+        // ld a, 1 ; ldh (KEY1), a ; stop ; jr -2
+        let mut gameboy = game_boy_with(Model::Cgb, &[0x3E, 0x01, 0xE0, 0x4D, 0x10, 0x00, 0x18, 0xFE]);
+        assert_eq!(gameboy.run_frame_until_breakpoint(&KeyInput::default(), &HashSet::new()), FrameExit::VBlank);
+        assert!(gameboy.bus.borrow().is_double_speed());
+
+        let start = gameboy.cpu.cycles;
+        assert_eq!(gameboy.run_frame_until_breakpoint(&KeyInput::default(), &HashSet::new()), FrameExit::VBlank);
+
+        // Give or take the jr the CPU was in when VBlank started
+        let frame = gameboy.cpu.cycles - start;
+        assert!(frame.abs_diff(2 * FRAME_DOTS) <= 12, "frame took {frame} CPU cycles");
+    }
 
-            cycles_elapsed += 1;
+    #[test]
+    fn strict_access_after_a_skipped_boot_runs_the_cartridge() {
+        // ld a, $42 ; jr -2
+        let mut gameboy = game_boy(&[0x3E, 0x42, 0x18, 0xFE]);
+        gameboy.set_strict_access(true);
+
+        gameboy.step_instruction();
+
+        assert_eq!(gameboy.cpu.registers.get_a(), 0x42);
+        assert_eq!(gameboy.cpu.pc, 0x0102);
+    }
+
+    #[test]
+    fn strict_access_locks_follow_the_mode_edges() {
+        let gameboy = game_boy(&[]);
+        gameboy.bus.borrow_mut().set_strict_access(true);
+
+        // Walk one line from its first dot, in mode 2, noting the dots where each lock flips
+        let mut board = gameboy.board.borrow_mut();
+        let mut cycles = 0;
+        while board.ppu.dots != 0 || gameboy.bus.borrow().peek(0xFF41) & 0b11 != 2 {
+            cycles += 1;
+            board.tick(cycles);
         }
-        false
+        let mut vram_edges = Vec::new();
+        let mut oam_edges = Vec::new();
+        let (mut vram_locked, mut oam_locked) = (false, true);
+        for dot in 1..456 {
+            cycles += 1;
+            board.tick(cycles);
+            let bus = gameboy.bus.borrow();
+            if bus.is_locked_for_cpu(0x8000) != vram_locked {
+                vram_locked = !vram_locked;
+                vram_edges.push(dot);
+            }
+            if bus.is_locked_for_cpu(0xFE00) != oam_locked {
+                oam_locked = !oam_locked;
+                oam_edges.push(dot);
+            }
+        }
+
+        // Mode 2 holds OAM for 80 dots, mode 3 holds both for 172 with no sprite and SCX 0
+        assert_eq!(vram_edges, vec![80, 252]);
+        assert_eq!(oam_edges, vec![252]);
     }
 }
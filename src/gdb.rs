@@ -0,0 +1,588 @@
+#![allow(dead_code)]
+
+use std::collections::HashSet;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::gameboy::GameBoy;
+use crate::mmu::inspector::MemoryInspector;
+use crate::mmu::mbc::Mbc;
+
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+const MAX_MEMORY_TRANSFER: usize = 0x1000;
+
+/*
+    The SM83 has no register layout in upstream gdb, so it is described with target.xml.
+    Registers are the z80 port pairs, 16 bits little endian: AF BC DE HL SP PC.
+*/
+const REGISTER_COUNT: usize = 6;
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.gnu.gdb.sm83.core">
+    <reg name="af" bitsize="16" type="int" regnum="0"/>
+    <reg name="bc" bitsize="16" type="int"/>
+    <reg name="de" bitsize="16" type="int"/>
+    <reg name="hl" bitsize="16" type="int"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>"#;
+
+pub trait GdbTarget {
+    fn read_register(&self, index: usize) -> u16;
+    fn write_register(&mut self, index: usize, value: u16);
+    fn read_memory(&self, addr: u16) -> u8;
+    fn write_memory(&mut self, addr: u16, val: u8);
+    fn step(&mut self) -> bool; // returns true if a frame was completed during the step
+}
+
+impl<T: Mbc> GdbTarget for GameBoy<T> {
+    fn read_register(&self, index: usize) -> u16 {
+        let registers = &self.cpu.registers;
+        match index {
+            0 => registers.get_af(),
+            1 => registers.get_bc(),
+            2 => registers.get_de(),
+            3 => registers.get_hl(),
+            4 => registers.get_sp(),
+            _ => self.cpu.pc,
+        }
+    }
+
+    fn write_register(&mut self, index: usize, value: u16) {
+        let registers = &mut self.cpu.registers;
+        match index {
+            0 => registers.set_af(value),
+            1 => registers.set_bc(value),
+            2 => registers.set_de(value),
+            3 => registers.set_hl(value),
+            4 => registers.set_sp(value),
+            _ => self.cpu.pc = value,
+        }
+    }
+
+    fn read_memory(&self, addr: u16) -> u8 {
+        self.bus.borrow().peek(addr)
+    }
+
+    fn write_memory(&mut self, addr: u16, val: u8) {
+        self.bus.borrow_mut().poke(addr, val);
+    }
+
+    fn step(&mut self) -> bool {
+        self.step_instruction()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Incoming {
+    Interrupt,
+    Packet(String),
+    Corrupted,
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+// Takes the next complete message out of the buffer, acks and garbage are dropped
+fn take_incoming(buffer: &mut Vec<u8>) -> Option<Incoming> {
+    loop {
+        match *buffer.first()? {
+            0x03 => {
+                buffer.remove(0);
+                return Some(Incoming::Interrupt);
+            }
+            b'$' => {
+                let end = buffer.iter().position(|byte| *byte == b'#')?;
+                if buffer.len() < end + 3 {
+                    return None;
+                }
+                let data = buffer[1..end].to_vec();
+                let expected = std::str::from_utf8(&buffer[end + 1..end + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                buffer.drain(..end + 3);
+
+                return if expected == Some(checksum(&data)) {
+                    Some(Incoming::Packet(String::from_utf8_lossy(&data).into_owned()))
+                } else {
+                    Some(Incoming::Corrupted)
+                };
+            }
+            _ => {
+                buffer.remove(0);
+            }
+        }
+    }
+}
+
+fn parse_hex(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+fn decode_hex_bytes(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+fn encode_register(value: u16) -> String {
+    format!("{:02x}{:02x}", value & 0xFF, value >> 8)
+}
+
+fn decode_register(text: &str) -> Option<u16> {
+    match decode_hex_bytes(text)?.as_slice() {
+        [low, high] => Some(((*high as u16) << 8) | *low as u16),
+        _ => None,
+    }
+}
+
+// "addr,len" as sent by the m, M and Z packets
+fn parse_range(text: &str) -> Option<(u16, usize)> {
+    let (addr, len) = text.split_once(',')?;
+    let addr = parse_hex(addr)?;
+    let len = parse_hex(len)?;
+    if addr.checked_add(len).is_none_or(|end| end > 0x10000) || len > MAX_MEMORY_TRANSFER {
+        return None;
+    }
+    Some((addr as u16, len))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RunState {
+    Halted,
+    Running,
+}
+
+// Protocol state, kept apart from the socket so it can be driven by tests
+struct GdbSession {
+    breakpoints: HashSet<u16>,
+    state: RunState,
+    detached: bool,
+}
+
+impl GdbSession {
+    fn new() -> Self {
+        GdbSession {
+            breakpoints: HashSet::new(),
+            state: RunState::Halted,
+            detached: false,
+        }
+    }
+
+    // Returns the reply to send, None when the answer comes later (continue)
+    fn respond(&mut self, packet: &str, target: &mut impl GdbTarget, frame_done: &mut bool) -> Option<String> {
+        let Some(command) = packet.chars().next() else {
+            return Some(String::new());
+        };
+        let args = &packet[command.len_utf8()..];
+        let error = || "E01".to_string();
+
+        let reply = match command {
+            '?' => format!("S{SIGTRAP:02x}"),
+            'g' => (0..REGISTER_COUNT)
+                .map(|index| encode_register(target.read_register(index)))
+                .collect(),
+            'G' => {
+                if args.len() != REGISTER_COUNT * 4 || !args.is_ascii() {
+                    return Some(error());
+                }
+                for index in 0..REGISTER_COUNT {
+                    match decode_register(&args[index * 4..index * 4 + 4]) {
+                        Some(value) => target.write_register(index, value),
+                        None => return Some(error()),
+                    }
+                }
+                "OK".to_string()
+            }
+            'p' => match parse_hex(args) {
+                Some(index) if index < REGISTER_COUNT => encode_register(target.read_register(index)),
+                _ => error(),
+            },
+            'P' => {
+                let register = args.split_once('=').and_then(|(index, value)| {
+                    Some((parse_hex(index).filter(|index| *index < REGISTER_COUNT)?, decode_register(value)?))
+                });
+                match register {
+                    Some((index, value)) => {
+                        target.write_register(index, value);
+                        "OK".to_string()
+                    }
+                    None => error(),
+                }
+            }
+            'm' => match parse_range(args) {
+                Some((addr, len)) => (0..len)
+                    .map(|offset| format!("{:02x}", target.read_memory(addr.wrapping_add(offset as u16))))
+                    .collect(),
+                None => error(),
+            },
+            'M' => {
+                let write = args.split_once(':').and_then(|(range, data)| {
+                    let (addr, len) = parse_range(range)?;
+                    let bytes = decode_hex_bytes(data).filter(|bytes| bytes.len() == len)?;
+                    Some((addr, bytes))
+                });
+                match write {
+                    Some((addr, bytes)) => {
+                        for (offset, byte) in bytes.iter().enumerate() {
+                            target.write_memory(addr.wrapping_add(offset as u16), *byte);
+                        }
+                        "OK".to_string()
+                    }
+                    None => error(),
+                }
+            }
+            // Software and hardware breakpoints are the same thing for us
+            'Z' | 'z' => {
+                let mut fields = args.split(',');
+                let kind = fields.next();
+                let addr = fields.next().and_then(parse_hex);
+                match (kind, addr) {
+                    (Some("0") | Some("1"), Some(addr)) if addr <= 0xFFFF => {
+                        if command == 'Z' {
+                            self.breakpoints.insert(addr as u16);
+                        } else {
+                            self.breakpoints.remove(&(addr as u16));
+                        }
+                        "OK".to_string()
+                    }
+                    _ => String::new(),
+                }
+            }
+            's' => {
+                if let Some(addr) = parse_hex(args) {
+                    target.write_register(5, addr as u16);
+                }
+                *frame_done |= target.step();
+                format!("S{SIGTRAP:02x}")
+            }
+            'c' => {
+                if let Some(addr) = parse_hex(args) {
+                    target.write_register(5, addr as u16);
+                }
+                self.state = RunState::Running;
+                return None;
+            }
+            'D' => {
+                self.detached = true;
+                "OK".to_string()
+            }
+            'k' => {
+                self.detached = true;
+                return None;
+            }
+            'H' => "OK".to_string(),
+            'q' => self.respond_query(args),
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+
+    fn respond_query(&self, query: &str) -> String {
+        if query.starts_with("Supported") {
+            return "PacketSize=1000;qXfer:features:read+".to_string();
+        }
+        if let Some(range) = query.strip_prefix("Xfer:features:read:target.xml:") {
+            let Some((offset, len)) = range.split_once(',') else {
+                return "E01".to_string();
+            };
+            let offset = parse_hex(offset).unwrap_or(0).min(TARGET_XML.len());
+            let end = offset.saturating_add(parse_hex(len).unwrap_or(0)).min(TARGET_XML.len());
+            let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+            return format!("{marker}{}", &TARGET_XML[offset..end]);
+        }
+
+        match query {
+            "Attached" => "1".to_string(),
+            "C" => "QC1".to_string(),
+            "fThreadInfo" => "m1".to_string(),
+            "sThreadInfo" => "l".to_string(),
+            "Offsets" => "Text=0;Data=0;Bss=0".to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+pub struct GdbStub {
+    listener: TcpListener,
+    client: Option<TcpStream>,
+    incoming: Vec<u8>,
+    session: GdbSession,
+}
+
+impl GdbStub {
+    pub fn bind(port: u16) -> Result<Self, String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("Cannot listen for gdb on port {port}: {e}"))?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        println!("gdb stub listening on 127.0.0.1:{port}");
+
+        Ok(GdbStub {
+            listener,
+            client: None,
+            incoming: Vec::new(),
+            session: GdbSession::new(),
+        })
+    }
+
+    pub fn breakpoints(&self) -> &HashSet<u16> {
+        &self.session.breakpoints
+    }
+
+    // The game only runs on its own when no gdb is attached or gdb asked to continue
+    pub fn is_halted(&self) -> bool {
+        self.client.is_some() && self.session.state == RunState::Halted
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.client.is_some()
+    }
+
+    pub fn notify_breakpoint(&mut self) {
+        self.session.state = RunState::Halted;
+        self.send_packet(&format!("S{SIGTRAP:02x}"));
+    }
+
+    // Handles everything gdb sent since the last call, returns true if a step completed a frame
+    pub fn poll(&mut self, target: &mut impl GdbTarget) -> bool {
+        self.accept_client();
+        self.read_client();
+
+        let mut frame_done = false;
+        while let Some(incoming) = take_incoming(&mut self.incoming) {
+            match incoming {
+                Incoming::Interrupt => {
+                    if self.session.state == RunState::Running {
+                        self.session.state = RunState::Halted;
+                        self.send_packet(&format!("S{SIGINT:02x}"));
+                    }
+                }
+                Incoming::Corrupted => self.send_raw(b"-"),
+                Incoming::Packet(packet) => {
+                    self.send_raw(b"+");
+                    if let Some(reply) = self.session.respond(&packet, target, &mut frame_done) {
+                        self.send_packet(&reply);
+                    }
+                    if self.session.detached {
+                        self.disconnect();
+                    }
+                }
+            }
+        }
+        frame_done
+    }
+
+    // Called on every update, gdb can attach at any time
+    pub fn accept_client(&mut self) {
+        if self.client.is_some() {
+            return;
+        }
+        if let Ok((stream, address)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_err() {
+                return;
+            }
+            let _ = stream.set_nodelay(true);
+            println!("gdb connected from {address}");
+            self.client = Some(stream);
+            self.session = GdbSession::new();
+        }
+    }
+
+    fn read_client(&mut self) {
+        let Some(client) = self.client.as_mut() else {
+            return;
+        };
+        let mut buffer = [0u8; 1024];
+        loop {
+            match client.read(&mut buffer) {
+                Ok(0) => return self.disconnect(),
+                Ok(count) => self.incoming.extend_from_slice(&buffer[..count]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return self.disconnect(),
+            }
+        }
+    }
+
+    fn send_packet(&mut self, data: &str) {
+        let packet = format!("${data}#{:02x}", checksum(data.as_bytes()));
+        self.send_raw(packet.as_bytes());
+    }
+
+    fn send_raw(&mut self, data: &[u8]) {
+        if let Some(client) = self.client.as_mut()
+            && client.write_all(data).is_err()
+        {
+            self.disconnect();
+        }
+    }
+
+    fn disconnect(&mut self) {
+        println!("gdb disconnected");
+        self.client = None;
+        self.incoming.clear();
+        self.session = GdbSession::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeTarget {
+        registers: [u16; REGISTER_COUNT],
+        memory: Vec<u8>,
+        steps: usize,
+    }
+
+    impl GdbTarget for FakeTarget {
+        fn read_register(&self, index: usize) -> u16 {
+            self.registers[index]
+        }
+
+        fn write_register(&mut self, index: usize, value: u16) {
+            self.registers[index] = value;
+        }
+
+        fn read_memory(&self, addr: u16) -> u8 {
+            self.memory[addr as usize]
+        }
+
+        fn write_memory(&mut self, addr: u16, val: u8) {
+            self.memory[addr as usize] = val;
+        }
+
+        fn step(&mut self) -> bool {
+            self.steps += 1;
+            self.registers[5] += 1;
+            false
+        }
+    }
+
+    fn respond(session: &mut GdbSession, target: &mut FakeTarget, packet: &str) -> Option<String> {
+        session.respond(packet, target, &mut false)
+    }
+
+    #[test]
+    fn take_incoming_checks_checksum_and_skips_acks() {
+        let mut buffer = b"+$g#67$g#00\x03$m0".to_vec();
+
+        assert_eq!(take_incoming(&mut buffer), Some(Incoming::Packet("g".to_string())));
+        assert_eq!(take_incoming(&mut buffer), Some(Incoming::Corrupted));
+        assert_eq!(take_incoming(&mut buffer), Some(Incoming::Interrupt));
+        // incomplete packet stays in the buffer
+        assert_eq!(take_incoming(&mut buffer), None);
+        assert_eq!(buffer, b"$m0".to_vec());
+    }
+
+    #[test]
+    fn registers_are_little_endian() {
+        let mut session = GdbSession::new();
+        let mut target = FakeTarget::default();
+        target.registers[5] = 0x0150;
+
+        let reply = respond(&mut session, &mut target, "g").unwrap();
+        assert_eq!(&reply[20..24], "5001");
+
+        assert_eq!(respond(&mut session, &mut target, "P1=3412").unwrap(), "OK");
+        assert_eq!(target.registers[1], 0x1234);
+        assert_eq!(respond(&mut session, &mut target, "p1").unwrap(), "3412");
+    }
+
+    #[test]
+    fn memory_read_and_write() {
+        let mut session = GdbSession::new();
+        let mut target = FakeTarget {
+            memory: vec![0; 0x10000],
+            ..Default::default()
+        };
+
+        assert_eq!(respond(&mut session, &mut target, "Mc000,2:abcd").unwrap(), "OK");
+        assert_eq!(respond(&mut session, &mut target, "mc000,3").unwrap(), "abcd00");
+        assert_eq!(respond(&mut session, &mut target, "mffff,2").unwrap(), "E01");
+    }
+
+    #[test]
+    fn breakpoints_step_and_continue() {
+        let mut session = GdbSession::new();
+        let mut target = FakeTarget::default();
+
+        assert_eq!(respond(&mut session, &mut target, "Z0,150,1").unwrap(), "OK");
+        assert!(session.breakpoints.contains(&0x0150));
+        assert_eq!(respond(&mut session, &mut target, "z0,150,1").unwrap(), "OK");
+        assert!(session.breakpoints.is_empty());
+        // watchpoints are not supported
+        assert_eq!(respond(&mut session, &mut target, "Z2,c000,1").unwrap(), "");
+
+        assert_eq!(respond(&mut session, &mut target, "s").unwrap(), "S05");
+        assert_eq!(target.steps, 1);
+
+        assert_eq!(respond(&mut session, &mut target, "c"), None);
+        assert_eq!(session.state, RunState::Running);
+    }
+
+    #[test]
+    fn memory_range_overflow_is_an_error() {
+        let mut session = GdbSession::new();
+        let mut target = FakeTarget { memory: vec![0; 0x10000], ..Default::default() };
+
+        assert_eq!(respond(&mut session, &mut target, "mffffffffffffffff,2").unwrap(), "E01");
+        assert_eq!(respond(&mut session, &mut target, "M1,ffffffffffffffff:00").unwrap(), "E01");
+    }
+
+    #[test]
+    fn client_connecting_gets_a_stop_reply() {
+        use std::io::Write;
+
+        let mut stub = GdbStub::bind(0).unwrap();
+        let port = stub.listener.local_addr().unwrap().port();
+        let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        client.set_read_timeout(Some(std::time::Duration::from_secs(1))).unwrap();
+        client.write_all(b"$?#3f").unwrap();
+
+        let mut target = FakeTarget::default();
+        let mut reply = Vec::new();
+        for _ in 0..100 {
+            stub.accept_client();
+            stub.poll(&mut target);
+            let mut buffer = [0u8; 64];
+            if let Ok(count) = client.read(&mut buffer) {
+                reply.extend_from_slice(&buffer[..count]);
+            }
+            if reply.ends_with(b"#b8") {
+                break;
+            }
+        }
+
+        assert!(stub.is_attached());
+        assert_eq!(reply, b"+$S05#b8");
+    }
+
+    #[test]
+    fn target_xml_is_sent_in_chunks() {
+        let session = GdbSession::new();
+
+        let first = session.respond_query("Xfer:features:read:target.xml:0,10");
+        assert_eq!(first, format!("m{}", &TARGET_XML[..0x10]));
+
+        let last = session.respond_query(&format!("Xfer:features:read:target.xml:{:x},1000", 0x10));
+        assert_eq!(last, format!("l{}", &TARGET_XML[0x10..]));
+    }
+
+    #[test]
+    fn oversized_target_xml_length_reads_to_the_end() {
+        let session = GdbSession::new();
+
+        let reply = session.respond_query("Xfer:features:read:target.xml:10,ffffffffffffffff");
+        assert_eq!(reply, format!("l{}", &TARGET_XML[0x10..]));
+    }
+}
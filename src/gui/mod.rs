@@ -7,8 +7,19 @@ mod views;
 
 use std::path::{Path, PathBuf};
 use egui_file_dialog::{FileDialog, Filter};
-use crate::mmu::mbc::{Mbc1, Mbc2, Mbc3, RomOnly};
+use crate::cpu::call_stack::{CallFrame, TraceEvent};
+use crate::file::{CheatFile, RomSettings};
+use crate::gameboy::SgbBorder;
+use crate::mmu::cheats::CheatCode;
+use crate::mmu::compat_palettes::PaletteChoice;
+use crate::mmu::events::HardwareEvent;
+use crate::mmu::mbc::{Mbc1, Mbc2, Mbc3, Mbc5, RomOnly};
+use crate::mmu::sgb;
+use crate::model::{BootSetup, Model};
 use crate::ppu;
+use crate::ppu::layers::LayerToggles;
+use crate::ppu::timeline::FrameTimeline;
+use crate::symbols::SymbolTable;
 use eframe::egui::{Key, TextureHandle};
 use eframe::egui::{load::SizedTexture, vec2, ColorImage, TextureOptions};
 use std::collections::HashSet;
@@ -54,11 +65,21 @@ impl eframe::App for GraphicalApp {
 pub struct EmulationAppOptions {
     rom_path: String,
     boot_rom: bool,
+    gdb_port: Option<u16>,
+    cheats: Vec<String>,
+    model: Model,
+    boot_rom_dir: PathBuf,
+    strict_access: bool,
 }
 
 pub struct CoreGameOptions {
     rom_path: String,
     boot_rom: bool,
+    gdb_port: Option<u16>,
+    cheats: Vec<String>, // codes from the command line, enabled on top of the saved ones
+    model: Model,
+    boot_rom_dir: PathBuf,
+    strict_access: bool, // VRAM/OAM and the bus blocked like on hardware
 }
 
 impl From<EmulationAppOptions> for CoreGameOptions {
@@ -66,14 +87,19 @@ impl From<EmulationAppOptions> for CoreGameOptions {
         Self {
             rom_path: value.rom_path,
             boot_rom: value.boot_rom,
+            gdb_port: value.gdb_port,
+            cheats: value.cheats,
+            model: value.model,
+            boot_rom_dir: value.boot_rom_dir,
+            strict_access: value.strict_access,
         }
     }
 }
 
 impl EmulationAppOptions {
-    pub fn new(rom_path: String, boot_rom: bool) -> Self{
+    pub fn new(rom_path: String, boot_rom: bool, gdb_port: Option<u16>, cheats: Vec<String>, model: Model, boot_rom_dir: PathBuf, strict_access: bool) -> Self{
         Self {
-            rom_path, boot_rom
+            rom_path, boot_rom, gdb_port, cheats, model, boot_rom_dir, strict_access
         }
     }
 }
@@ -182,6 +208,7 @@ pub enum AnyGameApp {
     Mbc1(GameApp<Mbc1>),
     Mbc2(GameApp<Mbc2>),
     Mbc3(GameApp<Mbc3>),
+    Mbc5(GameApp<Mbc5>),
 }
 
 impl AnyGameApp {
@@ -191,6 +218,7 @@ impl AnyGameApp {
             AnyGameApp::Mbc1(g)=> g.update(keys_down),
             AnyGameApp::Mbc2(g)=> g.update(keys_down),
             AnyGameApp::Mbc3(g)=> g.update(keys_down),
+            AnyGameApp::Mbc5(g)=> g.update(keys_down),
         }
     }
 
@@ -200,13 +228,59 @@ impl AnyGameApp {
             AnyGameApp::Mbc1(g)=> g.simulate_boot_rom_effect(),
             AnyGameApp::Mbc2(g)=> g.simulate_boot_rom_effect(),
             AnyGameApp::Mbc3(g)=> g.simulate_boot_rom_effect(),
+            AnyGameApp::Mbc5(g)=> g.simulate_boot_rom_effect(),
+        }
+    }
+
+    pub fn set_cheats(&mut self, codes: Vec<CheatCode>) {
+        match self {
+            AnyGameApp::OnlyRom(g) => g.set_cheats(codes),
+            AnyGameApp::Mbc1(g)=> g.set_cheats(codes),
+            AnyGameApp::Mbc2(g)=> g.set_cheats(codes),
+            AnyGameApp::Mbc3(g)=> g.set_cheats(codes),
+            AnyGameApp::Mbc5(g)=> g.set_cheats(codes),
+        }
+    }
+
+    pub fn set_compat_palette(&mut self, choice: PaletteChoice) {
+        match self {
+            AnyGameApp::OnlyRom(g) => g.set_compat_palette(choice),
+            AnyGameApp::Mbc1(g)=> g.set_compat_palette(choice),
+            AnyGameApp::Mbc2(g)=> g.set_compat_palette(choice),
+            AnyGameApp::Mbc3(g)=> g.set_compat_palette(choice),
+            AnyGameApp::Mbc5(g)=> g.set_compat_palette(choice),
+        }
+    }
+
+    pub fn set_strict_access(&mut self, enabled: bool) {
+        match self {
+            AnyGameApp::OnlyRom(g) => g.set_strict_access(enabled),
+            AnyGameApp::Mbc1(g)=> g.set_strict_access(enabled),
+            AnyGameApp::Mbc2(g)=> g.set_strict_access(enabled),
+            AnyGameApp::Mbc3(g)=> g.set_strict_access(enabled),
+            AnyGameApp::Mbc5(g)=> g.set_strict_access(enabled),
+        }
+    }
+
+    pub fn set_sgb_border(&mut self, border: SgbBorder) {
+        match self {
+            AnyGameApp::OnlyRom(g) => g.set_sgb_border(border),
+            AnyGameApp::Mbc1(g)=> g.set_sgb_border(border),
+            AnyGameApp::Mbc2(g)=> g.set_sgb_border(border),
+            AnyGameApp::Mbc3(g)=> g.set_sgb_border(border),
+            AnyGameApp::Mbc5(g)=> g.set_sgb_border(border),
         }
     }
 }
 
 async fn async_launch_game(
     rom_path: String,
-    boot_rom: bool,
+    boot: BootSetup,
+    gdb_port: Option<u16>,
+    cheats: Vec<CheatCode>,
+    compat_palette: PaletteChoice,
+    sgb_border: Option<SgbBorder>,
+    strict_access: bool,
     input_receiver: Receiver<KeyInput>,
     updated_image_boolean: Arc<AtomicBool>,
     command_query_receiver: Receiver<DebugCommandQueries>,
@@ -214,12 +288,18 @@ async fn async_launch_game(
     global_is_debug: Arc<AtomicBool>,
     image_to_change: Arc<Mutex<Vec<u8>>>,
 ) -> Result<(), String> {
-    launch_game(rom_path, boot_rom, input_receiver, updated_image_boolean, command_query_receiver, debug_response_sender, global_is_debug, image_to_change)
+    launch_game(rom_path, boot, gdb_port, cheats, compat_palette, sgb_border, strict_access, input_receiver, updated_image_boolean, command_query_receiver, debug_response_sender, global_is_debug, image_to_change)
+        .inspect_err(|e| eprintln!("Cannot launch the game: {e}"))
 }
 
 fn launch_game(
     rom_path: String,
-    boot_rom: bool,
+    boot: BootSetup,
+    gdb_port: Option<u16>,
+    cheats: Vec<CheatCode>,
+    compat_palette: PaletteChoice,
+    sgb_border: Option<SgbBorder>, // Some to run the cart in a Super Game Boy
+    strict_access: bool,
     mut input_receiver: Receiver<KeyInput>,
     updated_image_boolean: Arc<AtomicBool>,
     command_query_receiver: Receiver<DebugCommandQueries>,
@@ -230,13 +310,13 @@ fn launch_game(
     let rom_data: Vec<u8> = read_rom(rom_path);
     let code = rom_data[0x0147];
     let mut app = match code {
-            0x00 | 0x08 | 0x09 => Ok(AnyGameApp::OnlyRom(GameApp::new( rom_data, command_query_receiver, debug_response_sender, global_is_debug, image_to_change, boot_rom)?)),
-            0x01 | 0x02 | 0x03 => Ok(AnyGameApp::Mbc1(GameApp::new( rom_data, command_query_receiver, debug_response_sender, global_is_debug, image_to_change, boot_rom)?)),
-            0x05 | 0x06 => Ok(AnyGameApp::Mbc2(GameApp::new( rom_data, command_query_receiver, debug_response_sender, global_is_debug, image_to_change, boot_rom)?)),
-            0x0F | 0x10 | 0x11 | 0x12 | 0x13 => Ok(AnyGameApp::Mbc3(GameApp::new( rom_data, command_query_receiver, debug_response_sender, global_is_debug, image_to_change, boot_rom)?)),
+            0x00 | 0x08 | 0x09 => Ok(AnyGameApp::OnlyRom(GameApp::new( rom_data, command_query_receiver, debug_response_sender, global_is_debug, image_to_change, &boot, gdb_port)?)),
+            0x01 | 0x02 | 0x03 => Ok(AnyGameApp::Mbc1(GameApp::new( rom_data, command_query_receiver, debug_response_sender, global_is_debug, image_to_change, &boot, gdb_port)?)),
+            0x05 | 0x06 => Ok(AnyGameApp::Mbc2(GameApp::new( rom_data, command_query_receiver, debug_response_sender, global_is_debug, image_to_change, &boot, gdb_port)?)),
+            0x0F | 0x10 | 0x11 | 0x12 | 0x13 => Ok(AnyGameApp::Mbc3(GameApp::new( rom_data, command_query_receiver, debug_response_sender, global_is_debug, image_to_change, &boot, gdb_port)?)),
+            0x19..=0x1E => Ok(AnyGameApp::Mbc5(GameApp::new( rom_data, command_query_receiver, debug_response_sender, global_is_debug, image_to_change, &boot, gdb_port)?)),
         /*
             0x0B | 0x0C | 0x0D => Ok(todo!()), // MMM01 pas dans le sujet
-            0x19 | 0x1A | 0x1B | 0x1C | 0x1D | 0x1E => Ok(todo!()), // Mbc5
             0x20 => Ok(todo!()), // Mbc6
             0x22 => Ok(todo!()),// MBC7+SENSOR+RUMBLE+RAM+BATTERY
         */
@@ -244,9 +324,25 @@ fn launch_game(
 
     }?;
 
-    if !boot_rom {
+    /*
+        A CGB always colors DMG carts, its boot ROM picks the palette itself. Without it the palette is
+        set before the boot state, which depends on the compatibility mode.
+    */
+    let compat_palette = match compat_palette {
+        PaletteChoice::Off if boot.model.is_cgb() => PaletteChoice::Auto,
+        choice => choice,
+    };
+    if !(boot.model.is_cgb() && boot.boot_rom.is_some()) {
+        app.set_compat_palette(compat_palette);
+    }
+    if let Some(border) = sgb_border {
+        app.set_sgb_border(border);
+    }
+    if boot.boot_rom.is_none() {
         app.simulate_boot_rom_effect()
     }
+    app.set_cheats(cheats);
+    app.set_strict_access(strict_access);
 
     let mut input = KeyInput::default();
 
@@ -271,6 +367,18 @@ pub enum DebugCommandQueries {
     GetRegisters,
     WatchAddress(u16),
     GetAddresses,
+    SetRegister(EditableRegister, u16),
+    SetFlag(CpuFlag, bool),
+    SetIme(bool),
+    WriteMemory(PokeTarget, u16, u8),
+    DumpMemory(MemoryView),
+    RecordPpuTimeline(bool),
+    SetLayers(LayerToggles),
+    RecordEvents(bool),
+    SetCheats(Vec<CheatCode>),
+    SnapshotRam,
+    SetCompatPalette(PaletteChoice),
+    SetStrictAccess(bool),
 }
 
 pub enum DebugResponse {
@@ -278,7 +386,422 @@ pub enum DebugResponse {
     InstructionsExecuted(usize),
     NextInstructions(Vec<u16>),
     AddressesWatched(WatchedAdresses),
-    Registers(u8, u8, u8, u8, u8, u8, u8, u16, u16, u16),
+    Registers(RegistersSnapshot),
+    CommandFailed(String),
+    CallStack(Vec<CallFrame>, Vec<TraceEvent>),
+    MemoryDump(MemoryDump),
+    Video(VideoSnapshot),
+    PpuTimeline(FrameTimeline),
+    IoRegisters(IoSnapshot),
+    Events(Vec<HardwareEvent>),
+    RamSnapshot(RamSnapshot),
+    Breakpoints(Vec<u16>),
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct RegistersSnapshot {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    pub ime: bool,
+    pub halted: bool,
+    pub rom_bank: u16, // bank mapped at 0x4000, picks which labels apply there
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditableRegister {
+    A,
+    F,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    AF,
+    BC,
+    DE,
+    HL,
+    SP,
+    PC,
+}
+
+impl EditableRegister {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EditableRegister::A => "A",
+            EditableRegister::F => "F",
+            EditableRegister::B => "B",
+            EditableRegister::C => "C",
+            EditableRegister::D => "D",
+            EditableRegister::E => "E",
+            EditableRegister::H => "H",
+            EditableRegister::L => "L",
+            EditableRegister::AF => "AF",
+            EditableRegister::BC => "BC",
+            EditableRegister::DE => "DE",
+            EditableRegister::HL => "HL",
+            EditableRegister::SP => "SP",
+            EditableRegister::PC => "PC",
+        }
+    }
+
+    pub fn is_16bit(&self) -> bool {
+        matches!(
+            self,
+            EditableRegister::AF
+                | EditableRegister::BC
+                | EditableRegister::DE
+                | EditableRegister::HL
+                | EditableRegister::SP
+                | EditableRegister::PC
+        )
+    }
+
+    pub fn read(&self, registers: &RegistersSnapshot) -> u16 {
+        let pair = |high: u8, low: u8| ((high as u16) << 8) | low as u16;
+        match self {
+            EditableRegister::A => registers.a as u16,
+            EditableRegister::F => registers.f as u16,
+            EditableRegister::B => registers.b as u16,
+            EditableRegister::C => registers.c as u16,
+            EditableRegister::D => registers.d as u16,
+            EditableRegister::E => registers.e as u16,
+            EditableRegister::H => registers.h as u16,
+            EditableRegister::L => registers.l as u16,
+            EditableRegister::AF => pair(registers.a, registers.f),
+            EditableRegister::BC => pair(registers.b, registers.c),
+            EditableRegister::DE => pair(registers.d, registers.e),
+            EditableRegister::HL => pair(registers.h, registers.l),
+            EditableRegister::SP => registers.sp,
+            EditableRegister::PC => registers.pc,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CpuFlag {
+    Zero,
+    Subtract,
+    HalfCarry,
+    Carry,
+}
+
+impl CpuFlag {
+    pub fn mask(&self) -> u8 {
+        match self {
+            CpuFlag::Zero => 0b1000_0000,
+            CpuFlag::Subtract => 0b0100_0000,
+            CpuFlag::HalfCarry => 0b0010_0000,
+            CpuFlag::Carry => 0b0001_0000,
+        }
+    }
+}
+
+// Where a poked byte goes: through the bus like a CPU write, or straight into a cartridge bank
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PokeTarget {
+    Bus,
+    RomBank(usize),
+    RamBank(usize),
+}
+
+// Value currently being edited inline in the debugger
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditTarget {
+    Register(EditableRegister),
+    Memory(u16),
+}
+
+#[derive(Debug, Clone)]
+pub struct PokeForm {
+    pub target: PokeTarget,
+    pub address: String,
+    pub value: String,
+}
+
+impl Default for PokeForm {
+    fn default() -> Self {
+        Self {
+            target: PokeTarget::Bus,
+            address: String::new(),
+            value: String::new(),
+        }
+    }
+}
+
+// Memory shown by the hex viewer, cartridge banks are read directly without going through the MBC
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemoryView {
+    Bus,
+    RomBank(usize),
+    RamBank(usize),
+    Vram,
+    Oam,
+    HRam,
+}
+
+impl MemoryView {
+    // Address of the first byte of the view once mapped on the bus
+    pub fn base_address(&self) -> u16 {
+        match self {
+            MemoryView::Bus | MemoryView::RomBank(0) => 0x0000,
+            MemoryView::RomBank(_) => 0x4000,
+            MemoryView::RamBank(_) => 0xA000,
+            MemoryView::Vram => 0x8000,
+            MemoryView::Oam => 0xFE00,
+            MemoryView::HRam => 0xFF80,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            MemoryView::Bus => "Bus".to_string(),
+            MemoryView::RomBank(bank) => format!("ROM bank {bank}"),
+            MemoryView::RamBank(bank) => format!("RAM bank {bank}"),
+            MemoryView::Vram => "VRAM".to_string(),
+            MemoryView::Oam => "OAM".to_string(),
+            MemoryView::HRam => "HRAM".to_string(),
+        }
+    }
+}
+
+pub struct MemoryDump {
+    pub view: MemoryView,
+    pub bytes: Vec<u8>,
+    pub rom_bank_count: usize,
+    pub ram_bank_count: usize,
+}
+
+impl Default for MemoryDump {
+    fn default() -> Self {
+        Self {
+            view: MemoryView::Bus,
+            bytes: Vec::new(),
+            rom_bank_count: 0,
+            ram_bank_count: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryViewerForm {
+    pub view: MemoryView,
+    pub goto_address: String,
+    pub search_pattern: String,
+}
+
+impl Default for MemoryViewerForm {
+    fn default() -> Self {
+        Self {
+            view: MemoryView::Bus,
+            goto_address: String::new(),
+            search_pattern: String::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryViewer {
+    pub form: MemoryViewerForm,
+    pub dump: MemoryDump,
+    pub previous_bytes: Vec<u8>, // dump of the same view before the last pause, to highlight changes
+    pub search_results: Vec<usize>,
+    pub search_length: usize,
+    pub scroll_to_row: Option<usize>,
+}
+
+// WRAM, HRAM and the mapped cartridge RAM, each region with the bus address of its first byte
+#[derive(Default, Clone)]
+pub struct RamSnapshot {
+    pub regions: Vec<(u16, Vec<u8>)>,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum SearchWidth {
+    #[default]
+    Byte,
+    Word,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum SearchComparison {
+    #[default]
+    Equal,
+    NotEqual,
+    Greater,
+    Less,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum SearchOperand {
+    #[default]
+    Previous,
+    Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchStep {
+    Start,
+    Filter(Option<u16>), // None compares with the previous snapshot
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct RamSearchForm {
+    pub width: SearchWidth,
+    pub comparison: SearchComparison,
+    pub operand: SearchOperand,
+    pub value: String,
+}
+
+#[derive(Default)]
+pub struct RamSearch {
+    pub form: RamSearchForm,
+    pub snapshot: Option<RamSnapshot>,
+    pub candidates: Vec<u16>,
+    pub pending: Option<SearchStep>, // what to do with the snapshot we are waiting for
+}
+
+// VRAM, OAM and the LCD registers as seen at the end of the last frame, for the graphics viewers
+pub struct VideoSnapshot {
+    pub vram: Vec<u8>,
+    pub oam: Vec<u8>,
+    pub lcdc: u8,
+    pub scy: u8,
+    pub scx: u8,
+    pub ly: u8,
+    pub bgp: u8,
+    pub obp0: u8,
+    pub obp1: u8,
+    pub wy: u8,
+    pub wx: u8,
+}
+
+impl Default for VideoSnapshot {
+    fn default() -> Self {
+        Self {
+            vram: vec![0; 0x2000],
+            oam: vec![0; 0xA0],
+            lcdc: 0,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            bgp: 0,
+            obp0: 0,
+            obp1: 0,
+            wy: 0,
+            wx: 0,
+        }
+    }
+}
+
+// 0xFF00-0xFF7F and IE, read without side effects
+pub struct IoSnapshot {
+    pub bytes: Vec<u8>,
+    pub ie: u8,
+}
+
+impl IoSnapshot {
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFFFF => self.ie,
+            0xFF00..=0xFF7F => self.bytes[(address - 0xFF00) as usize],
+            _ => 0xFF,
+        }
+    }
+}
+
+impl Default for IoSnapshot {
+    fn default() -> Self {
+        Self {
+            bytes: vec![0xFF; 0x80],
+            ie: 0,
+        }
+    }
+}
+
+// Tabs of the panel under the game screen
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum GraphicsTab {
+    #[default]
+    Memory,
+    Tiles,
+    Tilemaps,
+    Oam,
+    Timeline,
+    IoRegisters,
+    Events,
+    Cheats,
+    RamSearch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TilePalette {
+    #[default]
+    Bgp,
+    Obp0,
+    Obp1,
+}
+
+impl TilePalette {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TilePalette::Bgp => "BGP",
+            TilePalette::Obp0 => "OBP0",
+            TilePalette::Obp1 => "OBP1",
+        }
+    }
+
+    pub fn value(&self, video: &VideoSnapshot) -> u8 {
+        match self {
+            TilePalette::Bgp => video.bgp,
+            TilePalette::Obp0 => video.obp0,
+            TilePalette::Obp1 => video.obp1,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TileViewer {
+    pub palette: TilePalette,
+    pub texture: Option<TextureHandle>,
+}
+
+#[derive(Default)]
+pub struct TilemapViewer {
+    pub textures: [Option<TextureHandle>; 2],
+}
+
+#[derive(Default)]
+pub struct OamViewer {
+    pub scanline: u8, // line used to show which sprites the OAM scan keeps
+    pub texture: Option<TextureHandle>,
+}
+
+#[derive(Default)]
+pub struct PpuTimelineViewer {
+    pub recording: bool,
+    pub per_dot: bool,
+    pub frame: Option<FrameTimeline>,
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct CheatForm {
+    pub code: String,
+    pub description: String,
+}
+
+#[derive(Default)]
+pub struct EventViewer {
+    pub recording: bool,
+    pub events: Vec<HardwareEvent>,
 }
 
 pub struct WatchedAdresses {
@@ -296,6 +819,11 @@ pub struct CoreGameDevice {
     pub global_is_debug: Arc<AtomicBool>,
     texture_handler: Option<TextureHandle>,
     key_mapping: KeyMapping,
+    pub symbols: SymbolTable,
+    pub cheats: CheatFile,
+    pub settings: RomSettings,
+    pub sgb_border: Option<SgbBorder>,
+    pub strict_access: bool,
 }
 
 impl KeyMapping {
@@ -328,7 +856,10 @@ impl CoreGameDevice {
             let loaded_image;
             {
                 let image = self.actual_image.lock().unwrap();
-                loaded_image = ColorImage::from_rgb([ppu::WIN_SIZE_X, ppu::WIN_SIZE_Y], &image);
+                loaded_image = match self.sgb_border_image(&image) {
+                    Some(framed) => ColorImage::from_rgb([sgb::BORDER_WIDTH, sgb::BORDER_HEIGHT], &framed),
+                    None => ColorImage::from_rgb([ppu::WIN_SIZE_X, ppu::WIN_SIZE_Y], &image),
+                };
             }
             if let Some(th) = &mut self.texture_handler {
                 th.set(loaded_image, TextureOptions::NEAREST);
@@ -336,7 +867,10 @@ impl CoreGameDevice {
                 self.texture_handler = Some(ui.ctx().load_texture("gb_frame", loaded_image, TextureOptions::NEAREST));
             }
             if let Some(th) = &self.texture_handler {
-                let scaled_size = vec2(ppu::WIN_SIZE_X as f32 * 4., ppu::WIN_SIZE_Y as f32 * 4.);
+                // The border is larger, scaled a bit less so the game keeps about the same room
+                let [width, height] = th.size();
+                let scale = if width == ppu::WIN_SIZE_X { 4. } else { 3. };
+                let scaled_size = vec2(width as f32 * scale, height as f32 * scale);
                 let sized_texture = SizedTexture::new(th.id(), scaled_size);
                 self.sized_image = Some(sized_texture);
                 self.updated_image_boolean.store(false, Ordering::Relaxed);
@@ -344,6 +878,18 @@ impl CoreGameDevice {
         }
     }
 
+    // The game screen pasted in the middle of the SGB border
+    fn sgb_border_image(&self, frame: &[u8]) -> Option<Vec<u8>> {
+        let border = self.sgb_border.as_ref()?.lock().unwrap();
+        let mut framed = border.as_ref()?.clone();
+
+        for (y, line) in frame.chunks_exact(ppu::WIN_SIZE_X * 3).enumerate() {
+            let offset = ((sgb::SCREEN_Y + y) * sgb::BORDER_WIDTH + sgb::SCREEN_X) * 3;
+            framed[offset..offset + line.len()].copy_from_slice(line);
+        }
+        Some(framed)
+    }
+
     pub fn capture_input(&self, ui: &mut egui::Ui) -> KeyInput {
         let keys_down= ui.ctx().input(|i| {
             i.keys_down.clone()
@@ -361,13 +907,34 @@ impl CoreGameDevice {
         let global_is_debug = Arc::new(AtomicBool::new(false));
         let actual_image = Arc::new(Mutex::new(vec![0; 160 * 144 * 3]));
         let texture_handler = None;
+        let symbols = SymbolTable::load_for_rom(&options.rom_path).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            SymbolTable::default()
+        });
+        let settings = RomSettings::load_for_rom(&options.rom_path);
+        let sgb_border = options.model.is_sgb().then(|| Arc::new(Mutex::new(None)));
+        let boot = BootSetup {
+            model: options.model,
+            boot_rom: options.boot_rom.then(|| options.model.boot_rom_path(&options.boot_rom_dir)),
+        };
+        let mut cheats = CheatFile::load_for_rom(&options.rom_path);
+        for code in &options.cheats {
+            if let Err(e) = cheats.add(code, "command line") {
+                eprintln!("Skipping cheat: {e}");
+            }
+        }
         Self {
             input_sender,
             command_query_sender,
             debug_response_receiver,
             handler: tokio::spawn(async_launch_game(
                 options.rom_path,
-                options.boot_rom,
+                boot,
+                options.gdb_port,
+                cheats.enabled_codes(),
+                settings.compat_palette(),
+                sgb_border.clone(),
+                options.strict_access,
                 input_receiver,
                 updated_image_boolean.clone(),
                 command_query_receiver,
@@ -381,6 +948,11 @@ impl CoreGameDevice {
             global_is_debug,
             sized_image: None,
             key_mapping: KeyMapping::default(),
+            symbols,
+            cheats,
+            settings,
+            sgb_border,
+            strict_access: options.strict_access,
         }
     }
 }
@@ -421,13 +993,30 @@ pub struct DebuggingDevice {
     */
     pub next_instructions: Vec<u16>,
     pub watched_adress: WatchedAdresses,
-    pub registers: (u8, u8, u8, u8, u8, u8, u8, u16, u16, u16),
+    pub registers: RegistersSnapshot,
     pub is_step: bool,
+    pub call_stack: Vec<CallFrame>,
+    pub jump_history: Vec<TraceEvent>,
+    pub breakpoints: Vec<u16>, // set from gdb, the panel only lists them
     pub watched_address_value: u16,
     pub nb_instruction: usize,
 
     pub error_message: Option<String>,
     pub hex_string: String,
+    pub editing: Option<(EditTarget, String)>,
+    pub poke_form: PokeForm,
+    pub memory_viewer: MemoryViewer,
+    pub video: VideoSnapshot,
+    pub graphics_tab: GraphicsTab,
+    pub tile_viewer: TileViewer,
+    pub tilemap_viewer: TilemapViewer,
+    pub oam_viewer: OamViewer,
+    pub timeline_viewer: PpuTimelineViewer,
+    pub layers: LayerToggles,
+    pub io: IoSnapshot,
+    pub event_viewer: EventViewer,
+    pub cheat_form: CheatForm,
+    pub ram_search: RamSearch,
 }
 
 impl Default for AppState {
@@ -1,21 +1,66 @@
+mod cheat_panel;
 mod display;
+mod event_viewer;
+mod io_viewer;
+mod memory_viewer;
+mod oam_viewer;
+mod ram_search;
+mod tile_viewer;
+mod timeline_viewer;
+mod tilemap_viewer;
 
+use crate::cpu::call_stack::{CallFrame, TraceEvent};
 use crate::debugger::debbuger;
-use crate::gui::{AppState, DebuggingDevice, WatchedAdresses};
+use crate::file::SavedCheat;
+use crate::mmu::compat_palettes::PaletteChoice;
+use crate::ppu::layers::LayerToggles;
+use crate::symbols::SymbolTable;
+use crate::gui::{
+    AppState, CheatForm, CpuFlag, DebuggingDevice, EditTarget, EventViewer, GraphicsTab, IoSnapshot, MemoryViewer, MemoryViewerForm,
+    PokeForm, PokeTarget, PpuTimelineViewer, RamSearch, RamSearchForm, RegistersSnapshot, SearchStep, SearchWidth, TilePalette, VideoSnapshot,
+    WatchedAdresses,
+};
 
 use eframe::egui::load::SizedTexture;
 
+use cheat_panel::CheatActions;
 use display::display_interface;
+use memory_viewer::MemoryViewerActions;
+use ram_search::RamSearchActions;
+use timeline_viewer::TimelineActions;
 
 struct DebuggingDataIn<'a> {
     is_step: bool,
     watched_address: &'a WatchedAdresses,
-    registers: &'a (u8, u8, u8, u8, u8, u8, u8, u16, u16, u16),
+    registers: &'a RegistersSnapshot,
     nb_instruction: u8,
     next_instructions: &'a Vec<u16>,
+    call_stack: &'a [CallFrame],
+    jump_history: &'a [TraceEvent],
+    breakpoints: &'a [u16],
     hex_string: &'a String,
     error_message: Option<&'a String>,
     sized_texture: Option<SizedTexture>,
+    editing: Option<&'a (EditTarget, String)>,
+    poke_form: &'a PokeForm,
+    memory_viewer: &'a MemoryViewer,
+    symbols: &'a SymbolTable,
+    graphics_tab: GraphicsTab,
+    video: &'a VideoSnapshot,
+    tile_palette: TilePalette,
+    tile_texture: Option<SizedTexture>,
+    tilemap_textures: Option<[SizedTexture; 2]>,
+    oam_scanline: u8,
+    oam_texture: Option<SizedTexture>,
+    timeline: &'a PpuTimelineViewer,
+    layers: LayerToggles,
+    compat_palette: PaletteChoice,
+    strict_access: bool,
+    io: &'a IoSnapshot,
+    event_viewer: &'a EventViewer,
+    cheats: &'a [SavedCheat],
+    cheat_form: &'a CheatForm,
+    ram_search: &'a RamSearch,
 }
 
 #[derive(Debug)]
@@ -28,6 +73,27 @@ struct DebuggingDataOut {
     nb_instruction_requested: u8,
     hex_string: String,
     register_new_addr: bool,
+    editing: Option<(EditTarget, String)>,
+    committed_edit: Option<(EditTarget, String)>,
+    flag_toggled: Option<(CpuFlag, bool)>,
+    ime_toggled: Option<bool>,
+    poke_form: PokeForm,
+    poke_submitted: bool,
+    memory_form: MemoryViewerForm,
+    memory_actions: MemoryViewerActions,
+    graphics_tab: GraphicsTab,
+    tile_palette: TilePalette,
+    oam_scanline: u8,
+    timeline_actions: Option<TimelineActions>,
+    layers: LayerToggles,
+    compat_palette: PaletteChoice,
+    strict_access: bool,
+    io_writes: Vec<(u16, u8)>,
+    event_recording: Option<bool>,
+    cheat_form: CheatForm,
+    cheat_actions: Option<CheatActions>,
+    ram_search_form: RamSearchForm,
+    ram_search_actions: RamSearchActions,
 }
 
 enum OutState {
@@ -58,11 +124,206 @@ impl DebuggingDevice {
             self.request_registers();
         }
 
+        self.editing = data.editing;
+        if let Some((target, text)) = data.committed_edit {
+            self.commit_edit(target, &text);
+        }
+
+        if let Some((flag, value)) = data.flag_toggled {
+            self.request_set_flag(flag, value);
+        }
+
+        if let Some(ime) = data.ime_toggled {
+            self.request_set_ime(ime);
+        }
+
+        self.poke_form = data.poke_form;
+        if data.poke_submitted {
+            self.submit_poke();
+        }
+
+        self.update_memory_viewer(data.memory_form, data.memory_actions);
+        self.graphics_tab = data.graphics_tab;
+        self.tile_viewer.palette = data.tile_palette;
+        self.oam_viewer.scanline = data.oam_scanline;
+        for (address, value) in data.io_writes {
+            self.request_write_memory(PokeTarget::Bus, address, value);
+        }
+
+        if let Some(recording) = data.event_recording
+            && recording != self.event_viewer.recording
+        {
+            self.event_viewer.recording = recording;
+            self.event_viewer.events.clear();
+            self.request_event_recording(recording);
+        }
+
+        self.cheat_form = data.cheat_form;
+        if let Some(actions) = data.cheat_actions {
+            self.update_cheats(actions);
+        }
+
+        self.ram_search.form = data.ram_search_form;
+        self.update_ram_search(data.ram_search_actions);
+
+        if data.layers != self.layers {
+            self.layers = data.layers;
+            self.request_layers(self.layers);
+        }
+
+        if data.compat_palette != self.core_game.settings.compat_palette() {
+            self.core_game.settings.set_compat_palette(data.compat_palette);
+            self.request_compat_palette(data.compat_palette);
+        }
+
+        if data.strict_access != self.core_game.strict_access {
+            self.core_game.strict_access = data.strict_access;
+            self.request_strict_access(data.strict_access);
+        }
+
+        if let Some(timeline) = data.timeline_actions {
+            if timeline.recording != self.timeline_viewer.recording {
+                self.request_ppu_timeline(timeline.recording);
+                self.timeline_viewer.frame = None;
+            }
+            self.timeline_viewer.recording = timeline.recording;
+            self.timeline_viewer.per_dot = timeline.per_dot;
+        }
+
         self.hex_string = data.hex_string;
-        if let Ok(result) = u16::from_str_radix(self.hex_string.as_ref(), 16) {}
+        if data.register_new_addr {
+            match self.core_game.symbols.parse_address(&self.hex_string) {
+                Ok(address) => self.request_watch_address(address),
+                Err(message) => self.error_message = Some(message),
+            }
+        }
         OutState::Debugging
     }
 
+    fn commit_edit(&mut self, target: EditTarget, text: &str) {
+        let text = text.trim().trim_start_matches("0x");
+        match target {
+            EditTarget::Register(register) => match u16::from_str_radix(text, 16) {
+                Ok(value) if register.is_16bit() || value <= 0xFF => {
+                    self.request_set_register(register, value)
+                }
+                _ => self.error_message = Some(format!("Invalid value for {}: {text}", register.name())),
+            },
+            EditTarget::Memory(address) => match u8::from_str_radix(text, 16) {
+                Ok(value) => self.request_write_memory(PokeTarget::Bus, address, value),
+                Err(_) => self.error_message = Some(format!("Invalid byte for 0x{address:04X}: {text}")),
+            },
+        }
+    }
+
+    fn update_memory_viewer(&mut self, form: MemoryViewerForm, actions: MemoryViewerActions) {
+        let view_changed = form.view != self.memory_viewer.form.view;
+        self.memory_viewer.form = form;
+        self.memory_viewer.scroll_to_row = None;
+
+        if view_changed {
+            self.memory_viewer.search_results.clear();
+        }
+        if view_changed || actions.refresh_clicked {
+            self.request_memory_dump(self.memory_viewer.form.view);
+        }
+
+        let result = if actions.goto_clicked {
+            self.memory_viewer.goto(&self.core_game.symbols)
+        } else if actions.search_clicked {
+            self.memory_viewer.search()
+        } else {
+            Ok(())
+        };
+        if let Err(message) = result {
+            self.error_message = Some(message);
+        }
+
+        if let Some(offset) = actions.result_clicked {
+            self.memory_viewer.scroll_to_offset(offset);
+        }
+    }
+
+    fn update_ram_search(&mut self, actions: RamSearchActions) {
+        if actions.new_search {
+            self.ram_search.pending = Some(SearchStep::Start);
+            self.request_ram_snapshot();
+        } else if actions.filter {
+            match self.ram_search.parse_value() {
+                Ok(value) => {
+                    self.ram_search.pending = Some(SearchStep::Filter(value));
+                    self.request_ram_snapshot();
+                }
+                Err(message) => self.error_message = Some(message),
+            }
+        }
+
+        if let Some(address) = actions.watch {
+            self.request_watch_address(address);
+        }
+        if let Some(address) = actions.freeze {
+            self.freeze_address(address);
+        }
+    }
+
+    // Freezing is a GameShark code on the current bank with the value from the last snapshot
+    fn freeze_address(&mut self, address: u16) {
+        let Some(snapshot) = &self.ram_search.snapshot else {
+            return;
+        };
+        let width = self.ram_search.form.width;
+        let Some(value) = snapshot.read(address, width) else {
+            return;
+        };
+
+        let bytes = match width {
+            SearchWidth::Byte => vec![(address, value as u8)],
+            SearchWidth::Word => vec![(address, value as u8), (address + 1, (value >> 8) as u8)],
+        };
+        for (address, byte) in bytes {
+            let [low, high] = address.to_le_bytes();
+            let code = format!("01{byte:02X}{low:02X}{high:02X}");
+            if let Err(message) = self.core_game.cheats.add(&code, &format!("RAM search 0x{address:04X}")) {
+                self.error_message = Some(message);
+            }
+        }
+        self.core_game.cheats.persist();
+        self.request_cheats(self.core_game.cheats.enabled_codes());
+    }
+
+    fn update_cheats(&mut self, actions: CheatActions) {
+        let cheats = &mut self.core_game.cheats;
+        if actions.add_clicked {
+            match cheats.add(&self.cheat_form.code, &self.cheat_form.description) {
+                Ok(()) => self.cheat_form = CheatForm::default(),
+                Err(message) => {
+                    self.error_message = Some(message);
+                    return;
+                }
+            }
+        } else if let Some((index, enabled)) = actions.toggled {
+            cheats.cheats[index].enabled = enabled;
+        } else if let Some(index) = actions.removed {
+            cheats.cheats.remove(index);
+        } else {
+            return;
+        }
+
+        cheats.persist();
+        self.request_cheats(self.core_game.cheats.enabled_codes());
+    }
+
+    fn submit_poke(&mut self) {
+        let address = self.core_game.symbols.parse_address(&self.poke_form.address);
+        let value = u8::from_str_radix(self.poke_form.value.trim(), 16);
+
+        match (address, value) {
+            (Ok(address), Ok(value)) => self.request_write_memory(self.poke_form.target, address, value),
+            (Err(message), _) => self.error_message = Some(message),
+            (_, Err(_)) => self.error_message = Some(format!("Invalid value: {}", self.poke_form.value)),
+        }
+    }
+
     pub fn debug_view(mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) -> AppState {
         let debugging_data_in = self.update_and_get_debugging_data(ui);
         let actions_to_perform = display_interface(ui, _frame, debugging_data_in);
@@ -74,6 +335,12 @@ impl DebuggingDevice {
     fn update_and_get_debugging_data(&mut self, ui: &mut egui::Ui) -> DebuggingDataIn<'_> {
         self.core_game.update_and_size_image(ui);
         debbuger::update_info_struct(self);
+        let tile_texture = (self.graphics_tab == GraphicsTab::Tiles)
+            .then(|| self.tile_viewer.update_texture(ui.ctx(), &self.video));
+        let tilemap_textures = (self.graphics_tab == GraphicsTab::Tilemaps)
+            .then(|| self.tilemap_viewer.update_textures(ui.ctx(), &self.video));
+        let oam_texture = (self.graphics_tab == GraphicsTab::Oam)
+            .then(|| self.oam_viewer.update_texture(ui.ctx(), &self.video));
 
         let error_message = if let Some(value) = &self.error_message {
             Some(value)
@@ -87,8 +354,31 @@ impl DebuggingDevice {
             registers: &self.registers,
             nb_instruction: self.nb_instruction as u8,
             next_instructions: &self.next_instructions,
+            call_stack: &self.call_stack,
+            jump_history: &self.jump_history,
+            breakpoints: &self.breakpoints,
             error_message,
             hex_string: &self.hex_string,
+            editing: self.editing.as_ref(),
+            poke_form: &self.poke_form,
+            memory_viewer: &self.memory_viewer,
+            symbols: &self.core_game.symbols,
+            graphics_tab: self.graphics_tab,
+            video: &self.video,
+            tile_palette: self.tile_viewer.palette,
+            tile_texture,
+            tilemap_textures,
+            oam_scanline: self.oam_viewer.scanline,
+            oam_texture,
+            timeline: &self.timeline_viewer,
+            layers: self.layers,
+            compat_palette: self.core_game.settings.compat_palette(),
+            strict_access: self.core_game.strict_access,
+            io: &self.io,
+            event_viewer: &self.event_viewer,
+            cheats: &self.core_game.cheats.cheats,
+            cheat_form: &self.cheat_form,
+            ram_search: &self.ram_search,
         }
     }
 
@@ -0,0 +1,60 @@
+use crate::file::SavedCheat;
+use crate::gui::CheatForm;
+use crate::mmu::cheats::CheatCode;
+
+use eframe::egui::{Button, Checkbox, Color32, Grid, RichText, TextEdit, Ui};
+
+#[derive(Debug, Default)]
+pub struct CheatActions {
+    pub add_clicked: bool,
+    pub toggled: Option<(usize, bool)>,
+    pub removed: Option<usize>,
+}
+
+pub fn cheat_panel(ui: &mut Ui, cheats: &[SavedCheat], form: &mut CheatForm) -> CheatActions {
+    let mut actions = CheatActions::default();
+
+    ui.horizontal(|ui| {
+        ui.label("Code:");
+        ui.add(TextEdit::singleline(&mut form.code).hint_text("ABC-DEF-GHI / 01VVAAAA").desired_width(140.0));
+        ui.label("Description:");
+        ui.add(TextEdit::singleline(&mut form.description).desired_width(200.0));
+        actions.add_clicked = ui.add_enabled(!form.code.trim().is_empty(), Button::new("Add")).clicked();
+    });
+    ui.label(
+        RichText::new("Game Genie codes patch ROM reads, GameShark codes write RAM on every VBlank.")
+            .color(Color32::from_rgb(150, 150, 150)),
+    );
+    ui.separator();
+
+    if cheats.is_empty() {
+        ui.label(RichText::new("No cheat for this ROM").color(Color32::from_rgb(150, 150, 150)));
+        return actions;
+    }
+
+    Grid::new("cheat_grid").num_columns(5).striped(true).spacing([12.0, 4.0]).show(ui, |ui| {
+        for header in ["On", "Code", "Effect", "Description", ""] {
+            ui.label(RichText::new(header).strong().color(Color32::from_rgb(255, 200, 100)));
+        }
+        ui.end_row();
+
+        for (index, cheat) in cheats.iter().enumerate() {
+            let mut enabled = cheat.enabled;
+            if ui.add(Checkbox::without_text(&mut enabled)).changed() {
+                actions.toggled = Some((index, enabled));
+            }
+            ui.label(RichText::new(&cheat.code).monospace().color(Color32::from_rgb(100, 200, 255)));
+            match CheatCode::parse(&cheat.code) {
+                Ok(code) => ui.label(RichText::new(code.describe()).monospace()),
+                Err(e) => ui.label(RichText::new(e).color(Color32::from_rgb(255, 100, 100))),
+            };
+            ui.label(&cheat.description);
+            if ui.small_button("🗑").on_hover_text("Remove").clicked() {
+                actions.removed = Some(index);
+            }
+            ui.end_row();
+        }
+    });
+
+    actions
+}
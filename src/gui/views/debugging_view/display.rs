@@ -1,17 +1,51 @@
+use crate::cpu::call_stack::{CallKind, TraceKind};
 use crate::gui::common::display_game;
+use crate::gui::{CpuFlag, EditTarget, EditableRegister, GraphicsTab, PokeForm, PokeTarget};
+use crate::mmu::compat_palettes::PaletteChoice;
+use crate::ppu::layers::LayerToggles;
 
 use eframe::egui::{
-    Align, Button, Color32, DragValue, Grid, Layout, RichText, ScrollArea, Panel,
-    TextEdit, Ui,
+    Align, Button, Checkbox, Color32, ComboBox, DragValue, Grid, Key, Label, Layout, RichText,
+    ScrollArea, Panel, Sense, TextEdit, Ui,
 };
 
 use super::{DebuggingDataIn, DebuggingDataOut};
+use super::cheat_panel::cheat_panel;
+use super::event_viewer::event_viewer;
+use super::io_viewer::io_viewer;
+use super::memory_viewer::{memory_viewer, MemoryViewerActions};
+use super::oam_viewer::oam_viewer;
+use super::ram_search::{ram_search, RamSearchActions};
+use super::tile_viewer::tile_viewer;
+use super::timeline_viewer::timeline_viewer;
+use super::tilemap_viewer::tilemap_viewer;
 
 pub fn display_interface(
     ui: &mut egui::Ui,
     _frame: &mut eframe::Frame,
     data: DebuggingDataIn,
 ) -> DebuggingDataOut {
+    let mut editing = data.editing.cloned();
+    let mut committed_edit = None;
+    let mut flag_toggled = None;
+    let mut ime_toggled = None;
+    let mut poke_form = data.poke_form.clone();
+    let mut poke_submitted = false;
+    let mut memory_form = data.memory_viewer.form.clone();
+    let mut graphics_tab = data.graphics_tab;
+    let mut tile_palette = data.tile_palette;
+    let mut oam_scanline = data.oam_scanline;
+    let mut timeline_actions = None;
+    let mut layers = data.layers;
+    let mut compat_palette = data.compat_palette;
+    let mut strict_access = data.strict_access;
+    let mut io_writes = Vec::new();
+    let mut event_recording = None;
+    let mut cheat_form = data.cheat_form.clone();
+    let mut cheat_actions = None;
+    let mut ram_search_form = data.ram_search.form.clone();
+    let mut ram_search_actions = RamSearchActions::default();
+
     let (
         close_btn_clicked,
         step_mode_btn_clkd,
@@ -57,7 +91,9 @@ pub fn display_interface(
                     let refresh_register_clicked: bool = ui
                         .group(|inner_ui| {
                             inner_ui.label(RichText::new("Registers").strong());
-                            get_registers(inner_ui, &data)
+                            let refresh_clicked = get_registers(inner_ui, &data, &mut editing, &mut committed_edit);
+                            (flag_toggled, ime_toggled) = cpu_flags(inner_ui, &data);
+                            refresh_clicked
                         })
                         .inner;
 
@@ -70,13 +106,55 @@ pub fn display_interface(
 
                     ui.add_space(8.0);
 
+                    ui.group(|inner_ui| {
+                        inner_ui.label(RichText::new("Call Stack").strong());
+                        call_stack(inner_ui, &data);
+                    });
+
+                    ui.add_space(8.0);
+
+                    ui.group(|inner_ui| {
+                        inner_ui.label(RichText::new("Breakpoints").strong());
+                        breakpoints(inner_ui, &data);
+                    });
+
+                    ui.add_space(8.0);
+
                     let (hex_string, register_new_addr) = ui
                         .group(|inner_ui| {
                             inner_ui.label(RichText::new("Memory Watch").strong());
-                            watch_address(inner_ui, &data)
+                            watch_address(inner_ui, &data, &mut editing, &mut committed_edit)
                         })
                         .inner;
 
+                    ui.add_space(8.0);
+
+                    ui.group(|inner_ui| {
+                        inner_ui.label(RichText::new("Memory Poke").strong());
+                        poke_submitted = poke_memory(inner_ui, &mut poke_form);
+                    });
+
+                    ui.add_space(8.0);
+
+                    ui.group(|inner_ui| {
+                        inner_ui.label(RichText::new("Layers").strong());
+                        layer_toggles(inner_ui, &mut layers);
+                    });
+
+                    ui.add_space(8.0);
+
+                    ui.group(|inner_ui| {
+                        inner_ui.label(RichText::new("DMG Colors").strong());
+                        compat_palette_picker(inner_ui, &mut compat_palette);
+                    });
+
+                    ui.add_space(8.0);
+
+                    ui.group(|inner_ui| {
+                        inner_ui.label(RichText::new("Accuracy").strong());
+                        inner_ui.checkbox(&mut strict_access, "Block VRAM/OAM during PPU modes and the bus during OAM DMA");
+                    });
+
                     (
                         close_button_is_clicked,
                         step_mode_button_clicked,
@@ -92,6 +170,72 @@ pub fn display_interface(
         })
         .inner;
 
+    let memory_actions = Panel::bottom("memory_viewer_panel")
+        .resizable(true)
+        .default_size(280.0)
+        .min_size(120.0)
+        .show_inside(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut graphics_tab, GraphicsTab::Memory, RichText::new("Memory Viewer").strong());
+                ui.selectable_value(&mut graphics_tab, GraphicsTab::Tiles, RichText::new("Tiles").strong());
+                ui.selectable_value(&mut graphics_tab, GraphicsTab::Tilemaps, RichText::new("Tilemaps").strong());
+                ui.selectable_value(&mut graphics_tab, GraphicsTab::Oam, RichText::new("OAM").strong());
+                ui.selectable_value(&mut graphics_tab, GraphicsTab::Timeline, RichText::new("PPU Timeline").strong());
+                ui.selectable_value(&mut graphics_tab, GraphicsTab::IoRegisters, RichText::new("I/O Registers").strong());
+                ui.selectable_value(&mut graphics_tab, GraphicsTab::Events, RichText::new("Events").strong());
+                ui.selectable_value(&mut graphics_tab, GraphicsTab::Cheats, RichText::new("Cheats").strong());
+                ui.selectable_value(&mut graphics_tab, GraphicsTab::RamSearch, RichText::new("RAM Search").strong());
+            });
+            ui.separator();
+
+            let graphics_scroll = ScrollArea::both()
+                .id_salt("graphics_scroll")
+                .auto_shrink([false, false]);
+            // textures are only rendered for the tab that was open when the frame started
+            match graphics_tab {
+                GraphicsTab::Memory => memory_viewer(ui, data.memory_viewer, &mut memory_form),
+                GraphicsTab::Tiles => {
+                    if let Some(texture) = data.tile_texture {
+                        graphics_scroll.show(ui, |ui| tile_viewer(ui, texture, data.video, &mut tile_palette));
+                    }
+                    MemoryViewerActions::default()
+                }
+                GraphicsTab::Tilemaps => {
+                    if let Some(textures) = data.tilemap_textures {
+                        graphics_scroll.show(ui, |ui| tilemap_viewer(ui, textures, data.video));
+                    }
+                    MemoryViewerActions::default()
+                }
+                GraphicsTab::Oam => {
+                    if let Some(texture) = data.oam_texture {
+                        graphics_scroll.show(ui, |ui| oam_viewer(ui, texture, data.video, &mut oam_scanline, &mut layers));
+                    }
+                    MemoryViewerActions::default()
+                }
+                GraphicsTab::Timeline => {
+                    timeline_actions = Some(timeline_viewer(ui, data.timeline));
+                    MemoryViewerActions::default()
+                }
+                GraphicsTab::IoRegisters => {
+                    graphics_scroll.show(ui, |ui| io_writes = io_viewer(ui, data.io));
+                    MemoryViewerActions::default()
+                }
+                GraphicsTab::Events => {
+                    event_recording = Some(event_viewer(ui, data.event_viewer));
+                    MemoryViewerActions::default()
+                }
+                GraphicsTab::RamSearch => {
+                    ram_search_actions = ram_search(ui, data.ram_search, &mut ram_search_form);
+                    MemoryViewerActions::default()
+                }
+                GraphicsTab::Cheats => {
+                    graphics_scroll.show(ui, |ui| cheat_actions = Some(cheat_panel(ui, data.cheats, &mut cheat_form)));
+                    MemoryViewerActions::default()
+                }
+            }
+        })
+        .inner;
+
     if let Some(sized_texture) = data.sized_texture {
         display_game(sized_texture, ui);
     }
@@ -105,6 +249,27 @@ pub fn display_interface(
         nb_instruction_requested,
         hex_string,
         register_new_addr,
+        editing,
+        committed_edit,
+        flag_toggled,
+        ime_toggled,
+        poke_form,
+        poke_submitted,
+        memory_form,
+        memory_actions,
+        graphics_tab,
+        tile_palette,
+        oam_scanline,
+        timeline_actions,
+        layers,
+        compat_palette,
+        strict_access,
+        io_writes,
+        event_recording,
+        cheat_form,
+        cheat_actions,
+        ram_search_form,
+        ram_search_actions,
     }
 }
 
@@ -121,7 +286,50 @@ fn step_button(ui: &mut Ui) -> bool {
     ui.button("Next Step").clicked()
 }
 
-fn get_registers(ui: &mut Ui, debugging_data: &DebuggingDataIn) -> bool {
+// Shows a value that turns into a text field when clicked, Enter commits it and anything else cancels
+fn editable_value(
+    ui: &mut Ui,
+    target: EditTarget,
+    text: String,
+    char_limit: usize,
+    editing: &mut Option<(EditTarget, String)>,
+    committed_edit: &mut Option<(EditTarget, String)>,
+) {
+    match editing {
+        Some((editing_target, buffer)) if *editing_target == target => {
+            let response = ui.add(
+                TextEdit::singleline(buffer)
+                    .desired_width(50.0)
+                    .char_limit(char_limit),
+            );
+            if response.lost_focus() {
+                if ui.input(|i| i.key_pressed(Key::Enter)) {
+                    *committed_edit = editing.take();
+                } else {
+                    *editing = None;
+                }
+            } else if !response.has_focus() {
+                response.request_focus();
+            }
+        }
+        _ => {
+            let clicked = ui
+                .add(Label::new(RichText::new(format!("0x{text}")).monospace()).sense(Sense::click()))
+                .on_hover_text("Click to edit")
+                .clicked();
+            if clicked {
+                *editing = Some((target, text));
+            }
+        }
+    }
+}
+
+fn get_registers(
+    ui: &mut Ui,
+    debugging_data: &DebuggingDataIn,
+    editing: &mut Option<(EditTarget, String)>,
+    committed_edit: &mut Option<(EditTarget, String)>,
+) -> bool {
     // Button to refresh registers
     let refresh_button_is_clicked = ui
         .horizontal(|ui| ui.button("🔄 Refresh Registers").clicked())
@@ -143,18 +351,29 @@ fn get_registers(ui: &mut Ui, debugging_data: &DebuggingDataIn) -> bool {
             ui.end_row();
 
             let registers_8bit = [
-                ("A", debugging_data.registers.0),
-                ("B", debugging_data.registers.1),
-                ("C", debugging_data.registers.2),
-                ("D", debugging_data.registers.3),
-                ("E", debugging_data.registers.4),
-                ("H", debugging_data.registers.5),
+                EditableRegister::A,
+                EditableRegister::F,
+                EditableRegister::B,
+                EditableRegister::C,
+                EditableRegister::D,
+                EditableRegister::E,
+                EditableRegister::H,
+                EditableRegister::L,
             ];
 
-            for (name, value) in registers_8bit.iter() {
-                ui.label(RichText::new(*name).color(Color32::from_rgb(100, 200, 255)));
+            for register in registers_8bit {
+                let value = register.read(debugging_data.registers);
 
-                ui.label(RichText::new(format!("0x{:02X}", value)).monospace());
+                ui.label(RichText::new(register.name()).color(Color32::from_rgb(100, 200, 255)));
+
+                editable_value(
+                    ui,
+                    EditTarget::Register(register),
+                    format!("{:02X}", value),
+                    2,
+                    editing,
+                    committed_edit,
+                );
 
                 ui.label(
                     RichText::new(format!("{:3}", value))
@@ -179,16 +398,27 @@ fn get_registers(ui: &mut Ui, debugging_data: &DebuggingDataIn) -> bool {
 
             // 16-bit registers
             let registers_16bit = [
-                ("L", debugging_data.registers.6 as u16),
-                ("HL", debugging_data.registers.7),
-                ("SP", debugging_data.registers.8),
-                ("PC", debugging_data.registers.9),
+                EditableRegister::AF,
+                EditableRegister::BC,
+                EditableRegister::DE,
+                EditableRegister::HL,
+                EditableRegister::SP,
+                EditableRegister::PC,
             ];
 
-            for (name, value) in registers_16bit.iter() {
-                ui.label(RichText::new(*name).color(Color32::from_rgb(255, 200, 100)));
+            for register in registers_16bit {
+                let value = register.read(debugging_data.registers);
+
+                ui.label(RichText::new(register.name()).color(Color32::from_rgb(255, 200, 100)));
 
-                ui.label(RichText::new(format!("0x{:04X}", value)).monospace());
+                editable_value(
+                    ui,
+                    EditTarget::Register(register),
+                    format!("{:04X}", value),
+                    4,
+                    editing,
+                    committed_edit,
+                );
 
                 ui.label(
                     RichText::new(format!("{:5}", value))
@@ -208,6 +438,132 @@ fn get_registers(ui: &mut Ui, debugging_data: &DebuggingDataIn) -> bool {
     refresh_button_is_clicked
 }
 
+fn cpu_flags(ui: &mut Ui, debugging_data: &DebuggingDataIn) -> (Option<(CpuFlag, bool)>, Option<bool>) {
+    let mut flag_toggled = None;
+    let mut ime_toggled = None;
+
+    ui.add_space(8.0);
+    ui.horizontal(|ui| {
+        let flags = [
+            ("Z", CpuFlag::Zero),
+            ("N", CpuFlag::Subtract),
+            ("H", CpuFlag::HalfCarry),
+            ("C", CpuFlag::Carry),
+        ];
+
+        for (name, flag) in flags {
+            let mut is_set = debugging_data.registers.f & flag.mask() != 0;
+            if ui.add(Checkbox::new(&mut is_set, name)).changed() {
+                flag_toggled = Some((flag, is_set));
+            }
+        }
+
+        ui.separator();
+
+        let mut ime = debugging_data.registers.ime;
+        if ui.add(Checkbox::new(&mut ime, "IME")).changed() {
+            ime_toggled = Some(ime);
+        }
+
+        if debugging_data.registers.halted {
+            ui.label(RichText::new("HALTED").color(Color32::YELLOW));
+        }
+    });
+
+    (flag_toggled, ime_toggled)
+}
+
+fn poke_memory(ui: &mut Ui, form: &mut PokeForm) -> bool {
+    ui.horizontal(|ui| {
+        let selected_text = match form.target {
+            PokeTarget::Bus => "Bus".to_string(),
+            PokeTarget::RomBank(bank) => format!("ROM bank {bank}"),
+            PokeTarget::RamBank(bank) => format!("RAM bank {bank}"),
+        };
+
+        ComboBox::from_id_salt("poke_target")
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut form.target, PokeTarget::Bus, "Bus");
+                ui.selectable_value(&mut form.target, PokeTarget::RomBank(0), "ROM bank");
+                ui.selectable_value(&mut form.target, PokeTarget::RamBank(0), "RAM bank");
+            });
+
+        match &mut form.target {
+            PokeTarget::RomBank(bank) | PokeTarget::RamBank(bank) => {
+                ui.add(DragValue::new(bank).range(0..=511).prefix("#"));
+            }
+            PokeTarget::Bus => {}
+        }
+    });
+
+    ui.horizontal(|ui| {
+        // Bank targets take an offset inside the bank, the bus takes a full address
+        let address_label = if form.target == PokeTarget::Bus { "Address:" } else { "Offset:" };
+        ui.label(address_label);
+        ui.add(
+            TextEdit::singleline(&mut form.address)
+                .desired_width(100.0)
+                .hint_text("C000 or label"),
+        );
+
+        ui.label("Value: (0x)");
+        ui.add(
+            TextEdit::singleline(&mut form.value)
+                .desired_width(30.0)
+                .hint_text("00")
+                .char_limit(2),
+        );
+
+        ui.add_sized([80.0, 20.0], Button::new("✏ Write")).clicked()
+    })
+    .inner
+}
+
+// Checkboxes show a layer, hiding it only changes what the PPU draws
+fn layer_toggles(ui: &mut Ui, layers: &mut LayerToggles) {
+    ui.horizontal(|ui| {
+        let mut show_background = !layers.hide_background;
+        let mut show_window = !layers.hide_window;
+        let mut show_sprites = !layers.hide_sprites;
+        ui.checkbox(&mut show_background, "Background");
+        ui.checkbox(&mut show_window, "Window");
+        ui.checkbox(&mut show_sprites, "Sprites");
+        layers.hide_background = !show_background;
+        layers.hide_window = !show_window;
+        layers.hide_sprites = !show_sprites;
+    });
+    ui.checkbox(&mut layers.false_colors, "Highlight layers with false colors");
+    if layers.hidden_sprites != 0 {
+        ui.horizontal(|ui| {
+            ui.label(
+                RichText::new(format!("{} OAM entries hidden", layers.hidden_sprites.count_ones()))
+                    .color(Color32::from_rgb(150, 150, 150)),
+            );
+            if ui.small_button("Show all").clicked() {
+                layers.hidden_sprites = 0;
+            }
+        });
+    }
+}
+
+// Runs a DMG cart as on a CGB, the palette the boot ROM would pick or one of its button combos
+fn compat_palette_picker(ui: &mut Ui, choice: &mut PaletteChoice) {
+    ComboBox::from_id_salt("compat_palette")
+        .selected_text(choice.name())
+        .show_ui(ui, |ui| {
+            for option in PaletteChoice::ALL {
+                ui.selectable_value(choice, option, option.name());
+            }
+        });
+    let hint = match choice.button_combo() {
+        Some(combo) => format!("Hold {combo} on the CGB boot logo for the same colors"),
+        None if *choice == PaletteChoice::Auto => "Picked from the title checksum, CGB carts keep their palettes".to_string(),
+        None => "DMG shades".to_string(),
+    };
+    ui.label(RichText::new(hint).color(Color32::from_rgb(150, 150, 150)));
+}
+
 fn get_next_instructions(ui: &mut Ui, data: &DebuggingDataIn) -> (u8, bool) {
     // Input section
     let instruction_requested_tuple = ui
@@ -234,6 +590,12 @@ fn get_next_instructions(ui: &mut Ui, data: &DebuggingDataIn) -> (u8, bool) {
 
     ui.add_space(8.0);
 
+    ui.label(
+        RichText::new(format!("PC: {}", address_with_label(data, data.registers.pc)))
+            .monospace()
+            .color(Color32::from_rgb(100, 200, 255)),
+    );
+
     // Display instructions
     if data.nb_instruction > 0 && !data.next_instructions.is_empty() {
         ui.separator();
@@ -250,10 +612,11 @@ fn get_next_instructions(ui: &mut Ui, data: &DebuggingDataIn) -> (u8, bool) {
 
         // Headers (outside scroll area, always visible)
         Grid::new("instructions_header")
-            .num_columns(4)
+            .num_columns(5)
             .spacing([15.0, 6.0])
             .show(ui, |ui| {
                 ui.label(RichText::new("#").strong());
+                ui.label(RichText::new("Address").strong());
                 ui.label(RichText::new("Hex").strong());
                 ui.label(RichText::new("Dec").strong());
                 ui.label(RichText::new("Binary").strong());
@@ -267,8 +630,10 @@ fn get_next_instructions(ui: &mut Ui, data: &DebuggingDataIn) -> (u8, bool) {
                 .max_height(100.0)
                 .auto_shrink([true; 2])
                 .show(ui, |ui| {
+                    // The first value is the PC itself, the bytes after it follow
+                    let pc = data.next_instructions[0];
                     Grid::new("instructions_grid")
-                        .num_columns(4)
+                        .num_columns(5)
                         .spacing([15.0, 6.0])
                         .striped(true)
                         .show(ui, |ui| {
@@ -280,6 +645,11 @@ fn get_next_instructions(ui: &mut Ui, data: &DebuggingDataIn) -> (u8, bool) {
                                         .color(Color32::from_rgb(150, 150, 150)),
                                 );
 
+                                ui.label(
+                                    RichText::new(address_with_label(data, pc.wrapping_add(i as u16)))
+                                        .monospace(),
+                                );
+
                                 // Hex value
                                 ui.label(
                                     RichText::new(format!("0x{:02X}", instruction))
@@ -316,7 +686,128 @@ fn get_next_instructions(ui: &mut Ui, data: &DebuggingDataIn) -> (u8, bool) {
     instruction_requested_tuple
 }
 
-fn watch_address(ui: &mut Ui, data: &DebuggingDataIn) -> (String, bool) {
+// `0x0150 Main+3` when a symbol file was loaded, plain hex otherwise
+fn address_with_label(data: &DebuggingDataIn, address: u16) -> String {
+    match data.symbols.describe(address, data.registers.rom_bank) {
+        Some(label) => format!("0x{address:04X} {label}"),
+        None => format!("0x{address:04X}"),
+    }
+}
+
+fn call_kind_label(kind: CallKind) -> String {
+    match kind {
+        CallKind::Call => "CALL".to_string(),
+        CallKind::Rst => "RST".to_string(),
+        CallKind::Interrupt(interrupt) => format!("INT {interrupt:?}"),
+    }
+}
+
+fn trace_kind_label(kind: TraceKind) -> String {
+    match kind {
+        TraceKind::Jump => "JP".to_string(),
+        TraceKind::Call => "CALL".to_string(),
+        TraceKind::Rst => "RST".to_string(),
+        TraceKind::Ret => "RET".to_string(),
+        TraceKind::Reti => "RETI".to_string(),
+        TraceKind::Interrupt(interrupt) => format!("INT {interrupt:?}"),
+    }
+}
+
+fn call_stack(ui: &mut Ui, data: &DebuggingDataIn) {
+    if data.call_stack.is_empty() {
+        ui.label(
+            RichText::new("Call stack is empty")
+                .italics()
+                .color(Color32::DARK_GRAY),
+        );
+    } else {
+        Grid::new("call_stack_grid")
+            .num_columns(4)
+            .spacing([15.0, 6.0])
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label(RichText::new("Kind").strong());
+                ui.label(RichText::new("From").strong());
+                ui.label(RichText::new("Routine").strong());
+                ui.label(RichText::new("Return").strong());
+                ui.end_row();
+
+                // Innermost frame first, like any debugger backtrace
+                for frame in data.call_stack.iter().rev() {
+                    ui.label(
+                        RichText::new(call_kind_label(frame.kind))
+                            .color(Color32::from_rgb(255, 200, 100)),
+                    );
+                    ui.label(RichText::new(format!("0x{:04X}", frame.call_site)).monospace());
+                    ui.label(
+                        RichText::new(address_with_label(data, frame.target))
+                            .monospace()
+                            .color(Color32::from_rgb(100, 200, 255)),
+                    );
+                    ui.label(RichText::new(format!("0x{:04X}", frame.return_address)).monospace());
+                    ui.end_row();
+                }
+            });
+    }
+
+    ui.add_space(8.0);
+    ui.label(RichText::new("History").strong());
+
+    ui.push_id("jump_history", |ui| {
+        ScrollArea::vertical()
+            .max_height(150.0)
+            .auto_shrink([true; 2])
+            .show(ui, |ui| {
+                Grid::new("jump_history_grid")
+                    .num_columns(4)
+                    .spacing([15.0, 6.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for event in data.jump_history.iter().rev() {
+                            ui.label(
+                                RichText::new(format!("{}", event.cycle))
+                                    .monospace()
+                                    .color(Color32::from_rgb(150, 150, 150)),
+                            );
+                            ui.label(trace_kind_label(event.kind));
+                            ui.label(RichText::new(format!("0x{:04X}", event.from)).monospace());
+                            ui.label(
+                                RichText::new(format!("→ {}", address_with_label(data, event.to)))
+                                    .monospace()
+                                    .color(Color32::from_rgb(100, 200, 255)),
+                            );
+                            ui.end_row();
+                        }
+                    });
+            });
+    });
+}
+
+fn breakpoints(ui: &mut Ui, data: &DebuggingDataIn) {
+    if data.breakpoints.is_empty() {
+        ui.label(
+            RichText::new("No breakpoint, they are set from gdb")
+                .italics()
+                .color(Color32::DARK_GRAY),
+        );
+        return;
+    }
+
+    for address in data.breakpoints {
+        ui.label(
+            RichText::new(address_with_label(data, *address))
+                .monospace()
+                .color(Color32::from_rgb(255, 100, 100)),
+        );
+    }
+}
+
+fn watch_address(
+    ui: &mut Ui,
+    data: &DebuggingDataIn,
+    editing: &mut Option<(EditTarget, String)>,
+    committed_edit: &mut Option<(EditTarget, String)>,
+) -> (String, bool) {
     let mut hex_string = data.hex_string.clone();
     // Input section with better layout
     let register_new_addr: bool = ui
@@ -325,14 +816,12 @@ fn watch_address(ui: &mut Ui, data: &DebuggingDataIn) -> (String, bool) {
                 .horizontal(|h_ui| {
                     h_ui.label("Address:");
 
-                    // Hex input with better formatting
-                    h_ui.label("(0x)");
+                    // Hex address or symbol name
                     let response_changed = h_ui
                         .add(
                             TextEdit::singleline(&mut hex_string)
-                                .desired_width(60.0)
-                                .hint_text("0000")
-                                .char_limit(4),
+                                .desired_width(100.0)
+                                .hint_text("C000 or label"),
                         )
                         .changed();
 
@@ -405,13 +894,20 @@ fn watch_address(ui: &mut Ui, data: &DebuggingDataIn) -> (String, bool) {
 
                             // Address in hex
                             ui.label(
-                                RichText::new(format!("0x{:04X}", address))
+                                RichText::new(address_with_label(data, *address))
                                     .monospace()
                                     .color(Color32::from_rgb(100, 200, 255)),
                             );
 
-                            // Value in hex
-                            ui.label(RichText::new(format!("0x{:02X}", value)).monospace());
+                            // Value in hex, click to poke a new one
+                            editable_value(
+                                ui,
+                                EditTarget::Memory(*address),
+                                format!("{:02X}", value),
+                                2,
+                                editing,
+                                committed_edit,
+                            );
 
                             // Value in decimal
                             ui.label(
@@ -0,0 +1,153 @@
+use crate::gui::EventViewer;
+use crate::mmu::events::{EventKind, HardwareEvent};
+use crate::mmu::interrupt::Interrupt;
+
+use eframe::egui::{vec2, Checkbox, Color32, RichText, ScrollArea, Sense, Stroke, TextStyle, Ui};
+
+const SCANLINE_DOTS: f32 = 456.0;
+const FRAME_LINES: f32 = 154.0;
+const LINE_HEIGHT: f32 = 2.0;
+const MAX_KEPT_EVENTS: usize = 10_000;
+
+impl EventViewer {
+    pub fn push(&mut self, events: Vec<HardwareEvent>) {
+        self.events.extend(events);
+        let overflow = self.events.len().saturating_sub(MAX_KEPT_EVENTS);
+        self.events.drain(..overflow);
+    }
+
+    // Last frame whose events are all received, the frame being emulated is still incomplete
+    fn last_complete_frame(&self) -> Option<u64> {
+        self.events.last().and_then(|event| event.frame.checked_sub(1))
+    }
+}
+
+fn interrupt_color(interrupt: Interrupt) -> Color32 {
+    match interrupt {
+        Interrupt::VBlank => Color32::from_rgb(170, 100, 200),
+        Interrupt::LcdStat => Color32::from_rgb(100, 200, 255),
+        Interrupt::Timer => Color32::from_rgb(255, 200, 100),
+        Interrupt::Serial => Color32::from_rgb(100, 255, 100),
+        Interrupt::Joypad => Color32::from_rgb(255, 100, 100),
+    }
+}
+
+fn event_color(kind: EventKind) -> Color32 {
+    match kind {
+        EventKind::TimaOverflow => Color32::WHITE,
+        EventKind::Requested(interrupt) | EventKind::Serviced(interrupt) => interrupt_color(interrupt),
+        EventKind::Masked(_) => Color32::from_rgb(150, 150, 150),
+    }
+}
+
+fn describe(kind: EventKind) -> String {
+    match kind {
+        EventKind::TimaOverflow => "TIMA overflow".to_string(),
+        EventKind::Requested(interrupt) => format!("{interrupt:?} requested"),
+        EventKind::Masked(interrupt) => format!("{interrupt:?} requested, masked by IE"),
+        EventKind::Serviced(interrupt) => format!("{interrupt:?} serviced"),
+    }
+}
+
+// Returns the state of the record checkbox
+pub fn event_viewer(ui: &mut Ui, viewer: &EventViewer) -> bool {
+    let mut recording = viewer.recording;
+
+    ui.horizontal(|ui| {
+        ui.add(Checkbox::new(&mut recording, "Record"));
+        ui.separator();
+        for interrupt in [Interrupt::VBlank, Interrupt::LcdStat, Interrupt::Timer, Interrupt::Serial, Interrupt::Joypad] {
+            ui.label(RichText::new(format!("● {interrupt:?}")).color(interrupt_color(interrupt)));
+        }
+        ui.label(RichText::new("○ TIMA overflow").color(Color32::WHITE));
+        ui.label(RichText::new("● masked").color(Color32::from_rgb(150, 150, 150)));
+    });
+    ui.separator();
+
+    ui.horizontal_top(|ui| {
+        frame_diagram(ui, viewer);
+        ui.separator();
+        event_list(ui, viewer);
+    });
+
+    recording
+}
+
+// One row per scanline, one column per dot; serviced interrupts are filled, requests are outlined
+fn frame_diagram(ui: &mut Ui, viewer: &EventViewer) {
+    ui.vertical(|ui| {
+        let frame = viewer.last_complete_frame();
+        let title = match frame {
+            Some(frame) => format!("Frame {frame}"),
+            None => "No complete frame yet".to_string(),
+        };
+        ui.label(RichText::new(title).strong().color(Color32::from_rgb(255, 200, 100)));
+
+        let size = vec2(SCANLINE_DOTS, FRAME_LINES * LINE_HEIGHT);
+        let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, Color32::from_rgb(30, 30, 30));
+
+        // visible area vs VBlank
+        let vblank_top = rect.min.y + 144.0 * LINE_HEIGHT;
+        painter.hline(rect.x_range(), vblank_top, Stroke::new(1.0, Color32::from_rgb(90, 90, 90)));
+
+        let Some(frame) = frame else {
+            return;
+        };
+        for event in viewer.events.iter().filter(|event| event.frame == frame) {
+            let center = rect.min + vec2(event.dot as f32, event.line as f32 * LINE_HEIGHT);
+            let color = event_color(event.kind);
+            match event.kind {
+                EventKind::Serviced(_) => painter.circle_filled(center, 3.0, color),
+                _ => painter.circle_stroke(center, 3.0, Stroke::new(1.0, color)),
+            };
+        }
+    });
+}
+
+fn event_list(ui: &mut Ui, viewer: &EventViewer) {
+    let row_height = ui.text_style_height(&TextStyle::Monospace);
+
+    ScrollArea::vertical()
+        .id_salt("event_list_scroll")
+        .auto_shrink([false, false])
+        .show_rows(ui, row_height, viewer.events.len(), |ui, row_range| {
+            // newest first
+            for row in row_range {
+                let event = &viewer.events[viewer.events.len() - 1 - row];
+                ui.horizontal(|ui| {
+                    ui.label(
+                        RichText::new(format!(
+                            "F{:<6} LY {:3} dot {:3} cycle {:<10}",
+                            event.frame, event.line, event.dot, event.cycle
+                        ))
+                        .monospace()
+                        .color(Color32::from_rgb(150, 150, 150)),
+                    );
+                    ui.label(RichText::new(describe(event.kind)).monospace().color(event_color(event.kind)));
+                });
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(frame: u64) -> HardwareEvent {
+        HardwareEvent { kind: EventKind::TimaOverflow, cycle: 0, frame, line: 0, dot: 0 }
+    }
+
+    #[test]
+    fn viewer_keeps_the_latest_events() {
+        let mut viewer = EventViewer::default();
+        viewer.push(vec![event(0)]);
+        assert_eq!(viewer.last_complete_frame(), None);
+
+        viewer.push((0..MAX_KEPT_EVENTS as u64).map(event).collect());
+        assert_eq!(viewer.events.len(), MAX_KEPT_EVENTS);
+        assert_eq!(viewer.events[0].frame, 0);
+        assert_eq!(viewer.last_complete_frame(), Some(MAX_KEPT_EVENTS as u64 - 2));
+    }
+}
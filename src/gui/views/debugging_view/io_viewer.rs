@@ -0,0 +1,292 @@
+use crate::gui::IoSnapshot;
+
+use eframe::egui::{Checkbox, CollapsingHeader, Color32, DragValue, Grid, RichText, Ui};
+
+struct BitField {
+    name: &'static str,
+    shift: u8,
+    width: u8,
+    writable: bool,
+}
+
+impl BitField {
+    fn mask(&self) -> u8 {
+        (((1u16 << self.width) - 1) as u8) << self.shift
+    }
+
+    fn get(&self, value: u8) -> u8 {
+        (value & self.mask()) >> self.shift
+    }
+
+    fn set(&self, value: u8, field: u8) -> u8 {
+        (value & !self.mask()) | ((field << self.shift) & self.mask())
+    }
+}
+
+struct IoRegister {
+    address: u16,
+    name: &'static str,
+    fields: &'static [BitField],
+}
+
+const fn bit(name: &'static str, shift: u8) -> BitField {
+    BitField { name, shift, width: 1, writable: true }
+}
+
+const fn bits(name: &'static str, shift: u8, width: u8) -> BitField {
+    BitField { name, shift, width, writable: true }
+}
+
+const fn read_only(field: BitField) -> BitField {
+    BitField { writable: false, ..field }
+}
+
+const WHOLE_BYTE: &[BitField] = &[bits("Value", 0, 8)];
+
+const fn byte(address: u16, name: &'static str) -> IoRegister {
+    IoRegister { address, name, fields: WHOLE_BYTE }
+}
+
+const INTERRUPTS: &[BitField] = &[
+    bit("Joypad", 4),
+    bit("Serial", 3),
+    bit("Timer", 2),
+    bit("LCD", 1),
+    bit("VBlank", 0),
+];
+const PALETTE: &[BitField] = &[
+    bits("Color 3", 6, 2),
+    bits("Color 2", 4, 2),
+    bits("Color 1", 2, 2),
+    bits("Color 0", 0, 2),
+];
+const ENVELOPE: &[BitField] = &[
+    bits("Initial volume", 4, 4),
+    bit("Increase", 3),
+    bits("Sweep pace", 0, 3),
+];
+const LENGTH_AND_DUTY: &[BitField] = &[bits("Duty", 6, 2), bits("Length", 0, 6)];
+const PERIOD_HIGH: &[BitField] = &[
+    bit("Trigger", 7),
+    bit("Length enable", 6),
+    bits("Period high", 0, 3),
+];
+
+// Bit layouts follow Pan Docs, fields the CPU cannot change are shown read-only
+const IO_REGISTERS: &[(&str, &[IoRegister])] = &[
+    ("Joypad and serial", &[
+        IoRegister {
+            address: 0xFF00,
+            name: "JOYP",
+            fields: &[
+                bit("Select buttons", 5),
+                bit("Select d-pad", 4),
+                read_only(bit("Start/Down", 3)),
+                read_only(bit("Select/Up", 2)),
+                read_only(bit("B/Left", 1)),
+                read_only(bit("A/Right", 0)),
+            ],
+        },
+        byte(0xFF01, "SB"),
+        IoRegister { address: 0xFF02, name: "SC", fields: &[bit("Transfer", 7), bit("Internal clock", 0)] },
+    ]),
+    ("Timers", &[
+        byte(0xFF04, "DIV"),
+        byte(0xFF05, "TIMA"),
+        byte(0xFF06, "TMA"),
+        IoRegister { address: 0xFF07, name: "TAC", fields: &[bit("Enable", 2), bits("Clock select", 0, 2)] },
+    ]),
+    ("Interrupts", &[
+        IoRegister { address: 0xFF0F, name: "IF", fields: INTERRUPTS },
+        IoRegister { address: 0xFFFF, name: "IE", fields: INTERRUPTS },
+    ]),
+    ("LCD", &[
+        IoRegister {
+            address: 0xFF40,
+            name: "LCDC",
+            fields: &[
+                bit("LCD on", 7),
+                bit("Window map 9C00", 6),
+                bit("Window on", 5),
+                bit("Tiles 8000", 4),
+                bit("BG map 9C00", 3),
+                bit("OBJ 8x16", 2),
+                bit("OBJ on", 1),
+                bit("BG/Window on", 0),
+            ],
+        },
+        IoRegister {
+            address: 0xFF41,
+            name: "STAT",
+            fields: &[
+                bit("LYC int", 6),
+                bit("Mode 2 int", 5),
+                bit("Mode 1 int", 4),
+                bit("Mode 0 int", 3),
+                read_only(bit("LYC=LY", 2)),
+                read_only(bits("Mode", 0, 2)),
+            ],
+        },
+        byte(0xFF42, "SCY"),
+        byte(0xFF43, "SCX"),
+        IoRegister { address: 0xFF44, name: "LY", fields: &[read_only(bits("Value", 0, 8))] },
+        byte(0xFF45, "LYC"),
+        byte(0xFF46, "DMA"),
+        IoRegister { address: 0xFF47, name: "BGP", fields: PALETTE },
+        IoRegister { address: 0xFF48, name: "OBP0", fields: PALETTE },
+        IoRegister { address: 0xFF49, name: "OBP1", fields: PALETTE },
+        byte(0xFF4A, "WY"),
+        byte(0xFF4B, "WX"),
+    ]),
+    ("Sound", &[
+        IoRegister {
+            address: 0xFF10,
+            name: "NR10",
+            fields: &[bits("Sweep pace", 4, 3), bit("Decrease", 3), bits("Step", 0, 3)],
+        },
+        IoRegister { address: 0xFF11, name: "NR11", fields: LENGTH_AND_DUTY },
+        IoRegister { address: 0xFF12, name: "NR12", fields: ENVELOPE },
+        byte(0xFF13, "NR13"),
+        IoRegister { address: 0xFF14, name: "NR14", fields: PERIOD_HIGH },
+        IoRegister { address: 0xFF16, name: "NR21", fields: LENGTH_AND_DUTY },
+        IoRegister { address: 0xFF17, name: "NR22", fields: ENVELOPE },
+        byte(0xFF18, "NR23"),
+        IoRegister { address: 0xFF19, name: "NR24", fields: PERIOD_HIGH },
+        IoRegister { address: 0xFF1A, name: "NR30", fields: &[bit("DAC on", 7)] },
+        byte(0xFF1B, "NR31"),
+        IoRegister { address: 0xFF1C, name: "NR32", fields: &[bits("Output level", 5, 2)] },
+        byte(0xFF1D, "NR33"),
+        IoRegister { address: 0xFF1E, name: "NR34", fields: PERIOD_HIGH },
+        IoRegister { address: 0xFF20, name: "NR41", fields: &[bits("Length", 0, 6)] },
+        IoRegister { address: 0xFF21, name: "NR42", fields: ENVELOPE },
+        IoRegister {
+            address: 0xFF22,
+            name: "NR43",
+            fields: &[bits("Clock shift", 4, 4), bit("7-bit LFSR", 3), bits("Clock divider", 0, 3)],
+        },
+        IoRegister { address: 0xFF23, name: "NR44", fields: &[bit("Trigger", 7), bit("Length enable", 6)] },
+        IoRegister {
+            address: 0xFF24,
+            name: "NR50",
+            fields: &[bit("VIN left", 7), bits("Left volume", 4, 3), bit("VIN right", 3), bits("Right volume", 0, 3)],
+        },
+        IoRegister {
+            address: 0xFF25,
+            name: "NR51",
+            fields: &[
+                bit("CH4 left", 7),
+                bit("CH3 left", 6),
+                bit("CH2 left", 5),
+                bit("CH1 left", 4),
+                bit("CH4 right", 3),
+                bit("CH3 right", 2),
+                bit("CH2 right", 1),
+                bit("CH1 right", 0),
+            ],
+        },
+        IoRegister {
+            address: 0xFF26,
+            name: "NR52",
+            fields: &[
+                bit("Audio on", 7),
+                read_only(bit("CH4 on", 3)),
+                read_only(bit("CH3 on", 2)),
+                read_only(bit("CH2 on", 1)),
+                read_only(bit("CH1 on", 0)),
+            ],
+        },
+    ]),
+];
+
+// Returns the register writes requested by the user
+pub fn io_viewer(ui: &mut Ui, io: &IoSnapshot) -> Vec<(u16, u8)> {
+    let mut writes = Vec::new();
+
+    for (group, registers) in IO_REGISTERS {
+        CollapsingHeader::new(RichText::new(*group).strong())
+            .default_open(true)
+            .show(ui, |ui| {
+                Grid::new(("io_grid", *group))
+                    .num_columns(3)
+                    .spacing([12.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for register in registers.iter() {
+                            if let Some(value) = register_row(ui, register, io.read(register.address)) {
+                                writes.push((register.address, value));
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+
+    writes
+}
+
+fn register_row(ui: &mut Ui, register: &IoRegister, value: u8) -> Option<u8> {
+    let mut new_value = value;
+
+    ui.label(
+        RichText::new(format!("{:04X} {}", register.address, register.name))
+            .monospace()
+            .color(Color32::from_rgb(100, 200, 255)),
+    );
+    ui.label(RichText::new(format!("{value:02X} {value:08b}")).monospace());
+
+    ui.horizontal(|ui| {
+        for field in register.fields {
+            let current = field.get(value);
+            if field.width == 1 {
+                let mut checked = current == 1;
+                let response = ui.add_enabled(field.writable, Checkbox::new(&mut checked, field.name));
+                if response.changed() {
+                    new_value = field.set(new_value, checked as u8);
+                }
+            } else {
+                ui.label(RichText::new(format!("{}:", field.name)).color(Color32::from_rgb(150, 150, 150)));
+                let mut field_value = current;
+                let max = ((1u16 << field.width) - 1) as u8;
+                let response = ui.add_enabled(
+                    field.writable,
+                    DragValue::new(&mut field_value).range(0..=max).hexadecimal(2, false, true),
+                );
+                if response.changed() {
+                    new_value = field.set(new_value, field_value);
+                }
+            }
+        }
+    });
+
+    (new_value != value).then_some(new_value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bit_fields_read_and_write_their_bits_only() {
+        let clock = bits("Clock select", 0, 2);
+        let duty = bits("Duty", 6, 2);
+
+        assert_eq!(clock.get(0b0000_0111), 0b11);
+        assert_eq!(clock.set(0b0000_0111, 0b01), 0b0000_0101);
+        assert_eq!(duty.get(0b1000_0000), 0b10);
+        assert_eq!(duty.set(0xFF, 0), 0b0011_1111);
+        assert_eq!(bits("Value", 0, 8).set(0x12, 0xAB), 0xAB);
+    }
+
+    #[test]
+    fn register_fields_do_not_overlap() {
+        for (_, registers) in IO_REGISTERS {
+            for register in registers.iter() {
+                let mut used = 0u8;
+                for field in register.fields {
+                    assert_eq!(used & field.mask(), 0, "{} {}", register.name, field.name);
+                    used |= field.mask();
+                }
+            }
+        }
+    }
+}
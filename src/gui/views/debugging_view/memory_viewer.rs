@@ -0,0 +1,238 @@
+use crate::gui::{MemoryView, MemoryViewer, MemoryViewerForm};
+use crate::symbols::SymbolTable;
+
+use eframe::egui::{Color32, ComboBox, DragValue, Key, RichText, ScrollArea, TextEdit, TextStyle, Ui};
+
+const BYTES_PER_ROW: usize = 16;
+const MAX_SEARCH_RESULTS: usize = 256;
+
+#[derive(Debug, Default)]
+pub struct MemoryViewerActions {
+    pub refresh_clicked: bool,
+    pub goto_clicked: bool,
+    pub search_clicked: bool,
+    pub result_clicked: Option<usize>,
+}
+
+impl MemoryViewer {
+    // Moves the view to an address given as it is mapped on the bus
+    pub fn goto(&mut self, symbols: &SymbolTable) -> Result<(), String> {
+        let address = symbols.parse_address(&self.form.goto_address)?;
+
+        let base = self.dump.view.base_address() as usize;
+        let offset = (address as usize)
+            .checked_sub(base)
+            .filter(|offset| *offset < self.dump.bytes.len())
+            .ok_or(format!("0x{:04X} is not inside {}", address, self.dump.view.name()))?;
+
+        self.scroll_to_offset(offset);
+        Ok(())
+    }
+
+    pub fn scroll_to_offset(&mut self, offset: usize) {
+        self.scroll_to_row = Some(offset / BYTES_PER_ROW);
+    }
+
+    // Pattern is a list of hex bytes separated by spaces, `??` matches any byte
+    pub fn search(&mut self) -> Result<(), String> {
+        let pattern = parse_byte_pattern(&self.form.search_pattern)?;
+        self.search_results = find_pattern(&self.dump.bytes, &pattern);
+        self.search_length = pattern.len();
+
+        if let Some(first) = self.search_results.first() {
+            self.scroll_to_offset(*first);
+        }
+        Ok(())
+    }
+
+    fn is_search_match(&self, offset: usize) -> bool {
+        self.search_results
+            .iter()
+            .any(|start| (*start..*start + self.search_length).contains(&offset))
+    }
+}
+
+fn parse_byte_pattern(pattern: &str) -> Result<Vec<Option<u8>>, String> {
+    let bytes = pattern
+        .split_whitespace()
+        .map(|byte| match byte {
+            "??" => Ok(None),
+            _ => u8::from_str_radix(byte, 16)
+                .map(Some)
+                .map_err(|_| format!("Invalid byte in pattern: {byte}")),
+        })
+        .collect::<Result<Vec<Option<u8>>, String>>()?;
+
+    if bytes.is_empty() {
+        return Err("Search pattern is empty".to_string());
+    }
+    Ok(bytes)
+}
+
+fn find_pattern(bytes: &[u8], pattern: &[Option<u8>]) -> Vec<usize> {
+    bytes
+        .windows(pattern.len())
+        .enumerate()
+        .filter(|(_, window)| {
+            window
+                .iter()
+                .zip(pattern)
+                .all(|(byte, expected)| expected.is_none_or(|expected| expected == *byte))
+        })
+        .map(|(offset, _)| offset)
+        .take(MAX_SEARCH_RESULTS)
+        .collect()
+}
+
+pub fn memory_viewer(ui: &mut Ui, viewer: &MemoryViewer, form: &mut MemoryViewerForm) -> MemoryViewerActions {
+    let mut actions = MemoryViewerActions::default();
+
+    ui.horizontal(|ui| {
+        view_selector(ui, viewer, form);
+        actions.refresh_clicked = ui.button("🔄 Refresh").clicked();
+
+        ui.separator();
+
+        ui.label("Go to:");
+        let goto_response = ui.add(
+            TextEdit::singleline(&mut form.goto_address)
+                .desired_width(100.0)
+                .hint_text("C000 or label"),
+        );
+        let goto_entered = goto_response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+        actions.goto_clicked = ui.button("➡ Go").clicked() || goto_entered;
+
+        ui.separator();
+
+        ui.label("Search:");
+        let search_response = ui.add(
+            TextEdit::singleline(&mut form.search_pattern)
+                .desired_width(140.0)
+                .hint_text("CE ED ?? 66"),
+        );
+        let search_entered = search_response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+        actions.search_clicked = ui.button("🔍 Find").clicked() || search_entered;
+    });
+
+    if !viewer.search_results.is_empty() {
+        ui.horizontal_wrapped(|ui| {
+            ui.label(
+                RichText::new(format!("{} match(es):", viewer.search_results.len()))
+                    .color(Color32::from_rgb(150, 150, 150)),
+            );
+            let base = viewer.dump.view.base_address() as usize;
+            for offset in viewer.search_results.iter() {
+                if ui.link(format!("{:04X}", base + offset)).clicked() {
+                    actions.result_clicked = Some(*offset);
+                }
+            }
+        });
+    }
+
+    ui.separator();
+    hex_dump(ui, viewer);
+
+    actions
+}
+
+fn view_selector(ui: &mut Ui, viewer: &MemoryViewer, form: &mut MemoryViewerForm) {
+    ComboBox::from_id_salt("memory_view")
+        .selected_text(form.view.name())
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut form.view, MemoryView::Bus, "Bus");
+            ui.selectable_value(&mut form.view, MemoryView::RomBank(0), "ROM bank");
+            if viewer.dump.ram_bank_count > 0 {
+                ui.selectable_value(&mut form.view, MemoryView::RamBank(0), "RAM bank");
+            }
+            ui.selectable_value(&mut form.view, MemoryView::Vram, "VRAM");
+            ui.selectable_value(&mut form.view, MemoryView::Oam, "OAM");
+            ui.selectable_value(&mut form.view, MemoryView::HRam, "HRAM");
+        });
+
+    match &mut form.view {
+        MemoryView::RomBank(bank) => {
+            let last_bank = viewer.dump.rom_bank_count.saturating_sub(1);
+            ui.add(DragValue::new(bank).range(0..=last_bank).prefix("#"));
+        }
+        MemoryView::RamBank(bank) => {
+            let last_bank = viewer.dump.ram_bank_count.saturating_sub(1);
+            ui.add(DragValue::new(bank).range(0..=last_bank).prefix("#"));
+        }
+        _ => {}
+    }
+}
+
+fn hex_dump(ui: &mut Ui, viewer: &MemoryViewer) {
+    let bytes = &viewer.dump.bytes;
+    if bytes.is_empty() {
+        ui.label(RichText::new("No memory received yet").color(Color32::from_rgb(150, 150, 150)));
+        return;
+    }
+
+    let base = viewer.dump.view.base_address() as usize;
+    let compare_with_previous = viewer.previous_bytes.len() == bytes.len();
+    let row_height = ui.text_style_height(&TextStyle::Monospace);
+    let total_rows = bytes.len().div_ceil(BYTES_PER_ROW);
+
+    let mut scroll_area = ScrollArea::vertical()
+        .id_salt("memory_viewer_scroll")
+        .auto_shrink([false, false]);
+    if let Some(row) = viewer.scroll_to_row {
+        scroll_area = scroll_area.vertical_scroll_offset(row as f32 * (row_height + ui.spacing().item_spacing.y));
+    }
+
+    scroll_area.show_rows(ui, row_height, total_rows, |ui, row_range| {
+        for row in row_range {
+            let start = row * BYTES_PER_ROW;
+            let end = (start + BYTES_PER_ROW).min(bytes.len());
+
+            ui.horizontal(|ui| {
+                ui.spacing_mut().item_spacing.x = 4.0;
+                ui.label(
+                    RichText::new(format!("{:04X}:", base + start))
+                        .monospace()
+                        .color(Color32::from_rgb(100, 200, 255)),
+                );
+
+                for (offset, byte) in (start..end).zip(&bytes[start..end]) {
+                    let mut text = RichText::new(format!("{:02X}", byte)).monospace();
+                    if compare_with_previous && viewer.previous_bytes[offset] != *byte {
+                        text = text.color(Color32::from_rgb(255, 100, 100));
+                    }
+                    if viewer.is_search_match(offset) {
+                        text = text.background_color(Color32::from_rgb(90, 90, 0));
+                    }
+                    ui.label(text);
+                }
+
+                let ascii: String = bytes[start..end]
+                    .iter()
+                    .map(|byte| if byte.is_ascii_graphic() { *byte as char } else { '.' })
+                    .collect();
+                ui.label(RichText::new(ascii).monospace().color(Color32::from_rgb(150, 150, 150)));
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pattern_with_wildcards() {
+        assert_eq!(
+            parse_byte_pattern("CE ?? 0d").unwrap(),
+            vec![Some(0xCE), None, Some(0x0D)]
+        );
+        assert!(parse_byte_pattern("").is_err());
+        assert!(parse_byte_pattern("CE XY").is_err());
+    }
+
+    #[test]
+    fn find_pattern_reports_every_offset() {
+        let bytes = [0x00, 0xCE, 0xED, 0x00, 0xCE, 0x11, 0xCE];
+        assert_eq!(find_pattern(&bytes, &[Some(0xCE), None]), vec![1, 4]);
+        assert_eq!(find_pattern(&bytes, &[Some(0xFF)]), Vec::<usize>::new());
+    }
+}
@@ -0,0 +1,182 @@
+use crate::gui::{OamViewer, VideoSnapshot};
+use crate::mmu::oam::{Oam, Sprite};
+use crate::ppu::layers::LayerToggles;
+
+use super::tile_viewer::{shade, tile_color_index};
+
+use eframe::egui::{
+    load::SizedTexture, pos2, vec2, Color32, ColorImage, Context, DragValue, Grid, Image, Rect,
+    RichText, TextureOptions, Ui,
+};
+
+const SPRITE_COUNT: usize = 40;
+const ATLAS_WIDTH: usize = SPRITE_COUNT * 8;
+const ATLAS_HEIGHT: usize = 16;
+const PREVIEW_SCALE: f32 = 3.0;
+const TRANSPARENT: [u8; 3] = [60, 60, 60];
+const LCDC_OBJ_SIZE_MASK: u8 = 0b0000_0100;
+
+fn sprite_height(lcdc: u8) -> u8 {
+    if lcdc & LCDC_OBJ_SIZE_MASK != 0 { 16 } else { 8 }
+}
+
+fn oam_from_snapshot(video: &VideoSnapshot) -> Oam {
+    let mut oam = Oam::new();
+    for (offset, byte) in video.oam.iter().enumerate() {
+        oam.write_raw(offset as u8, *byte);
+    }
+    oam
+}
+
+// Sprite as drawn on screen (flips and OBP palette applied), color 0 stays transparent
+fn render_sprite(video: &VideoSnapshot, sprite: &Sprite, height: u8) -> Vec<[u8; 3]> {
+    let x_flip = sprite.attributes & 0b0010_0000 != 0;
+    let y_flip = sprite.attributes & 0b0100_0000 != 0;
+    let palette = if sprite.attributes & 0b0001_0000 != 0 { video.obp1 } else { video.obp0 };
+    let first_tile = if height == 16 { sprite.tile & 0xFE } else { sprite.tile } as usize;
+
+    (0..height as usize * 8)
+        .map(|pixel| {
+            let (x, y) = (pixel % 8, pixel / 8);
+            let x = if x_flip { 7 - x } else { x };
+            let y = if y_flip { height as usize - 1 - y } else { y };
+            match tile_color_index(&video.vram, first_tile + y / 8, x, y % 8) {
+                0 => TRANSPARENT,
+                color_index => shade(palette, color_index),
+            }
+        })
+        .collect()
+}
+
+impl OamViewer {
+    // Every sprite gets an 8x16 slot of a single texture
+    pub fn update_texture(&mut self, ctx: &Context, video: &VideoSnapshot) -> SizedTexture {
+        let oam = oam_from_snapshot(video);
+        let height = sprite_height(video.lcdc);
+        let mut atlas = vec![0; ATLAS_WIDTH * ATLAS_HEIGHT * 3];
+        for (index, sprite) in oam.sprites.iter().enumerate() {
+            for (pixel, rgb) in render_sprite(video, sprite, height).into_iter().enumerate() {
+                let offset = ((pixel / 8) * ATLAS_WIDTH + index * 8 + pixel % 8) * 3;
+                atlas[offset..offset + 3].copy_from_slice(&rgb);
+            }
+        }
+
+        let image = ColorImage::from_rgb([ATLAS_WIDTH, ATLAS_HEIGHT], &atlas);
+        let texture = self
+            .texture
+            .get_or_insert_with(|| ctx.load_texture("oam_sprites", image.clone(), TextureOptions::NEAREST));
+        texture.set(image, TextureOptions::NEAREST);
+
+        SizedTexture::new(texture.id(), vec2(ATLAS_WIDTH as f32, ATLAS_HEIGHT as f32))
+    }
+}
+
+pub fn oam_viewer(
+    ui: &mut Ui,
+    texture: SizedTexture,
+    video: &VideoSnapshot,
+    scanline: &mut u8,
+    layers: &mut LayerToggles,
+) {
+    let oam = oam_from_snapshot(video);
+    let height = sprite_height(video.lcdc);
+    let (selected, dropped) = oam.scan_line(*scanline, height);
+
+    ui.horizontal(|ui| {
+        ui.label("Scanline:");
+        ui.add(DragValue::new(scanline).range(0..=143));
+        if ui.button("Use LY").clicked() && video.ly < 144 {
+            *scanline = video.ly;
+        }
+        ui.label(RichText::new(format!("LY: {}", video.ly)).color(Color32::from_rgb(150, 150, 150)));
+        ui.separator();
+        ui.label(format!("Size: 8x{height}"));
+        ui.separator();
+        ui.label(format!("{} selected, {} dropped", selected.len(), dropped.len()));
+    });
+
+    Grid::new("oam_grid")
+        .num_columns(9)
+        .spacing([12.0, 4.0])
+        .striped(true)
+        .show(ui, |ui| {
+            for header in ["#", "Shown", "Sprite", "X", "Y", "Tile", "Flip", "Palette / Priority", "Line"] {
+                ui.label(RichText::new(header).strong());
+            }
+            ui.end_row();
+
+            for (index, sprite) in oam.sprites.iter().enumerate() {
+                ui.label(RichText::new(format!("{index:02}")).monospace());
+
+                let mut shown = !layers.is_sprite_hidden(index as u8);
+                if ui.checkbox(&mut shown, "").changed() {
+                    layers.set_sprite_hidden(index as u8, !shown);
+                }
+
+                let uv = Rect::from_min_max(
+                    pos2(index as f32 * 8.0 / ATLAS_WIDTH as f32, 0.0),
+                    pos2((index + 1) as f32 * 8.0 / ATLAS_WIDTH as f32, height as f32 / ATLAS_HEIGHT as f32),
+                );
+                let size = vec2(8.0, height as f32) * PREVIEW_SCALE;
+                ui.add(Image::new(SizedTexture::new(texture.id, size)).uv(uv));
+
+                ui.label(RichText::new(format!("{:3}", sprite.x)).monospace());
+                ui.label(RichText::new(format!("{:3}", sprite.y)).monospace());
+                ui.label(RichText::new(format!("0x{:02X}", sprite.tile)).monospace());
+                ui.label(RichText::new(flip_label(sprite.attributes)).monospace());
+                ui.label(RichText::new(palette_priority_label(sprite.attributes)).monospace());
+
+                let index = index as u8;
+                if let Some(order) = selected.iter().position(|selected| *selected == index) {
+                    ui.label(RichText::new(format!("selected #{order}")).color(Color32::from_rgb(100, 255, 100)));
+                } else if dropped.contains(&index) {
+                    ui.label(RichText::new("dropped (10 limit)").color(Color32::from_rgb(255, 100, 100)));
+                } else {
+                    ui.label("");
+                }
+                ui.end_row();
+            }
+        });
+}
+
+fn flip_label(attributes: u8) -> String {
+    let x_flip = if attributes & 0b0010_0000 != 0 { "X" } else { "-" };
+    let y_flip = if attributes & 0b0100_0000 != 0 { "Y" } else { "-" };
+    format!("{x_flip}{y_flip}")
+}
+
+fn palette_priority_label(attributes: u8) -> String {
+    let palette = if attributes & 0b0001_0000 != 0 { "OBP1" } else { "OBP0" };
+    let priority = if attributes & 0b1000_0000 != 0 { "behind BG" } else { "above BG" };
+    format!("{palette} / {priority}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ppu::colors_palette::Color;
+
+    #[test]
+    fn render_sprite_applies_flips_and_8x16_tiles() {
+        let mut video = VideoSnapshot {
+            obp0: 0b1110_0100,
+            ..VideoSnapshot::default()
+        };
+        // tile 2: top left pixel is color 3, tile 3: bottom right pixel is color 1
+        video.vram[2 * 16] = 0b1000_0000;
+        video.vram[2 * 16 + 1] = 0b1000_0000;
+        video.vram[3 * 16 + 14] = 0b0000_0001;
+
+        let sprite = Sprite { y: 16, x: 8, tile: 0x03, oam_index: 0, attributes: 0 };
+        let pixels = render_sprite(&video, &sprite, 16);
+        assert_eq!(pixels.len(), 8 * 16);
+        assert_eq!(pixels[0], Color::Black.to_rgb());
+        assert_eq!(pixels[8 * 16 - 1], Color::LightGray.to_rgb());
+        assert_eq!(pixels[1], TRANSPARENT);
+
+        let flipped = Sprite { attributes: 0b0110_0000, ..sprite };
+        let pixels = render_sprite(&video, &flipped, 16);
+        assert_eq!(pixels[0], Color::LightGray.to_rgb());
+        assert_eq!(pixels[8 * 16 - 1], Color::Black.to_rgb());
+    }
+}
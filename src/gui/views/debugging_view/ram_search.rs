@@ -0,0 +1,197 @@
+use crate::gui::{RamSearch, RamSearchForm, RamSnapshot, SearchComparison, SearchOperand, SearchStep, SearchWidth};
+
+use eframe::egui::{Button, Color32, ComboBox, RichText, ScrollArea, TextEdit, TextStyle, Ui};
+
+impl RamSnapshot {
+    // 16-bit values are little endian and must fit in one region
+    pub fn read(&self, address: u16, width: SearchWidth) -> Option<u16> {
+        let (start, bytes) = self
+            .regions
+            .iter()
+            .find(|(start, bytes)| address >= *start && ((address - start) as usize) < bytes.len())?;
+        let offset = (address - start) as usize;
+
+        match width {
+            SearchWidth::Byte => Some(bytes[offset] as u16),
+            SearchWidth::Word => bytes.get(offset + 1).map(|high| u16::from_le_bytes([bytes[offset], *high])),
+        }
+    }
+
+    fn addresses(&self) -> impl Iterator<Item = u16> + '_ {
+        self.regions
+            .iter()
+            .flat_map(|(start, bytes)| (0..bytes.len()).map(move |offset| start + offset as u16))
+    }
+}
+
+impl SearchComparison {
+    fn name(&self) -> &'static str {
+        match self {
+            SearchComparison::Equal => "==",
+            SearchComparison::NotEqual => "!=",
+            SearchComparison::Greater => ">",
+            SearchComparison::Less => "<",
+        }
+    }
+
+    fn matches(&self, current: u16, reference: u16) -> bool {
+        match self {
+            SearchComparison::Equal => current == reference,
+            SearchComparison::NotEqual => current != reference,
+            SearchComparison::Greater => current > reference,
+            SearchComparison::Less => current < reference,
+        }
+    }
+}
+
+impl RamSearch {
+    pub fn parse_value(&self) -> Result<Option<u16>, String> {
+        if self.form.operand == SearchOperand::Previous {
+            return Ok(None);
+        }
+
+        let text = self.form.value.trim().trim_start_matches("0x");
+        match (u16::from_str_radix(text, 16), self.form.width) {
+            (Ok(value), SearchWidth::Byte) if value > 0xFF => Err(format!("0x{value:X} doesn't fit in a byte")),
+            (Ok(value), _) => Ok(Some(value)),
+            (Err(_), _) => Err(format!("Invalid search value: {}", self.form.value)),
+        }
+    }
+
+    pub fn apply_snapshot(&mut self, snapshot: RamSnapshot) {
+        let width = self.form.width;
+        match self.pending.take() {
+            Some(SearchStep::Start) => {
+                self.candidates = snapshot.addresses().filter(|addr| snapshot.read(*addr, width).is_some()).collect();
+            }
+            Some(SearchStep::Filter(value)) => {
+                let previous = self.snapshot.take().unwrap_or_default();
+                let comparison = self.form.comparison;
+                self.candidates.retain(|addr| {
+                    let reference = value.or_else(|| previous.read(*addr, width));
+                    match (snapshot.read(*addr, width), reference) {
+                        (Some(current), Some(reference)) => comparison.matches(current, reference),
+                        _ => false,
+                    }
+                });
+            }
+            None => return,
+        }
+        self.snapshot = Some(snapshot);
+    }
+
+    fn current(&self, address: u16) -> Option<u16> {
+        self.snapshot.as_ref()?.read(address, self.form.width)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RamSearchActions {
+    pub new_search: bool,
+    pub filter: bool,
+    pub watch: Option<u16>,
+    pub freeze: Option<u16>,
+}
+
+pub fn ram_search(ui: &mut Ui, search: &RamSearch, form: &mut RamSearchForm) -> RamSearchActions {
+    let mut actions = RamSearchActions::default();
+    let started = search.snapshot.is_some();
+
+    ui.horizontal(|ui| {
+        actions.new_search = ui.button("New search").on_hover_text("Snapshot WRAM, HRAM and cartridge RAM").clicked();
+        ui.separator();
+
+        ComboBox::from_id_salt("search_width")
+            .selected_text(format!("{:?}", form.width))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut form.width, SearchWidth::Byte, "Byte");
+                ui.selectable_value(&mut form.width, SearchWidth::Word, "Word");
+            });
+        ComboBox::from_id_salt("search_comparison")
+            .selected_text(form.comparison.name())
+            .width(40.0)
+            .show_ui(ui, |ui| {
+                for comparison in [SearchComparison::Equal, SearchComparison::NotEqual, SearchComparison::Greater, SearchComparison::Less] {
+                    ui.selectable_value(&mut form.comparison, comparison, comparison.name());
+                }
+            });
+        ui.radio_value(&mut form.operand, SearchOperand::Previous, "previous value");
+        ui.radio_value(&mut form.operand, SearchOperand::Value, "value 0x");
+        ui.add_enabled(
+            form.operand == SearchOperand::Value,
+            TextEdit::singleline(&mut form.value).desired_width(50.0),
+        );
+
+        actions.filter = ui.add_enabled(started && search.pending.is_none(), Button::new("Filter")).clicked();
+    });
+
+    if !started {
+        ui.label(RichText::new("Start a new search to snapshot the RAM").color(Color32::from_rgb(150, 150, 150)));
+        return actions;
+    }
+    ui.label(
+        RichText::new(format!("{} candidates", search.candidates.len())).color(Color32::from_rgb(255, 200, 100)),
+    );
+    ui.separator();
+
+    let row_height = ui.text_style_height(&TextStyle::Monospace).max(ui.spacing().interact_size.y);
+    ScrollArea::vertical()
+        .id_salt("ram_search_scroll")
+        .auto_shrink([false, false])
+        .show_rows(ui, row_height, search.candidates.len(), |ui, row_range| {
+            for &address in &search.candidates[row_range] {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(format!("0x{address:04X}")).monospace().color(Color32::from_rgb(100, 200, 255)));
+                    let value = match (search.current(address), search.form.width) {
+                        (Some(value), SearchWidth::Byte) => format!("{value:02X} ({value:>5})"),
+                        (Some(value), SearchWidth::Word) => format!("{value:04X} ({value:>5})"),
+                        (None, _) => "--".to_string(),
+                    };
+                    ui.label(RichText::new(value).monospace());
+                    if ui.small_button("Watch").clicked() {
+                        actions.watch = Some(address);
+                    }
+                    if ui.small_button("Freeze").on_hover_text("Add a GameShark code writing this value every frame").clicked() {
+                        actions.freeze = Some(address);
+                    }
+                });
+            }
+        });
+
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(bytes: Vec<u8>) -> RamSnapshot {
+        RamSnapshot { regions: vec![(0xC000, bytes)] }
+    }
+
+    #[test]
+    fn snapshot_reads_words_inside_a_region() {
+        let snapshot = snapshot(vec![0x34, 0x12, 0xFF]);
+        assert_eq!(snapshot.read(0xC000, SearchWidth::Word), Some(0x1234));
+        assert_eq!(snapshot.read(0xC002, SearchWidth::Byte), Some(0xFF));
+        assert_eq!(snapshot.read(0xC002, SearchWidth::Word), None);
+        assert_eq!(snapshot.read(0xC003, SearchWidth::Byte), None);
+    }
+
+    #[test]
+    fn filters_against_previous_snapshot_or_a_value() {
+        let mut search = RamSearch { pending: Some(SearchStep::Start), ..Default::default() };
+        search.apply_snapshot(snapshot(vec![3, 5, 7, 9]));
+        assert_eq!(search.candidates, vec![0xC000, 0xC001, 0xC002, 0xC003]);
+
+        search.form.comparison = SearchComparison::Less;
+        search.pending = Some(SearchStep::Filter(None));
+        search.apply_snapshot(snapshot(vec![2, 5, 6, 10]));
+        assert_eq!(search.candidates, vec![0xC000, 0xC002]);
+
+        search.form.comparison = SearchComparison::Equal;
+        search.pending = Some(SearchStep::Filter(Some(6)));
+        search.apply_snapshot(snapshot(vec![6, 0, 6, 0]));
+        assert_eq!(search.candidates, vec![0xC000, 0xC002]);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::gui::{TilePalette, TileViewer, VideoSnapshot};
+use crate::ppu::colors_palette::Color;
+
+use eframe::egui::{
+    load::SizedTexture, vec2, Color32, ColorImage, ComboBox, Context, Image, RichText, Sense,
+    TextureOptions, Ui,
+};
+
+const TILE_COUNT: usize = 384;
+const TILES_PER_ROW: usize = 16;
+pub const SHEET_WIDTH: usize = TILES_PER_ROW * 8;
+pub const SHEET_HEIGHT: usize = TILE_COUNT / TILES_PER_ROW * 8;
+const SCALE: f32 = 2.0;
+const LCDC_TILE_DATA_MASK: u8 = 0b0001_0000;
+
+// Same decoding as the PPU, `vram` starts at 0x8000
+pub fn tile_color_index(vram: &[u8], tile: usize, x: usize, y: usize) -> u8 {
+    let lsb_byte = vram[tile * 16 + y * 2];
+    let msb_byte = vram[tile * 16 + y * 2 + 1];
+    let bit_index = 7 - x;
+
+    (((msb_byte >> bit_index) & 1) << 1) | ((lsb_byte >> bit_index) & 1)
+}
+
+pub fn shade(palette: u8, color_index: u8) -> [u8; 3] {
+    Color::from_index((palette >> (color_index * 2)) & 0b11).to_rgb()
+}
+
+fn render_tile_sheet(vram: &[u8], palette: u8) -> Vec<u8> {
+    let mut sheet = vec![0; SHEET_WIDTH * SHEET_HEIGHT * 3];
+    for (pixel, rgb) in sheet.chunks_exact_mut(3).enumerate() {
+        let (x, y) = (pixel % SHEET_WIDTH, pixel / SHEET_WIDTH);
+        let tile = (y / 8) * TILES_PER_ROW + x / 8;
+        rgb.copy_from_slice(&shade(palette, tile_color_index(vram, tile, x % 8, y % 8)));
+    }
+    sheet
+}
+
+/*
+    Tiles 0-127 are only reachable in 0x8000 mode, 256-383 only in 0x8800 mode (signed ids from 0x9000),
+    128-255 are shared by both. Objects always use 0x8000 mode.
+*/
+fn tile_ids(tile: usize) -> (Option<u8>, Option<u8>) {
+    let unsigned_id = (tile < 256).then_some(tile as u8);
+    let signed_id = (tile >= 128).then_some((tile % 256) as u8);
+    (unsigned_id, signed_id)
+}
+
+impl TileViewer {
+    pub fn update_texture(&mut self, ctx: &Context, video: &VideoSnapshot) -> SizedTexture {
+        let sheet = render_tile_sheet(&video.vram, self.palette.value(video));
+        let image = ColorImage::from_rgb([SHEET_WIDTH, SHEET_HEIGHT], &sheet);
+
+        let texture = self
+            .texture
+            .get_or_insert_with(|| ctx.load_texture("vram_tiles", image.clone(), TextureOptions::NEAREST));
+        texture.set(image, TextureOptions::NEAREST);
+
+        SizedTexture::new(
+            texture.id(),
+            vec2(SHEET_WIDTH as f32 * SCALE, SHEET_HEIGHT as f32 * SCALE),
+        )
+    }
+}
+
+pub fn tile_viewer(ui: &mut Ui, texture: SizedTexture, video: &VideoSnapshot, palette: &mut TilePalette) {
+    let uses_8000_mode = video.lcdc & LCDC_TILE_DATA_MASK != 0;
+
+    ui.horizontal(|ui| {
+        ui.label("Palette:");
+        ComboBox::from_id_salt("tile_palette")
+            .selected_text(palette.name())
+            .show_ui(ui, |ui| {
+                for choice in [TilePalette::Bgp, TilePalette::Obp0, TilePalette::Obp1] {
+                    ui.selectable_value(palette, choice, choice.name());
+                }
+            });
+        ui.label(RichText::new(format!("{:08b}", palette.value(video))).monospace());
+
+        ui.separator();
+        ui.label(format!(
+            "BG/Window addressing: {}",
+            if uses_8000_mode { "0x8000" } else { "0x8800" }
+        ));
+    });
+
+    ui.horizontal_top(|ui| {
+        let response = ui.add(Image::new(texture).sense(Sense::hover()));
+
+        ui.vertical(|ui| {
+            let hovered_tile = response.hover_pos().map(|pos| {
+                let local = (pos - response.rect.min) / SCALE;
+                let x = (local.x as usize).min(SHEET_WIDTH - 1);
+                let y = (local.y as usize).min(SHEET_HEIGHT - 1);
+                (y / 8) * TILES_PER_ROW + x / 8
+            });
+            match hovered_tile {
+                Some(tile) => tile_info(ui, tile, uses_8000_mode),
+                None => {
+                    ui.label(RichText::new("Hover a tile").color(Color32::from_rgb(150, 150, 150)));
+                }
+            }
+        });
+    });
+}
+
+fn tile_info(ui: &mut Ui, tile: usize, uses_8000_mode: bool) {
+    let (unsigned_id, signed_id) = tile_ids(tile);
+    let mode_line = |id: Option<u8>, mode: &str, active: bool| {
+        let text = match id {
+            Some(id) => format!("{mode}: id 0x{id:02X}"),
+            None => format!("{mode}: not reachable"),
+        };
+        let color = if active {
+            Color32::from_rgb(100, 255, 100)
+        } else {
+            Color32::from_rgb(150, 150, 150)
+        };
+        RichText::new(text).monospace().color(color)
+    };
+
+    ui.label(RichText::new(format!("Tile {tile}")).strong().color(Color32::from_rgb(255, 200, 100)));
+    ui.label(
+        RichText::new(format!("Address: 0x{:04X}", 0x8000 + tile * 16))
+            .monospace()
+            .color(Color32::from_rgb(100, 200, 255)),
+    );
+    ui.label(mode_line(unsigned_id, "0x8000", uses_8000_mode));
+    ui.label(mode_line(signed_id, "0x8800", !uses_8000_mode));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_sheet_uses_palette_and_layout() {
+        let mut vram = vec![0; 0x2000];
+        // tile 17: first row is color 1, 2, 3 then 0
+        vram[17 * 16] = 0b1010_0000;
+        vram[17 * 16 + 1] = 0b0110_0000;
+
+        let sheet = render_tile_sheet(&vram, 0b1110_0100);
+        let pixel = |x: usize, y: usize| &sheet[(y * SHEET_WIDTH + x) * 3..(y * SHEET_WIDTH + x) * 3 + 3];
+
+        // tile 17 is the second tile of the second row
+        assert_eq!(pixel(8, 8), Color::LightGray.to_rgb());
+        assert_eq!(pixel(9, 8), Color::DarkGray.to_rgb());
+        assert_eq!(pixel(10, 8), Color::Black.to_rgb());
+        assert_eq!(pixel(11, 8), Color::White.to_rgb());
+
+        // an inverted palette maps color 0 to black
+        let inverted = render_tile_sheet(&vram, 0b0001_1011);
+        assert_eq!(inverted[..3], Color::Black.to_rgb());
+    }
+
+    #[test]
+    fn tile_ids_follow_addressing_modes() {
+        assert_eq!(tile_ids(0), (Some(0x00), None));
+        assert_eq!(tile_ids(200), (Some(200), Some(200)));
+        assert_eq!(tile_ids(256), (None, Some(0x00)));
+        assert_eq!(tile_ids(383), (None, Some(0x7F)));
+    }
+}
@@ -0,0 +1,182 @@
+use crate::gui::{TilemapViewer, VideoSnapshot};
+
+use super::tile_viewer::{shade, tile_color_index};
+
+use eframe::egui::{
+    load::SizedTexture, vec2, Color32, ColorImage, Context, Image, Rect, RichText, Sense,
+    Stroke, StrokeKind, TextureOptions, Ui,
+};
+
+const MAP_SIZE: usize = 256; // 32 tiles of 8 pixels
+const TILEMAP_ADDRESSES: [u16; 2] = [0x9800, 0x9C00];
+const SCALE: f32 = 1.5;
+const LCDC_WINDOW_ENABLE_MASK: u8 = 0b0010_0000;
+const LCDC_WINDOW_TILE_MAP_MASK: u8 = 0b0100_0000;
+const LCDC_TILE_DATA_MASK: u8 = 0b0001_0000;
+const LCDC_BG_TILE_MAP_MASK: u8 = 0b0000_1000;
+
+// Address of the tile data for an id read in a tilemap, following LCDC bit 4
+fn tile_data_address(tile_id: u8, lcdc: u8) -> u16 {
+    if lcdc & LCDC_TILE_DATA_MASK != 0 {
+        0x8000 + tile_id as u16 * 16
+    } else {
+        0x9000u16.wrapping_add_signed((tile_id as i8) as i16 * 16)
+    }
+}
+
+fn tile_id_at(video: &VideoSnapshot, map: usize, tile_x: usize, tile_y: usize) -> u8 {
+    let map_offset = (TILEMAP_ADDRESSES[map] - 0x8000) as usize;
+    video.vram[map_offset + tile_y * 32 + tile_x]
+}
+
+fn render_tilemap(video: &VideoSnapshot, map: usize) -> Vec<u8> {
+    let mut image = vec![0; MAP_SIZE * MAP_SIZE * 3];
+    for (pixel, rgb) in image.chunks_exact_mut(3).enumerate() {
+        let (x, y) = (pixel % MAP_SIZE, pixel / MAP_SIZE);
+        let tile_id = tile_id_at(video, map, x / 8, y / 8);
+        let tile = (tile_data_address(tile_id, video.lcdc) - 0x8000) as usize / 16;
+        rgb.copy_from_slice(&shade(video.bgp, tile_color_index(&video.vram, tile, x % 8, y % 8)));
+    }
+    image
+}
+
+impl TilemapViewer {
+    pub fn update_textures(&mut self, ctx: &Context, video: &VideoSnapshot) -> [SizedTexture; 2] {
+        [0, 1].map(|map| {
+            let image = ColorImage::from_rgb([MAP_SIZE, MAP_SIZE], &render_tilemap(video, map));
+            let texture = self.textures[map].get_or_insert_with(|| {
+                ctx.load_texture(format!("tilemap_{map}"), image.clone(), TextureOptions::NEAREST)
+            });
+            texture.set(image, TextureOptions::NEAREST);
+
+            SizedTexture::new(texture.id(), vec2(MAP_SIZE as f32 * SCALE, MAP_SIZE as f32 * SCALE))
+        })
+    }
+}
+
+pub fn tilemap_viewer(ui: &mut Ui, textures: [SizedTexture; 2], video: &VideoSnapshot) {
+    let bg_map = (video.lcdc & LCDC_BG_TILE_MAP_MASK != 0) as usize;
+    let window_map = (video.lcdc & LCDC_WINDOW_TILE_MAP_MASK != 0) as usize;
+    let window_enabled = video.lcdc & LCDC_WINDOW_ENABLE_MASK != 0;
+
+    ui.horizontal(|ui| {
+        ui.label(RichText::new(format!("SCX: {:3}  SCY: {:3}", video.scx, video.scy)).monospace());
+        ui.separator();
+        ui.label(RichText::new(format!("WX: {:3}  WY: {:3}", video.wx, video.wy)).monospace());
+        ui.separator();
+        ui.label(RichText::new("■ viewport").color(Color32::from_rgb(255, 100, 100)));
+        ui.label(RichText::new("■ window").color(Color32::from_rgb(100, 200, 255)));
+    });
+
+    let mut hovered = None;
+    ui.horizontal_top(|ui| {
+        for (map, texture) in textures.into_iter().enumerate() {
+            ui.vertical(|ui| {
+                let mut title = format!("0x{:04X}", TILEMAP_ADDRESSES[map]);
+                if map == bg_map {
+                    title.push_str(" (BG)");
+                }
+                if map == window_map && window_enabled {
+                    title.push_str(" (Window)");
+                }
+                ui.label(RichText::new(title).strong().color(Color32::from_rgb(255, 200, 100)));
+
+                let response = ui.add(Image::new(texture).sense(Sense::hover()));
+                let painter = ui.painter_at(response.rect);
+                let to_screen = |x: f32, y: f32| response.rect.min + vec2(x, y) * SCALE;
+
+                if map == bg_map {
+                    // the viewport wraps around the map, draw it once per overlapping copy of the map
+                    for (offset_x, offset_y) in [(0.0, 0.0), (-256.0, 0.0), (0.0, -256.0), (-256.0, -256.0)] {
+                        let min = to_screen(video.scx as f32 + offset_x, video.scy as f32 + offset_y);
+                        let rect = Rect::from_min_size(min, vec2(160.0, 144.0) * SCALE);
+                        painter.rect_stroke(rect, 0.0, Stroke::new(2.0, Color32::from_rgb(255, 100, 100)), StrokeKind::Inside);
+                    }
+                }
+
+                // the window always starts at the top left of its map, only the part on screen is drawn
+                let window_x = video.wx.saturating_sub(7) as f32;
+                if map == window_map && window_enabled && window_x < 160.0 && video.wy < 144 {
+                    let size = vec2(160.0 - window_x, 144.0 - video.wy as f32);
+                    let rect = Rect::from_min_size(to_screen(0.0, 0.0), size * SCALE);
+                    painter.rect_stroke(rect, 0.0, Stroke::new(2.0, Color32::from_rgb(100, 200, 255)), StrokeKind::Inside);
+                }
+
+                if let Some(pos) = response.hover_pos() {
+                    let local = (pos - response.rect.min) / SCALE;
+                    let tile_x = (local.x as usize / 8).min(31);
+                    let tile_y = (local.y as usize / 8).min(31);
+                    hovered = Some((map, tile_x, tile_y));
+
+                    let tile_rect = Rect::from_min_size(
+                        to_screen(tile_x as f32 * 8.0, tile_y as f32 * 8.0),
+                        vec2(8.0, 8.0) * SCALE,
+                    );
+                    painter.rect_stroke(tile_rect, 0.0, Stroke::new(1.0, Color32::YELLOW), StrokeKind::Outside);
+                }
+            });
+        }
+
+        ui.vertical(|ui| match hovered {
+            Some((map, tile_x, tile_y)) => tile_info(ui, video, map, tile_x, tile_y),
+            None => {
+                ui.label(RichText::new("Hover a tile").color(Color32::from_rgb(150, 150, 150)));
+            }
+        });
+    });
+}
+
+fn tile_info(ui: &mut Ui, video: &VideoSnapshot, map: usize, tile_x: usize, tile_y: usize) {
+    let map_address = TILEMAP_ADDRESSES[map] + (tile_y * 32 + tile_x) as u16;
+    let tile_id = tile_id_at(video, map, tile_x, tile_y);
+
+    ui.label(
+        RichText::new(format!("Tile ({tile_x}, {tile_y})"))
+            .strong()
+            .color(Color32::from_rgb(255, 200, 100)),
+    );
+    ui.label(
+        RichText::new(format!("Map address: 0x{map_address:04X}"))
+            .monospace()
+            .color(Color32::from_rgb(100, 200, 255)),
+    );
+    ui.label(RichText::new(format!("Tile ID: 0x{tile_id:02X}")).monospace());
+    ui.label(
+        RichText::new(format!("Tile data: 0x{:04X}", tile_data_address(tile_id, video.lcdc)))
+            .monospace()
+            .color(Color32::from_rgb(100, 200, 255)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ppu::colors_palette::Color;
+
+    #[test]
+    fn tile_data_address_follows_lcdc() {
+        assert_eq!(tile_data_address(0x00, 0b0001_0000), 0x8000);
+        assert_eq!(tile_data_address(0x80, 0b0001_0000), 0x8800);
+        assert_eq!(tile_data_address(0x00, 0x00), 0x9000);
+        assert_eq!(tile_data_address(0x7F, 0x00), 0x97F0);
+        assert_eq!(tile_data_address(0x80, 0x00), 0x8800);
+    }
+
+    #[test]
+    fn render_tilemap_reads_the_selected_map() {
+        let mut video = VideoSnapshot {
+            lcdc: 0b1001_0000,
+            bgp: 0b1110_0100,
+            ..VideoSnapshot::default()
+        };
+        // tile 1 is fully black, placed at (1, 0) in the second map only
+        video.vram[16..32].fill(0xFF);
+        video.vram[0x9C01 - 0x8000] = 0x01;
+
+        let first = render_tilemap(&video, 0);
+        let second = render_tilemap(&video, 1);
+        assert_eq!(first[8 * 3..8 * 3 + 3], Color::White.to_rgb());
+        assert_eq!(second[8 * 3..8 * 3 + 3], Color::Black.to_rgb());
+        assert_eq!(second[..3], Color::White.to_rgb());
+    }
+}
@@ -0,0 +1,136 @@
+use crate::gui::PpuTimelineViewer;
+use crate::ppu::timeline::{FrameTimeline, RegisterWrite, ScanlineTiming};
+
+use eframe::egui::{vec2, Checkbox, Color32, Rect, RichText, ScrollArea, Sense, TextStyle, Ui};
+
+const SCANLINE_DOTS: f32 = 456.0;
+const BAR_HEIGHT: f32 = 10.0;
+
+// Order in which the modes happen on a line, with the index of their STAT bits
+const MODES: [(usize, &str, Color32); 4] = [
+    (2, "Mode 2 (OAM)", Color32::from_rgb(100, 150, 255)),
+    (3, "Mode 3 (transfer)", Color32::from_rgb(100, 220, 100)),
+    (0, "Mode 0 (HBlank)", Color32::from_rgb(110, 110, 110)),
+    (1, "Mode 1 (VBlank)", Color32::from_rgb(170, 100, 200)),
+];
+
+#[derive(Debug)]
+pub struct TimelineActions {
+    pub recording: bool,
+    pub per_dot: bool,
+}
+
+fn register_name(address: u16) -> String {
+    match address {
+        0xFF40 => "LCDC".to_string(),
+        0xFF41 => "STAT".to_string(),
+        0xFF42 => "SCY".to_string(),
+        0xFF43 => "SCX".to_string(),
+        0xFF45 => "LYC".to_string(),
+        0xFF46 => "DMA".to_string(),
+        0xFF47 => "BGP".to_string(),
+        0xFF48 => "OBP0".to_string(),
+        0xFF49 => "OBP1".to_string(),
+        0xFF4A => "WY".to_string(),
+        0xFF4B => "WX".to_string(),
+        _ => format!("{address:04X}"),
+    }
+}
+
+fn describe_write(write: &RegisterWrite, per_dot: bool) -> String {
+    if per_dot {
+        format!("{}={:02X}@{}", register_name(write.address), write.value, write.dot)
+    } else {
+        format!("{}={:02X}", register_name(write.address), write.value)
+    }
+}
+
+fn describe_timing(timing: &ScanlineTiming) -> String {
+    let mode_3 = timing.mode_dots[3];
+    if mode_3 == 0 {
+        return String::new();
+    }
+    format!(
+        "M3 {} (sprites +{}, SCX +{})",
+        mode_3, timing.sprite_dots, timing.scx_discard
+    )
+}
+
+pub fn timeline_viewer(ui: &mut Ui, viewer: &PpuTimelineViewer) -> TimelineActions {
+    let mut actions = TimelineActions {
+        recording: viewer.recording,
+        per_dot: viewer.per_dot,
+    };
+
+    ui.horizontal(|ui| {
+        ui.add(Checkbox::new(&mut actions.recording, "Record"));
+        ui.add(Checkbox::new(&mut actions.per_dot, "Per-dot writes"));
+        ui.separator();
+        for (_, name, color) in MODES {
+            ui.label(RichText::new(format!("■ {name}")).color(color));
+        }
+        ui.label(RichText::new("| register write").color(Color32::YELLOW));
+    });
+    ui.separator();
+
+    match &viewer.frame {
+        Some(frame) => scanlines(ui, frame, viewer.per_dot),
+        None => {
+            ui.label(RichText::new("No frame recorded yet").color(Color32::from_rgb(150, 150, 150)));
+        }
+    }
+
+    actions
+}
+
+fn scanlines(ui: &mut Ui, frame: &FrameTimeline, per_dot: bool) {
+    let row_height = ui.text_style_height(&TextStyle::Monospace).max(BAR_HEIGHT);
+
+    ScrollArea::vertical()
+        .id_salt("timeline_scroll")
+        .auto_shrink([false, false])
+        .show_rows(ui, row_height, frame.lines.len(), |ui, row_range| {
+            for line in row_range {
+                let timing = &frame.lines[line];
+                let writes: Vec<&RegisterWrite> = frame
+                    .writes
+                    .iter()
+                    .filter(|write| write.line as usize == line)
+                    .collect();
+
+                ui.horizontal(|ui| {
+                    ui.label(
+                        RichText::new(format!("LY {line:3}"))
+                            .monospace()
+                            .color(Color32::from_rgb(100, 200, 255)),
+                    );
+
+                    let (rect, _) = ui.allocate_exact_size(vec2(SCANLINE_DOTS, BAR_HEIGHT), Sense::hover());
+                    let painter = ui.painter_at(rect);
+                    let mut start = 0.0;
+                    for (mode, _, color) in MODES {
+                        let dots = timing.mode_dots[mode] as f32;
+                        if dots > 0.0 {
+                            let segment = Rect::from_min_size(rect.min + vec2(start, 0.0), vec2(dots, BAR_HEIGHT));
+                            painter.rect_filled(segment, 0.0, color);
+                            start += dots;
+                        }
+                    }
+                    if per_dot {
+                        for write in writes.iter() {
+                            let x = rect.min.x + write.dot as f32;
+                            painter.vline(x, rect.y_range(), (2.0, Color32::YELLOW));
+                        }
+                    }
+
+                    ui.label(RichText::new(describe_timing(timing)).monospace());
+                    let writes_text: Vec<String> = writes.iter().map(|write| describe_write(write, per_dot)).collect();
+                    ui.label(
+                        RichText::new(writes_text.join(" "))
+                            .monospace()
+                            .color(Color32::YELLOW),
+                    );
+                });
+            }
+        });
+}
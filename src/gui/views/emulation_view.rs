@@ -1,7 +1,14 @@
 use crate::gui::{
-        AppState, CoreGameDevice, CoreGameOptions, DebuggingDevice, EmulationDevice, SelectionDevice, WatchedAdresses
+        AppState, CheatForm, CoreGameDevice, CoreGameOptions, DebuggingDevice, EmulationDevice, EventViewer, GraphicsTab, IoSnapshot,
+    MemoryView, MemoryViewer, OamViewer, PokeForm, PpuTimelineViewer, RamSearch,
+    RegistersSnapshot, SelectionDevice, TileViewer, TilemapViewer, VideoSnapshot, WatchedAdresses
     };
 
+use crate::model::Model;
+use crate::ppu::layers::LayerToggles;
+
+use std::path::PathBuf;
+
 use std::sync::atomic::Ordering;
 
 use std::time::{Instant};
@@ -40,19 +47,38 @@ impl From<EmulationDevice> for DebuggingDevice {
             .core_game
             .global_is_debug
             .fetch_xor(true, Ordering::Relaxed);
-        Self {
+        let device = Self {
             core_game: original.core_game,
             next_instructions: Vec::new(),
             watched_adress: WatchedAdresses {
                 addresses_n_values: Vec::new(),
             },
-            registers: (0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+            registers: RegistersSnapshot::default(),
             is_step: false,
+            call_stack: Vec::new(),
+            jump_history: Vec::new(),
+            breakpoints: Vec::new(),
             watched_address_value: 0,
             nb_instruction: 0,
             error_message: None,
             hex_string: String::new(),
-        }
+            editing: None,
+            poke_form: PokeForm::default(),
+            memory_viewer: MemoryViewer::default(),
+            video: VideoSnapshot::default(),
+            graphics_tab: GraphicsTab::default(),
+            tile_viewer: TileViewer::default(),
+            tilemap_viewer: TilemapViewer::default(),
+            oam_viewer: OamViewer::default(),
+            timeline_viewer: PpuTimelineViewer::default(),
+            layers: LayerToggles::default(),
+            io: IoSnapshot::default(),
+            event_viewer: EventViewer::default(),
+            cheat_form: CheatForm::default(),
+            ram_search: RamSearch::default(),
+        };
+        device.request_memory_dump(MemoryView::Bus);
+        device
     }
 }
 
@@ -62,6 +88,11 @@ impl From<SelectionDevice> for EmulationDevice {
         let options = CoreGameOptions {
             rom_path,
             boot_rom: true,
+            gdb_port: None,
+            cheats: Vec::new(),
+            model: Model::default(),
+            boot_rom_dir: PathBuf::from("boot-roms"),
+            strict_access: false,
         };
         let core_game = CoreGameDevice::new(options);
         Self { core_game}
@@ -4,9 +4,12 @@ mod cli;
 mod cpu;
 mod debugger;
 mod gameboy;
+mod gdb;
 mod gui;
 mod mmu;
+mod model;
 mod ppu;
+mod symbols;
 mod file;
 
 use gui::GraphicalApp;
@@ -33,7 +36,12 @@ async fn main() {
     let app = if let Some(rom_path) = arguments.rom_path {
         let options = EmulationAppOptions::new(
             rom_path,
-            arguments.boot_rom
+            arguments.boot_rom,
+            arguments.gdb_port,
+            arguments.cheats,
+            arguments.model,
+            arguments.boot_rom_dir,
+            arguments.strict_access,
         );
         GraphicalApp::create_emulation_app(options)
     } else {
@@ -5,19 +5,34 @@ use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::{RwLock, RwLockReadGuard};
 
+pub mod cgb;
+pub mod cheats;
+pub mod compat_palettes;
+pub mod events;
+pub mod inspector;
 pub mod interrupt;
 pub mod mbc;
 pub mod timers;
 pub mod oam;
+pub mod sgb;
 pub mod apu;
 
 use self::timers::Timers;
+use crate::mmu::inspector::MemoryInspector;
+use crate::mmu::cgb::{HardwareMode, Hdma, PaletteRam, SpeedSwitch, Vram, Wram};
+use crate::mmu::cheats::Cheats;
+use crate::mmu::compat_palettes::PaletteChoice;
+use crate::mmu::events::EventKind;
 use crate::mmu::interrupt::Interrupt;
 use crate::mmu::interrupt::InterruptController;
 use crate::mmu::mbc::Mbc;
 use crate::mmu::oam::Oam;
+use crate::mmu::sgb::Sgb;
 use crate::mmu::apu::Apu;
 
+// DMG/MGB/SGB, Stadium 2 and CGB/AGB boot ROM images
+const BOOT_ROM_SIZES: [usize; 3] = [0x0100, 0x03F0, 0x0900];
+
 #[derive(PartialEq, Eq, Debug)]
 pub enum MemoryRegion {
     Mbc,                // 0x000-0x7FFF: read-only
@@ -83,67 +98,176 @@ impl<T: Mbc> Into<Rc<RefCell<Mmu<T>>>> for Mmu<T> {
 pub struct Mmu<T: Mbc> {
     data: [u8; 0x10000], // 0xFFFF (65535) + 1 = 0x10000 (65536)
     cart: T,
+    mode: HardwareMode,
+    vram: Vram,
+    wram: Wram,
+    key1: SpeedSwitch,
+    hdma: Hdma,
+    bg_palettes: PaletteRam,
+    obj_palettes: PaletteRam,
     interrupts: InterruptController,
     timers: Timers,
     oam: RwLock<Oam>,
     apu: Apu,
     boot_enable: bool,
-    boot_rom: [u8; 0x0100],
+    boot_rom: Vec<u8>,
     dpad_state: u8, // for joypad
     button_state: u8, // for joypad
     accessed_oam_ram: u8, // for OAM Bug
     dma_source: u16,
     pub dma_index: u8,
+    lcd_writes: Option<Vec<(u16, u8)>>, // CPU writes to 0xFF40-0xFF4B, only kept for the PPU timeline
+    pub cheats: Cheats,
+    sgb: Sgb,
+    strict_access: bool, // CPU locked out of VRAM/OAM like on hardware, off by default to compare
 }
 
 impl<T: Mbc> Mmu<T> {
     pub fn new(rom_image: &[u8]) -> Result<Self, String> {
+        Self::with_mode(rom_image, HardwareMode::from_header(rom_image))
+    }
+
+    // The mode comes from the console model, a CGB cart on a DMG runs as a DMG
+    pub fn with_mode(rom_image: &[u8], mode: HardwareMode) -> Result<Self, String> {
        Ok(Mmu {
             apu: Apu::default(),
             data: [0xFF; 0x10000],
             cart: T::new(rom_image)?,
+            mode,
+            vram: Vram::new(mode),
+            wram: Wram::new(mode),
+            key1: SpeedSwitch::default(),
+            hdma: Hdma::default(),
+            bg_palettes: PaletteRam::default(),
+            obj_palettes: PaletteRam::default(),
             interrupts: InterruptController::new(),
             timers: Timers::default(),
             oam: RwLock::new(Oam::default()),
             boot_enable: false,
-            boot_rom: [0xFF; 0x0100],
+            boot_rom: Vec::new(),
             dpad_state: 0x0F,
             button_state: 0x0F,
             accessed_oam_ram: 0xFF, // 0xFF means we're not in OAM search mode
             dma_source: 0x0,
             dma_index: 0xFF, // 0xFF means a DMA isn't happening
+            lcd_writes: None,
+            cheats: Cheats::default(),
+            sgb: Sgb::default(),
+            strict_access: false,
         })
     }
 
-    pub fn load_boot_rom(&mut self, boot_rom: [u8; 0x0100]) {
+    /*
+        The DMG and SGB boot ROMs map 0x0000-0x00FF. The bigger CGB and Stadium 2 images also map
+        0x0200 up to their end, the cartridge header at 0x0100-0x01FF stays visible in between.
+    */
+    pub fn load_boot_rom(&mut self, boot_rom: Vec<u8>) -> Result<(), String> {
+        if !BOOT_ROM_SIZES.contains(&boot_rom.len()) {
+            return Err(format!(
+                "Boot ROM is {} bytes, expected 256 (DMG, SGB), 1008 (Stadium 2) or 2304 (CGB, AGB)",
+                boot_rom.len()
+            ));
+        }
+
         self.boot_rom = boot_rom;
         self.boot_enable = true;
+        Ok(())
+    }
+
+    fn is_boot_rom_mapped(&self, addr: u16) -> bool {
+        self.boot_enable
+            && (addr as usize) < self.boot_rom.len()
+            && !(0x0100..0x0200).contains(&addr)
     }
 
     pub fn tick_timers(&mut self) {
         if self.timers.tick() {
-            let interrupt_flags_addr = MemoryRegion::InterruptFlag.to_address();
-            let mut interrupt_flags = self.read_byte(interrupt_flags_addr);
-            interrupt_flags |= 0b100;
-            self.write_byte(interrupt_flags_addr, interrupt_flags);
+            self.interrupts_request(Interrupt::Timer);
+        }
+    }
+
+    pub fn record_events(&mut self, enabled: bool) {
+        self.timers.events.set_enabled(enabled);
+        self.interrupts.events.set_enabled(enabled);
+    }
+
+    // Timer events come first, an overflow and the request it causes happen on the same tick
+    pub fn take_events(&mut self) -> Vec<EventKind> {
+        let mut events = self.timers.events.take();
+        events.append(&mut self.interrupts.events.take());
+        events
+    }
+
+    // GameShark bank 0x8X targets cartridge RAM bank X, anything else writes to what is mapped
+    pub fn apply_ram_cheats(&mut self) {
+        let writes: Vec<(u8, u16, u8)> = self.cheats.ram_writes().collect();
+        for (bank, address, value) in writes {
+            match MemoryRegion::from(address) {
+                MemoryRegion::ERam if bank & 0xF0 == 0x80 => {
+                    let _ = self.cart.write_ram_bank((bank & 0x0F) as usize, (address - 0xA000) as usize, value);
+                }
+                _ => self.poke(address, value),
+            }
+        }
+    }
+
+    pub fn set_strict_access(&mut self, enabled: bool) {
+        self.strict_access = enabled;
+    }
+
+    /*
+        With strict access the CPU gets 0xFF and its writes are dropped where the PPU or the OAM DMA
+        hold the bus: VRAM in mode 3, OAM in modes 2 and 3, everything below 0xFF00 during a DMA.
+        The PPU updates STAT on the dot it changes mode and runs after the CPU in a T-cycle, so an
+        access on the dot of a mode change still sees the previous mode, like STAT does.
+    */
+    pub(crate) fn is_locked_for_cpu(&self, addr: u16) -> bool {
+        if !self.strict_access {
+            return false;
+        }
+        if self.dma_index != 0xFF && addr < 0xFF00 {
+            return true;
+        }
+        if self.data[0xFF40] & 0x80 == 0 {
+            return false;
+        }
+
+        let ppu_mode = self.data[0xFF41] & 0b11;
+        match MemoryRegion::from(addr) {
+            MemoryRegion::Vram => ppu_mode == 3,
+            MemoryRegion::Oam => ppu_mode == 2 || ppu_mode == 3,
+            _ => false,
         }
     }
 
     pub fn read_byte(&self, addr: u16) -> u8 {
-        if self.boot_enable && addr <= 0x00FF {
+        if self.is_locked_for_cpu(addr) {
+            // The OAM bug still corrupts the row the PPU is reading
+            if MemoryRegion::from(addr) == MemoryRegion::Oam && self.accessed_oam_ram != 0xFF {
+                self.oam.write().unwrap().trigger_oam_bug_read(self.accessed_oam_ram);
+            }
+            return 0xFF;
+        }
+        self.read_from_bus(addr)
+    }
+
+    // What is mapped at `addr`, the DMAs read their source through here
+    fn read_from_bus(&self, addr: u16) -> u8 {
+        if self.is_boot_rom_mapped(addr) {
             return self.boot_rom[addr as usize];
         }
         
         match MemoryRegion::from(addr) {
-            MemoryRegion::Mbc | MemoryRegion::ERam => self.cart.read(addr),
-            MemoryRegion::Mram => {
-                let mirror = addr - 0x2000;
-
-                self.data[mirror as usize]
-            }
+            MemoryRegion::Mbc => self.cheats.patch_rom_read(addr, self.cart.read(addr)),
+            MemoryRegion::ERam => self.cart.read(addr),
+            MemoryRegion::Vram => self.vram.read(addr),
+            MemoryRegion::Wram => self.wram.read(addr),
+            MemoryRegion::Mram => self.wram.read(addr - 0x2000),
             MemoryRegion::Timers => self.timers.read_byte(addr),
             MemoryRegion::Io => {
-                if addr == 0xFF00 {
+                if let Some(val) = self.read_cgb_register(addr) {
+                    val
+                } else if addr == 0xFF00 {
                     let selection = self.data[0xFF00] & 0b0011_0000;
                     let mut result = 0x0F;
 
@@ -153,6 +277,14 @@ impl<T: Mbc> Mmu<T> {
                     if selection & 0b0010_0000 == 0 {
                         result &= self.button_state;
                     }
+                    // Only player 1 has a controller, with MLT_REQ the other ones show their id
+                    if self.mode == HardwareMode::Sgb {
+                        if selection == 0b0011_0000 {
+                            result = self.sgb.joypad_id();
+                        } else if self.sgb.player() != 0 {
+                            result = 0x0F;
+                        }
+                    }
 
                     0b1100_0000 | selection | result
                 } else {
@@ -174,29 +306,49 @@ impl<T: Mbc> Mmu<T> {
     }
 
     pub fn write_byte(&mut self, addr: u16, val: u8) {
-        if val != 0 && addr == 0xFF50 {
-            self.data[addr as usize] = val;
-            self.boot_enable = false;
+        if let Some(writes) = self.lcd_writes.as_mut()
+            && (0xFF40..=0xFF4B).contains(&addr)
+        {
+            writes.push((addr, val));
+        }
 
+        if self.is_locked_for_cpu(addr) {
+            if MemoryRegion::from(addr) == MemoryRegion::Oam && self.accessed_oam_ram != 0xFF {
+                self.oam.write().unwrap().trigger_oam_bug_write(self.accessed_oam_ram);
+            }
+            return;
+        }
+
+        // Once unmapped the boot ROM can't come back, BANK then reads 0xFF
+        if addr == 0xFF50 {
+            if val & 0x01 != 0 {
+                self.boot_enable = false;
+            }
             return;
         }
 
         match MemoryRegion::from(addr) {
             MemoryRegion::Mbc | MemoryRegion::ERam => self.cart.write(addr, val),
-            MemoryRegion::Mram => {
-                let mirror = addr - 0x2000;
-
-                self.data[mirror as usize] = val;
-            }
+            MemoryRegion::Vram => self.vram.write(addr, val),
+            MemoryRegion::Wram => self.wram.write(addr, val),
+            MemoryRegion::Mram => self.wram.write(addr - 0x2000, val),
             MemoryRegion::Timers => self.timers.write_byte(addr, val),
             MemoryRegion::Io => {
                 // The CPU can only change the bits 4 and 5. The emulator use methods to write into the memory.
-                if addr == 0xFF00 {
+                if self.write_cgb_register(addr, val) {
+                    // VBK, SVBK and KEY1 don't live in data
+                } else if addr == 0xFF00 {
                     let selection_bits = val & 0b0011_0000;
                     let current_inputs = self.data[0xFF00] & 0x0F;
                     self.data[0xFF00] = 0b1100_0000 | selection_bits | current_inputs;
 
                     self.update_joypad_register();
+                    if self.mode == HardwareMode::Sgb
+                        && let Some(transfer) = self.sgb.write_p1(val)
+                    {
+                        let data = self.sgb_transfer_data();
+                        self.sgb.finish_transfer(transfer, &data);
+                    }
                 } else if addr == 0xFF41 { // STAT register
                     let current_val = self.data[0xFF41 as usize];
 
@@ -226,6 +378,173 @@ impl<T: Mbc> Mmu<T> {
         }
     }
 
+    // VBK, KEY1, HDMA, SVBK and the palette registers only exist on CGB, a DMG keeps them as plain bytes
+    fn read_cgb_register(&self, addr: u16) -> Option<u8> {
+        match (self.mode, addr) {
+            (HardwareMode::Cgb, 0xFF4D) => Some(self.key1.read()),
+            (HardwareMode::Cgb, 0xFF4F) => Some(self.vram.read_vbk()),
+            (HardwareMode::Cgb, 0xFF51..=0xFF54) => Some(0xFF),
+            (HardwareMode::Cgb, 0xFF55) => Some(self.hdma.read_control()),
+            (HardwareMode::Cgb, 0xFF68) => Some(self.bg_palettes.read_spec()),
+            (HardwareMode::Cgb, 0xFF69) => Some(self.bg_palettes.read_data()),
+            (HardwareMode::Cgb, 0xFF6A) => Some(self.obj_palettes.read_spec()),
+            (HardwareMode::Cgb, 0xFF6B) => Some(self.obj_palettes.read_data()),
+            (HardwareMode::Cgb, 0xFF70) => Some(self.wram.read_svbk()),
+            _ => None,
+        }
+    }
+
+    fn write_boot_palette(&mut self, addr: u16, val: u8) -> bool {
+        match addr {
+            0xFF68 => self.bg_palettes.write_spec(val),
+            0xFF69 => self.bg_palettes.write_data(val),
+            0xFF6A => self.obj_palettes.write_spec(val),
+            _ => self.obj_palettes.write_data(val),
+        }
+        true
+    }
+
+    fn write_cgb_register(&mut self, addr: u16, val: u8) -> bool {
+        match (self.mode, addr) {
+            // KEY0, the CGB boot ROM writes 0x04 there before starting a DMG cart
+            (HardwareMode::Cgb, 0xFF4C) if self.boot_enable => {
+                if val & 0x04 != 0 {
+                    self.mode = HardwareMode::CgbCompat;
+                }
+            }
+            (HardwareMode::Cgb, 0xFF4D) => self.key1.write(val),
+            (HardwareMode::Cgb, 0xFF4F) => self.vram.write_vbk(val),
+            (HardwareMode::Cgb, 0xFF51..=0xFF54) => self.hdma.write_register(addr, val),
+            (HardwareMode::Cgb, 0xFF55) => {
+                let in_hblank = self.data[0xFF41] & 0b11 == 0;
+                self.hdma.write_control(val, in_hblank)
+            }
+            // The boot ROM loads the compatibility palettes after writing KEY0
+            (HardwareMode::CgbCompat, 0xFF68..=0xFF6B) if self.boot_enable => return self.write_boot_palette(addr, val),
+            (HardwareMode::Cgb, 0xFF68) => self.bg_palettes.write_spec(val),
+            (HardwareMode::Cgb, 0xFF69) => self.bg_palettes.write_data(val),
+            (HardwareMode::Cgb, 0xFF6A) => self.obj_palettes.write_spec(val),
+            (HardwareMode::Cgb, 0xFF6B) => self.obj_palettes.write_data(val),
+            (HardwareMode::Cgb, 0xFF70) => self.wram.write_svbk(val),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn mode(&self) -> HardwareMode {
+        self.mode
+    }
+
+    // Does what the CGB boot ROM does for a DMG cart, a CGB cart keeps its own palettes
+    pub fn set_compat_palette(&mut self, choice: PaletteChoice) {
+        let Some(header) = self.cart.rom_bank(0) else { return };
+        if self.mode == HardwareMode::Sgb || HardwareMode::from_header(header) == HardwareMode::Cgb {
+            return;
+        }
+        let palette = choice.resolve(header);
+
+        match palette {
+            Some(palette) => {
+                self.mode = HardwareMode::CgbCompat;
+                self.bg_palettes.load(0, palette.bg);
+                self.obj_palettes.load(0, palette.obj0);
+                self.obj_palettes.load(1, palette.obj1);
+            }
+            None => self.mode = HardwareMode::Dmg,
+        }
+    }
+
+    // None while the SGB keeps the screen frozen
+    pub fn sgb_color(&self, x: usize, y: usize, shade: u8) -> Option<u16> {
+        self.sgb.color(x, y, shade)
+    }
+
+    // RGB555 border, only when a CHR_TRN/PCT_TRN changed it
+    pub fn take_sgb_border(&mut self) -> Option<Vec<u16>> {
+        self.sgb.take_border_change().then(|| self.sgb.render_border())
+    }
+
+    /*
+        The SGB reads the 4KB shown on screen: games map tiles 0-255 in order on the background, so we
+        follow the background map 20 tiles per row instead of capturing the LCD.
+    */
+    fn sgb_transfer_data(&self) -> Vec<u8> {
+        let lcdc = self.data[0xFF40];
+        let map_base: u16 = if lcdc & 0x08 != 0 { 0x9C00 } else { 0x9800 };
+
+        (0..256u16)
+            .flat_map(|i| {
+                let tile = self.vram.read_bank(0, map_base + (i / 20) * 32 + i % 20);
+                let tile_address = if lcdc & 0x10 != 0 {
+                    0x8000 + tile as u16 * 16
+                } else {
+                    0x9000u16.wrapping_add_signed(tile as i8 as i16 * 16)
+                };
+                (0..16).map(move |offset| tile_address + offset)
+            })
+            .map(|addr| self.vram.read_bank(0, addr))
+            .collect()
+    }
+
+    pub fn read_vram(&self, bank: usize, addr: u16) -> u8 {
+        self.vram.read_bank(bank, addr)
+    }
+
+    // RGB555 colors from the CGB palette RAM
+    pub fn bg_color(&self, palette: u8, color_index: u8) -> u16 {
+        self.bg_palettes.color(palette, color_index)
+    }
+
+    pub fn obj_color(&self, palette: u8, color_index: u8) -> u16 {
+        self.obj_palettes.color(palette, color_index)
+    }
+
+    pub fn is_double_speed(&self) -> bool {
+        self.key1.is_double_speed()
+    }
+
+    // Called by STOP, returns false when no switch was armed through KEY1
+    pub fn switch_speed(&mut self) -> bool {
+        self.mode == HardwareMode::Cgb && self.key1.switch()
+    }
+
+    pub fn record_lcd_writes(&mut self, enabled: bool) {
+        self.lcd_writes = enabled.then(Vec::new);
+    }
+
+    pub fn take_lcd_writes(&mut self) -> Vec<(u16, u8)> {
+        self.lcd_writes.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    // Debugger access straight into the cartridge banks, the MBC registers are left untouched
+    pub fn write_rom_bank(&mut self, bank: usize, offset: usize, val: u8) -> Result<(), String> {
+        self.cart.write_rom_bank(bank, offset, val)
+    }
+
+    pub fn write_ram_bank(&mut self, bank: usize, offset: usize, val: u8) -> Result<(), String> {
+        self.cart.write_ram_bank(bank, offset, val)
+    }
+
+    pub fn rom_bank(&self, bank: usize) -> Option<&[u8]> {
+        self.cart.rom_bank(bank)
+    }
+
+    pub fn ram_bank(&self, bank: usize) -> Option<&[u8]> {
+        self.cart.ram_bank(bank)
+    }
+
+    pub fn rom_bank_count(&self) -> usize {
+        self.cart.rom_bank_count()
+    }
+
+    pub fn ram_bank_count(&self) -> usize {
+        self.cart.ram_bank_count()
+    }
+
+    pub fn mapped_rom_bank(&self) -> usize {
+        self.cart.mapped_rom_bank()
+    }
+
     pub fn read_interrupt_enable(&self) -> u8 {
         self.interrupts.read_interrupt_enable()
     }
@@ -300,7 +619,7 @@ impl<T: Mbc> Mmu<T> {
     }
 
     pub fn tick_dma(&mut self) {
-        let byte = self.read_byte(self.dma_source + self.dma_index as u16);
+        let byte = self.read_from_bus(self.dma_source + self.dma_index as u16);
 
         let mut oam = self.oam.write().unwrap();
         oam.write(0xFE00 + self.dma_index as u16, byte);
@@ -309,6 +628,62 @@ impl<T: Mbc> Mmu<T> {
 
         if self.dma_index == 160 { self.dma_index = 0xFF; }
     }
+
+    // One M-cycle of HDMA: 2 bytes in normal speed, 1 in double speed
+    pub fn tick_hdma(&mut self) {
+        let bytes = if self.is_double_speed() { 1 } else { 2 };
+        for _ in 0..bytes {
+            if let Some((source, destination)) = self.hdma.next_transfer() {
+                let byte = self.read_from_bus(source);
+                self.vram.write(destination, byte);
+            }
+        }
+    }
+
+    // The CPU is halted while a block is copied
+    pub fn is_hdma_copying(&self) -> bool {
+        self.hdma.is_copying()
+    }
+
+    pub fn start_hdma_block(&mut self) {
+        self.hdma.start_hblank_block();
+    }
+}
+
+impl<T: Mbc> MemoryInspector for Mmu<T> {
+    fn peek(&self, addr: u16) -> u8 {
+        match MemoryRegion::from(addr) {
+            MemoryRegion::Mbc | MemoryRegion::ERam => self.cart.peek(addr),
+            MemoryRegion::Vram => self.vram.read(addr),
+            MemoryRegion::Wram => self.wram.read(addr),
+            MemoryRegion::Mram => self.wram.read(addr - 0x2000),
+            MemoryRegion::Io => self.read_cgb_register(addr).unwrap_or(self.data[addr as usize]),
+            MemoryRegion::Timers => self.timers.read_byte(addr),
+            MemoryRegion::Oam => self.oam.read().unwrap().read(addr),
+            MemoryRegion::Unusable => 0xFF,
+            MemoryRegion::InterruptFlag => self.interrupts.read_interrupt_flag(),
+            MemoryRegion::InterruptEnable => self.interrupts.read_interrupt_enable(),
+            // The joypad register is returned as last synthesized
+            _ => self.data[addr as usize],
+        }
+    }
+
+    fn poke(&mut self, addr: u16, val: u8) {
+        match MemoryRegion::from(addr) {
+            MemoryRegion::Mbc | MemoryRegion::ERam => self.cart.poke(addr, val),
+            MemoryRegion::Vram => self.vram.write(addr, val),
+            MemoryRegion::Wram => self.wram.write(addr, val),
+            MemoryRegion::Mram => self.wram.write(addr - 0x2000, val),
+            MemoryRegion::Io if self.write_cgb_register(addr, val) => {}
+            MemoryRegion::Timers => self.timers.poke(addr, val),
+            MemoryRegion::Oam => self.oam.get_mut().unwrap().write(addr, val),
+            MemoryRegion::Unusable => {}
+            MemoryRegion::InterruptFlag => self.interrupts.write_interrupt_flag(val),
+            MemoryRegion::InterruptEnable => self.interrupts.write_interrupt_enable(val),
+            // Raw register write: no DMA, no boot rom unmapping, LY and STAT take the value as is
+            _ => self.data[addr as usize] = val,
+        }
+    }
 }
 
 impl<T: Mbc> Default for Mmu<T> {
@@ -319,6 +694,9 @@ impl<T: Mbc> Default for Mmu<T> {
 
 #[cfg(test)]
 mod tests {
+    use crate::mmu::inspector::MemoryInspector;
+    use crate::mmu::cgb::HardwareMode;
+    use crate::mmu::compat_palettes::PaletteChoice;
     use crate::mmu::mbc::RomOnly;
 
     use super::{MemoryRegion, Mmu};
@@ -337,6 +715,235 @@ mod tests {
         assert_eq!(mmu.read_byte(0xC000), 0xAB);
     }
 
+    #[test]
+    fn mmu_pokes_rom_bank_and_rejects_missing_bank() {
+        let rom = vec![0x12, 0x34, 0x56, 0x78];
+        let mut mmu = Mmu::<RomOnly>::new(&rom).unwrap();
+
+        // Rom is read only through the bus, but the debugger can patch it directly
+        mmu.write_byte(0x0001, 0xFF);
+        assert_eq!(mmu.read_byte(0x0001), 0x34);
+        mmu.write_rom_bank(0, 0x0001, 0xFF).unwrap();
+        assert_eq!(mmu.read_byte(0x0001), 0xFF);
+
+        assert!(mmu.write_rom_bank(2, 0x0000, 0xFF).is_err());
+    }
+
+    #[test]
+    fn cgb_header_enables_vram_and_wram_banks() {
+        let mut rom = vec![0; 0x150];
+        rom[0x0143] = 0xC0;
+        let mut mmu = Mmu::<RomOnly>::new(&rom).unwrap();
+
+        mmu.write_byte(0xFF70, 2);
+        mmu.write_byte(0xD000, 0x22);
+        mmu.write_byte(0xFF4F, 1);
+        mmu.write_byte(0x8000, 0x11);
+        assert_eq!(mmu.read_byte(0xF000), 0x22); // echo RAM follows SVBK
+        assert_eq!(mmu.read_vram(0, 0x8000), 0xFF);
+        assert_eq!(mmu.read_vram(1, 0x8000), 0x11);
+
+        mmu.write_byte(0xFF70, 1);
+        assert_ne!(mmu.read_byte(0xD000), 0x22);
+        assert_eq!(mmu.read_byte(0xFF4F), 0xFF);
+
+        // A DMG cart keeps a single bank
+        let mut mmu = Mmu::<RomOnly>::new(&[]).unwrap();
+        mmu.write_byte(0xD000, 0x33);
+        mmu.write_byte(0xFF70, 2);
+        assert_eq!(mmu.read_byte(0xD000), 0x33);
+    }
+
+    #[test]
+    fn general_purpose_dma_copies_to_current_vram_bank() {
+        let mut rom = vec![0; 0x150];
+        rom[0x0143] = 0x80;
+        let mut mmu = Mmu::<RomOnly>::new(&rom).unwrap();
+        for offset in 0..0x20 {
+            mmu.write_byte(0xC000 + offset, offset as u8);
+        }
+
+        mmu.write_byte(0xFF4F, 1);
+        mmu.write_byte(0xFF51, 0xC0);
+        mmu.write_byte(0xFF52, 0x00);
+        mmu.write_byte(0xFF53, 0x10);
+        mmu.write_byte(0xFF54, 0x00);
+        mmu.write_byte(0xFF55, 0x01);
+        assert!(mmu.is_hdma_copying());
+        assert_eq!(mmu.read_byte(0xFF55), 0x01);
+
+        // 2 bytes per M-cycle in normal speed
+        for _ in 0..16 {
+            mmu.tick_hdma();
+        }
+        assert!(!mmu.is_hdma_copying());
+        assert_eq!(mmu.read_byte(0xFF55), 0xFF);
+        assert_eq!(mmu.read_vram(1, 0x9000), 0x00);
+        assert_eq!(mmu.read_vram(1, 0x901F), 0x1F);
+        assert_eq!(mmu.read_vram(0, 0x901F), 0xFF);
+    }
+
+    #[test]
+    fn dmg_cart_runs_in_cgb_compatibility_mode() {
+        let mut mmu = Mmu::<RomOnly>::new(&vec![0; 0x150]).unwrap();
+        mmu.set_compat_palette(PaletteChoice::Grayscale);
+        assert_eq!(mmu.mode(), HardwareMode::CgbCompat);
+        assert_eq!(mmu.bg_color(0, 0), 0x7FFF);
+        assert_eq!(mmu.read_byte(0xFF4F), 0xFF); // VBK stays locked
+        mmu.set_compat_palette(PaletteChoice::Off);
+        assert_eq!(mmu.mode(), HardwareMode::Dmg);
+
+        // The CGB boot ROM switches through KEY0, only while it is mapped
+        let mut rom = vec![0; 0x150];
+        rom[0x0143] = 0x80;
+        let mut mmu = Mmu::<RomOnly>::new(&rom).unwrap();
+        mmu.write_byte(0xFF4C, 0x04);
+        assert_eq!(mmu.mode(), HardwareMode::Cgb);
+        mmu.load_boot_rom(vec![0; 0x100]).unwrap();
+        mmu.write_byte(0xFF4C, 0x04);
+        assert_eq!(mmu.mode(), HardwareMode::CgbCompat);
+    }
+
+    #[test]
+    fn peek_does_not_trigger_oam_bug() {
+        let mut mmu = Mmu::<RomOnly>::new(&[]).unwrap();
+        for offset in 0..0xA0 {
+            mmu.write_byte(0xFE00 + offset, offset as u8);
+        }
+
+        // Pretend the PPU is scanning row 2 so a real read would corrupt the OAM
+        mmu.set_accessed_oam_row(0x10);
+        for offset in 0..0xA0 {
+            assert_eq!(mmu.peek(0xFE00 + offset), offset as u8);
+        }
+    }
+
+    #[test]
+    fn peek_ignores_boot_rom_overlay() {
+        let rom = vec![0x12, 0x34, 0x56, 0x78];
+        let mut mmu = Mmu::<RomOnly>::new(&rom).unwrap();
+        mmu.load_boot_rom(vec![0xAA; 0x0100]).unwrap();
+
+        assert_eq!(mmu.read_byte(0x0000), 0xAA);
+        assert_eq!(mmu.peek(0x0000), 0x12);
+    }
+
+    #[test]
+    fn cgb_boot_rom_leaves_the_header_visible() {
+        let rom: Vec<u8> = (0..0x1000).map(|i| (i >> 8) as u8 | 0x80).collect();
+        let mut mmu = Mmu::<RomOnly>::new(&rom).unwrap();
+        assert!(mmu.load_boot_rom(vec![0xAA; 0x200]).is_err());
+        mmu.load_boot_rom(vec![0xAA; 0x0900]).unwrap();
+
+        assert_eq!(mmu.read_byte(0x00FF), 0xAA);
+        assert_eq!(mmu.read_byte(0x0100), 0x81);
+        assert_eq!(mmu.read_byte(0x01FF), 0x81);
+        assert_eq!(mmu.read_byte(0x0200), 0xAA);
+        assert_eq!(mmu.read_byte(0x08FF), 0xAA);
+        assert_eq!(mmu.read_byte(0x0900), 0x89);
+
+        // Writing 0 to BANK does nothing, bit 0 unmaps it for good
+        mmu.write_byte(0xFF50, 0x00);
+        assert!(mmu.get_boot_enable());
+        mmu.write_byte(0xFF50, 0x11);
+        assert_eq!(mmu.read_byte(0x0000), 0x80);
+        assert_eq!(mmu.read_byte(0x0200), 0x82);
+        mmu.write_byte(0xFF50, 0x00);
+        assert!(!mmu.get_boot_enable());
+        assert_eq!(mmu.read_byte(0xFF50), 0xFF);
+    }
+
+    #[test]
+    fn strict_access_follows_the_ppu_mode() {
+        let mut mmu = Mmu::<RomOnly>::new(&[]).unwrap();
+        mmu.write_byte(0x8000, 0x11);
+        mmu.write_byte(0xFE00, 0x22);
+        mmu.write_byte(0xFF40, 0x80);
+        mmu.set_stat_byte_from_ppu(0x83);
+        assert_eq!(mmu.read_byte(0x8000), 0x11); // off by default
+
+        mmu.set_strict_access(true);
+        mmu.write_byte(0x8000, 0x33);
+        assert_eq!(mmu.read_byte(0x8000), 0xFF);
+        assert_eq!(mmu.read_byte(0xFE00), 0xFF);
+        assert_eq!(mmu.peek(0x8000), 0x11);
+
+        mmu.set_stat_byte_from_ppu(0x82);
+        assert_eq!(mmu.read_byte(0x8000), 0x11);
+        mmu.write_byte(0xFE00, 0x44);
+        assert_eq!(mmu.read_byte(0xFE00), 0xFF);
+
+        mmu.set_stat_byte_from_ppu(0x80);
+        assert_eq!(mmu.read_byte(0xFE00), 0x22);
+
+        // With the LCD off the STAT mode doesn't matter
+        mmu.set_stat_byte_from_ppu(0x83);
+        mmu.write_byte(0xFF40, 0x00);
+        assert_eq!(mmu.read_byte(0x8000), 0x11);
+    }
+
+    #[test]
+    fn oam_dma_leaves_only_hram_and_registers() {
+        let mut mmu = Mmu::<RomOnly>::new(&[]).unwrap();
+        mmu.write_byte(0xFF40, 0x00);
+        mmu.set_strict_access(true);
+        for offset in 0..0xA0 {
+            mmu.write_byte(0xC000 + offset, offset as u8);
+        }
+        mmu.write_byte(0xFF80, 0x55);
+
+        mmu.write_byte(0xFF46, 0xC0);
+        mmu.tick_dma();
+        assert_eq!(mmu.read_byte(0xC000), 0xFF);
+        mmu.write_byte(0xC000, 0x99);
+        assert_eq!(mmu.read_byte(0xFF80), 0x55);
+        assert_eq!(mmu.read_byte(0xFF46), 0xC0);
+
+        while mmu.dma_index != 0xFF {
+            mmu.tick_dma();
+        }
+        assert_eq!(mmu.read_byte(0xC000), 0x00);
+        assert_eq!(mmu.read_byte(0xFE9F), 0x9F);
+    }
+
+    #[test]
+    fn oam_dma_holds_the_bus_for_160_m_cycles() {
+        let mut mmu = Mmu::<RomOnly>::new(&[]).unwrap();
+        mmu.write_byte(0xFF40, 0x00);
+        mmu.write_byte(0xC000, 0x12);
+        mmu.set_strict_access(true);
+
+        // Locked from the write on, the first byte moves on the next M-cycle
+        mmu.write_byte(0xFF46, 0xC0);
+        assert_eq!(mmu.read_byte(0xC000), 0xFF);
+
+        for _ in 0..159 {
+            mmu.tick_dma();
+        }
+        assert_eq!(mmu.read_byte(0xC000), 0xFF);
+
+        mmu.tick_dma();
+        assert_eq!(mmu.read_byte(0xC000), 0x12);
+    }
+
+    #[test]
+    fn poke_has_no_hardware_side_effects() {
+        let mut mmu = Mmu::<RomOnly>::new(&[]).unwrap();
+        mmu.load_boot_rom(vec![0xAA; 0x0100]).unwrap();
+
+        // A CPU write would unmap the boot rom, start a DMA or reset DIV
+        mmu.poke(0xFF50, 0x01);
+        assert!(mmu.get_boot_enable());
+        mmu.poke(0xFF46, 0xC0);
+        assert_eq!(mmu.dma_index, 0xFF);
+        mmu.poke(0xFF04, 0x42);
+        assert_eq!(mmu.peek(0xFF04), 0x42);
+
+        // Rom is patched in place
+        mmu.poke(0x0150, 0x99);
+        assert_eq!(mmu.peek(0x0150), 0x99);
+    }
+
     #[test]
     fn memory_region_from_addr() {
         assert_eq!(MemoryRegion::from(0x0000), MemoryRegion::Mbc);
@@ -382,4 +989,25 @@ mod tests {
         assert_eq!(mmu.read_byte(base), 0xFF);
         assert_eq!(mmu.read_byte(base + 0x1F), 0xFF);
     }
+
+    #[test]
+    fn sgb_mode_decodes_p1_packets() {
+        let mut mmu = Mmu::<RomOnly>::with_mode(&[0; 0x150], HardwareMode::Sgb).unwrap();
+        assert_eq!(mmu.mode(), HardwareMode::Sgb);
+
+        // PAL01 turning color 1 of palette 0 red, sent bit by bit through P1
+        let mut packet = [0u8; 16];
+        packet[0] = 1;
+        packet[3..5].copy_from_slice(&0x001Fu16.to_le_bytes());
+        mmu.write_byte(0xFF00, 0x00);
+        mmu.write_byte(0xFF00, 0x30);
+        for bit in (0..128).map(|i| (packet[i / 8] >> (i % 8)) & 1) {
+            mmu.write_byte(0xFF00, if bit == 1 { 0x10 } else { 0x20 });
+            mmu.write_byte(0xFF00, 0x30);
+        }
+        mmu.write_byte(0xFF00, 0x20);
+        mmu.write_byte(0xFF00, 0x30);
+
+        assert_eq!(mmu.sgb_color(0, 0, 1), Some(0x001F));
+    }
 }
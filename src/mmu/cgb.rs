@@ -0,0 +1,374 @@
+// Header byte 0x143: 0x80 works on both, 0xC0 is CGB only. Anything else runs as a DMG
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum HardwareMode {
+    #[default]
+    Dmg,
+    Cgb,
+    CgbCompat, // a CGB running a DMG cart, DMG features with colors from the palette RAM
+    Sgb, // a DMG in a Super Game Boy, colors and border come from the SGB packets
+}
+
+impl HardwareMode {
+    pub fn from_header(rom_image: &[u8]) -> Self {
+        match rom_image.get(0x0143) {
+            Some(flag) if flag & 0x80 != 0 => HardwareMode::Cgb,
+            _ => HardwareMode::Dmg,
+        }
+    }
+}
+
+const VRAM_BANK_SIZE: usize = 0x2000;
+const WRAM_BANK_SIZE: usize = 0x1000;
+
+// 0x8000-0x9FFF, VBK (0xFF4F) selects the bank seen by the CPU
+pub struct Vram {
+    banks: Vec<[u8; VRAM_BANK_SIZE]>,
+    bank: usize,
+}
+
+impl Vram {
+    pub fn new(mode: HardwareMode) -> Self {
+        let count = if mode == HardwareMode::Cgb { 2 } else { 1 };
+        Vram { banks: vec![[0xFF; VRAM_BANK_SIZE]; count], bank: 0 }
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        self.read_bank(self.bank, addr)
+    }
+
+    pub fn write(&mut self, addr: u16, val: u8) {
+        self.banks[self.bank][(addr - 0x8000) as usize] = val;
+    }
+
+    // The PPU picks its bank itself, whatever VBK says
+    pub fn read_bank(&self, bank: usize, addr: u16) -> u8 {
+        self.banks.get(bank).map_or(0xFF, |bytes| bytes[(addr - 0x8000) as usize])
+    }
+
+    pub fn read_vbk(&self) -> u8 {
+        0xFE | self.bank as u8
+    }
+
+    pub fn write_vbk(&mut self, val: u8) {
+        self.bank = (val as usize & 1).min(self.banks.len() - 1);
+    }
+}
+
+// 0xC000-0xCFFF is always bank 0, SVBK (0xFF70) maps bank 1-7 at 0xD000-0xDFFF
+pub struct Wram {
+    banks: Vec<[u8; WRAM_BANK_SIZE]>,
+    bank: usize,
+}
+
+impl Wram {
+    pub fn new(mode: HardwareMode) -> Self {
+        let count = if mode == HardwareMode::Cgb { 8 } else { 2 };
+        Wram { banks: vec![[0xFF; WRAM_BANK_SIZE]; count], bank: 1 }
+    }
+
+    fn location(&self, addr: u16) -> (usize, usize) {
+        let offset = (addr as usize - 0xC000) % (2 * WRAM_BANK_SIZE);
+        match offset {
+            0..WRAM_BANK_SIZE => (0, offset),
+            _ => (self.bank, offset - WRAM_BANK_SIZE),
+        }
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        let (bank, offset) = self.location(addr);
+        self.banks[bank][offset]
+    }
+
+    pub fn write(&mut self, addr: u16, val: u8) {
+        let (bank, offset) = self.location(addr);
+        self.banks[bank][offset] = val;
+    }
+
+    pub fn read_svbk(&self) -> u8 {
+        0xF8 | self.bank as u8
+    }
+
+    pub fn write_svbk(&mut self, val: u8) {
+        self.bank = (val as usize & 0b111).max(1).min(self.banks.len() - 1);
+    }
+}
+
+// KEY1 (0xFF4D): bit 0 arms the switch, the next STOP toggles the speed shown in bit 7
+#[derive(Default)]
+pub struct SpeedSwitch {
+    double_speed: bool,
+    armed: bool,
+}
+
+impl SpeedSwitch {
+    pub fn read(&self) -> u8 {
+        ((self.double_speed as u8) << 7) | 0x7E | self.armed as u8
+    }
+
+    pub fn write(&mut self, val: u8) {
+        self.armed = val & 1 != 0;
+    }
+
+    pub fn is_double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    pub fn switch(&mut self) -> bool {
+        if !self.armed {
+            return false;
+        }
+        self.armed = false;
+        self.double_speed = !self.double_speed;
+        true
+    }
+}
+
+/*
+    BCPS/BCPD (0xFF68-0xFF69) and OCPS/OCPD (0xFF6A-0xFF6B): 8 palettes of 4 colors, each color is
+    a little endian RGB555 word. Bit 7 of the spec register increments the index after each data write.
+*/
+pub struct PaletteRam {
+    data: [u8; 64],
+    index: u8,
+    auto_increment: bool,
+}
+
+impl Default for PaletteRam {
+    fn default() -> Self {
+        PaletteRam { data: [0xFF; 64], index: 0, auto_increment: false }
+    }
+}
+
+impl PaletteRam {
+    pub fn read_spec(&self) -> u8 {
+        ((self.auto_increment as u8) << 7) | 0x40 | self.index
+    }
+
+    pub fn write_spec(&mut self, val: u8) {
+        self.index = val & 0x3F;
+        self.auto_increment = val & 0x80 != 0;
+    }
+
+    pub fn read_data(&self) -> u8 {
+        self.data[self.index as usize]
+    }
+
+    pub fn write_data(&mut self, val: u8) {
+        self.data[self.index as usize] = val;
+        if self.auto_increment {
+            self.index = (self.index + 1) & 0x3F;
+        }
+    }
+
+    pub fn color(&self, palette: u8, color_index: u8) -> u16 {
+        let offset = (palette as usize & 0b111) * 8 + color_index as usize * 2;
+        u16::from_le_bytes([self.data[offset], self.data[offset + 1]])
+    }
+
+    pub fn load(&mut self, palette: u8, colors: [u16; 4]) {
+        for (color_index, color) in colors.iter().enumerate() {
+            let offset = (palette as usize & 0b111) * 8 + color_index * 2;
+            self.data[offset..offset + 2].copy_from_slice(&color.to_le_bytes());
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8; 64] {
+        &self.data
+    }
+}
+
+/*
+    HDMA1-HDMA5 (0xFF51-0xFF55): copies 16 byte blocks from ROM/RAM to the current VRAM bank.
+    A general purpose DMA copies every block at once, an HBlank DMA copies one block per HBlank.
+    HDMA5 reads back the remaining blocks minus one, bit 7 set once the transfer is over.
+*/
+pub struct Hdma {
+    source: u16,
+    destination: u16,
+    blocks_left: u8,
+    hblank_mode: bool,
+    active: bool,
+    block_offset: Option<u16>, // Some while a block is being copied and the CPU is stalled
+}
+
+impl Default for Hdma {
+    fn default() -> Self {
+        Hdma {
+            source: 0,
+            destination: 0x8000,
+            blocks_left: 0x7F,
+            hblank_mode: false,
+            active: false,
+            block_offset: None,
+        }
+    }
+}
+
+impl Hdma {
+    pub fn write_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0xFF51 => self.source = (self.source & 0x00FF) | ((val as u16) << 8),
+            0xFF52 => self.source = (self.source & 0xFF00) | (val as u16 & 0xF0),
+            0xFF53 => self.destination = 0x8000 | (self.destination & 0x00FF) | ((val as u16 & 0x1F) << 8),
+            0xFF54 => self.destination = (self.destination & 0xFF00) | (val as u16 & 0xF0),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn read_control(&self) -> u8 {
+        ((!self.active as u8) << 7) | self.blocks_left
+    }
+
+    // `in_hblank` lets an HBlank DMA started during mode 0 (or with the LCD off) copy its first block right away
+    pub fn write_control(&mut self, val: u8, in_hblank: bool) {
+        if self.active && self.hblank_mode && val & 0x80 == 0 {
+            self.active = false;
+            return;
+        }
+
+        self.blocks_left = val & 0x7F;
+        self.hblank_mode = val & 0x80 != 0;
+        self.active = true;
+        if !self.hblank_mode || in_hblank {
+            self.block_offset = Some(0);
+        }
+    }
+
+    pub fn start_hblank_block(&mut self) {
+        if self.active && self.hblank_mode && self.block_offset.is_none() {
+            self.block_offset = Some(0);
+        }
+    }
+
+    pub fn is_copying(&self) -> bool {
+        self.block_offset.is_some()
+    }
+
+    // Returns the (source, destination) of the next byte to copy
+    pub fn next_transfer(&mut self) -> Option<(u16, u16)> {
+        let offset = self.block_offset?;
+        let transfer = (self.source.wrapping_add(offset), 0x8000 | ((self.destination + offset) & 0x1FFF));
+
+        if offset < 15 {
+            self.block_offset = Some(offset + 1);
+            return Some(transfer);
+        }
+
+        self.block_offset = None;
+        self.source = self.source.wrapping_add(16);
+        self.destination = 0x8000 | ((self.destination + 16) & 0x1FF0);
+        if self.blocks_left == 0 {
+            self.blocks_left = 0x7F;
+            self.active = false;
+        } else {
+            self.blocks_left -= 1;
+            if !self.hblank_mode {
+                self.block_offset = Some(0);
+            }
+        }
+        Some(transfer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette_data_auto_increments() {
+        let mut palettes = PaletteRam::default();
+        palettes.write_spec(0x80 | 0x3E);
+        palettes.write_data(0x1F);
+        palettes.write_data(0x00);
+        assert_eq!(palettes.read_spec(), 0xC0);
+        assert_eq!(palettes.color(7, 3), 0x001F);
+
+        palettes.write_spec(0x02);
+        palettes.write_data(0xE0);
+        palettes.write_data(0x03);
+        assert_eq!(palettes.read_spec(), 0x42);
+        assert_eq!(palettes.read_data(), 0x03);
+        assert_eq!(palettes.color(0, 1), 0xFF03);
+    }
+
+    #[test]
+    fn wram_bank_0_maps_to_bank_1() {
+        let mut wram = Wram::new(HardwareMode::Cgb);
+        wram.write(0xD000, 0x11);
+        wram.write_svbk(0);
+        assert_eq!(wram.read_svbk(), 0xF9);
+        assert_eq!(wram.read(0xD000), 0x11);
+
+        wram.write_svbk(7);
+        wram.write(0xD000, 0x77);
+        wram.write(0xC000, 0x42);
+        assert_eq!(wram.read(0xD000), 0x77);
+        wram.write_svbk(1);
+        assert_eq!(wram.read(0xD000), 0x11);
+        assert_eq!(wram.read(0xC000), 0x42);
+    }
+
+    #[test]
+    fn dmg_ignores_bank_registers() {
+        let mut vram = Vram::new(HardwareMode::Dmg);
+        let mut wram = Wram::new(HardwareMode::Dmg);
+        vram.write(0x8000, 0x12);
+        vram.write_vbk(1);
+        wram.write_svbk(5);
+        assert_eq!(vram.read(0x8000), 0x12);
+        assert_eq!(wram.read_svbk(), 0xF9);
+    }
+
+    #[test]
+    fn speed_switch_needs_to_be_armed() {
+        let mut key1 = SpeedSwitch::default();
+        assert!(!key1.switch());
+
+        key1.write(1);
+        assert_eq!(key1.read(), 0x7F);
+        assert!(key1.switch());
+        assert_eq!(key1.read(), 0xFE);
+        assert!(key1.is_double_speed());
+    }
+
+    #[test]
+    fn hblank_dma_copies_one_block_per_hblank() {
+        let mut hdma = Hdma::default();
+        hdma.write_register(0xFF51, 0xC1);
+        hdma.write_register(0xFF52, 0x2F);
+        hdma.write_register(0xFF53, 0xFF);
+        hdma.write_register(0xFF54, 0x00);
+        hdma.write_control(0x81, false);
+        assert_eq!(hdma.read_control(), 0x01);
+        assert!(hdma.next_transfer().is_none());
+
+        hdma.start_hblank_block();
+        assert_eq!(hdma.next_transfer(), Some((0xC120, 0x9F00)));
+        for _ in 1..16 {
+            assert!(hdma.next_transfer().is_some());
+        }
+        assert!(!hdma.is_copying());
+        assert_eq!(hdma.read_control(), 0x00);
+
+        // Clearing bit 7 cancels the HBlank DMA and keeps the remaining length
+        hdma.write_control(0x00, false);
+        assert_eq!(hdma.read_control(), 0x80);
+        hdma.start_hblank_block();
+        assert!(!hdma.is_copying());
+    }
+
+    #[test]
+    fn general_purpose_dma_copies_every_block() {
+        let mut hdma = Hdma::default();
+        assert_eq!(hdma.read_control(), 0xFF);
+        hdma.write_register(0xFF51, 0xC0);
+        hdma.write_control(0x01, false);
+
+        let mut copied = 0;
+        while hdma.next_transfer().is_some() {
+            copied += 1;
+        }
+        assert_eq!(copied, 32);
+        assert_eq!(hdma.read_control(), 0xFF);
+    }
+}
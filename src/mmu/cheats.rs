@@ -0,0 +1,131 @@
+/*
+    Game Genie codes patch what the CPU reads from the cartridge ROM, GameShark codes are RAM writes
+    done once per frame (the real device writes them on VBlank).
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheatCode {
+    GameGenie { address: u16, value: u8, compare: Option<u8> },
+    GameShark { bank: u8, address: u16, value: u8 },
+}
+
+fn hex_digits(code: &str) -> Result<Vec<u8>, String> {
+    code.chars()
+        .filter(|c| *c != '-')
+        .map(|c| c.to_digit(16).map(|d| d as u8).ok_or(format!("Invalid hex digit '{c}' in {code}")))
+        .collect()
+}
+
+impl CheatCode {
+    // `ABC-DEF` or `ABC-DEF-GHI` for Game Genie, `TTVVAAAA` for GameShark
+    pub fn parse(code: &str) -> Result<Self, String> {
+        let code = code.trim().to_uppercase();
+        let digits = hex_digits(&code)?;
+
+        match (code.contains('-'), digits.len()) {
+            (true, 6) | (true, 9) => Self::game_genie(&digits),
+            (false, 8) => Ok(Self::game_shark(&digits)),
+            _ => Err(format!("Unknown cheat format: {code}")),
+        }
+    }
+
+    fn game_genie(digits: &[u8]) -> Result<Self, String> {
+        let value = (digits[0] << 4) | digits[1];
+        let address = (((digits[5] as u16) << 12)
+            | ((digits[2] as u16) << 8)
+            | ((digits[3] as u16) << 4)
+            | digits[4] as u16)
+            ^ 0xF000;
+        if address >= 0x8000 {
+            return Err(format!("Game Genie address 0x{address:04X} is outside the ROM"));
+        }
+
+        // digit H is not part of the compare byte
+        let compare = (digits.len() == 9).then(|| ((digits[6] << 4) | digits[8]).rotate_right(2) ^ 0xBA);
+        Ok(CheatCode::GameGenie { address, value, compare })
+    }
+
+    fn game_shark(digits: &[u8]) -> Self {
+        let byte = |i: usize| (digits[i] << 4) | digits[i + 1];
+
+        CheatCode::GameShark {
+            bank: byte(0),
+            value: byte(2),
+            address: u16::from_le_bytes([byte(4), byte(6)]),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            CheatCode::GameGenie { address, value, compare: Some(compare) } => {
+                format!("ROM 0x{address:04X} = 0x{value:02X} if 0x{compare:02X}")
+            }
+            CheatCode::GameGenie { address, value, compare: None } => format!("ROM 0x{address:04X} = 0x{value:02X}"),
+            CheatCode::GameShark { bank, address, value } => {
+                format!("RAM 0x{address:04X} = 0x{value:02X} (bank {bank:02X})")
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Cheats {
+    codes: Vec<CheatCode>,
+}
+
+impl Cheats {
+    pub fn set(&mut self, codes: Vec<CheatCode>) {
+        self.codes = codes;
+    }
+
+    pub fn patch_rom_read(&self, addr: u16, read: u8) -> u8 {
+        self.codes
+            .iter()
+            .find_map(|code| match *code {
+                CheatCode::GameGenie { address, value, compare } if address == addr && compare.is_none_or(|c| c == read) => {
+                    Some(value)
+                }
+                _ => None,
+            })
+            .unwrap_or(read)
+    }
+
+    pub fn ram_writes(&self) -> impl Iterator<Item = (u8, u16, u8)> + '_ {
+        self.codes.iter().filter_map(|code| match *code {
+            CheatCode::GameShark { bank, address, value } => Some((bank, address, value)),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cheat_codes() {
+        assert_eq!(
+            CheatCode::parse("3eb-2fb-8aa"),
+            Ok(CheatCode::GameGenie { address: 0x4B2F, value: 0x3E, compare: Some(0x18) })
+        );
+        assert_eq!(
+            CheatCode::parse("00A-17B"),
+            Ok(CheatCode::GameGenie { address: 0x4A17, value: 0x00, compare: None })
+        );
+        assert_eq!(
+            CheatCode::parse("010F2DD1"),
+            Ok(CheatCode::GameShark { bank: 0x01, address: 0xD12D, value: 0x0F })
+        );
+        assert!(CheatCode::parse("010F2DD").is_err());
+        assert!(CheatCode::parse("3EB-2F0-8AA").is_err()); // would patch 0xFB2F
+    }
+
+    #[test]
+    fn game_genie_checks_the_compare_byte() {
+        let mut cheats = Cheats::default();
+        cheats.set(vec![CheatCode::GameGenie { address: 0x4B2F, value: 0x3E, compare: Some(0x18) }]);
+
+        assert_eq!(cheats.patch_rom_read(0x4B2F, 0x18), 0x3E);
+        assert_eq!(cheats.patch_rom_read(0x4B2F, 0x19), 0x19); // another bank is mapped
+        assert_eq!(cheats.patch_rom_read(0x4B30, 0x18), 0x18);
+    }
+}
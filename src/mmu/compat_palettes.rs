@@ -0,0 +1,287 @@
+/*
+    A CGB running a DMG cart colorizes it: the boot ROM picks 3 palettes (BG, OBJ0, OBJ1) and the PPU
+    then maps the BGP/OBP0/OBP1 shades to these colors. The palette comes from a table indexed by the
+    title checksum, or from a button combo held during the boot logo.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PaletteChoice {
+    #[default]
+    Off, // plain DMG shades
+    Auto, // title checksum, like the boot ROM
+    Brown,
+    Red,
+    DarkBrown,
+    Blue,
+    DarkBlue,
+    Grayscale,
+    Pastel,
+    Orange,
+    Yellow,
+    Green,
+    DarkGreen,
+    Inverted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompatPalette {
+    pub bg: [u16; 4],
+    pub obj0: [u16; 4],
+    pub obj1: [u16; 4],
+}
+
+/*
+    Tables of the CGB boot ROM. The title checksum is looked up in TITLE_CHECKSUMS, the last 14 are
+    shared by several games and are told apart by the 4th letter of the title: the letter for the
+    checksum at FIRST_SHARED_CHECKSUM + n is FOURTH_LETTERS[n], FOURTH_LETTERS[n + 14] or
+    FOURTH_LETTERS[n + 28], and the entry found is FIRST_SHARED_CHECKSUM + the letter index.
+*/
+const TITLE_CHECKSUMS: [u8; 79] = [
+    0x00, 0x88, 0x16, 0x36, 0xD1, 0xDB, 0xF2, 0x3C, 0x8C, 0x92, 0x3D, 0x5C, 0x58,
+    0xC9, 0x3E, 0x70, 0x1D, 0x59, 0x69, 0x19, 0x35, 0xA8, 0x14, 0xAA, 0x75, 0x95,
+    0x99, 0x34, 0x6F, 0x15, 0xFF, 0x97, 0x4B, 0x90, 0x17, 0x10, 0x39, 0xF7, 0xF6,
+    0xA2, 0x49, 0x4E, 0x43, 0x68, 0xE0, 0x8B, 0xF0, 0xCE, 0x0C, 0x29, 0xE8, 0xB7,
+    0x86, 0x9A, 0x52, 0x01, 0x9D, 0x71, 0x9C, 0xBD, 0x5D, 0x6D, 0x67, 0x3F, 0x6B,
+    0xB3, 0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D,
+    0xF4,
+];
+
+const FIRST_SHARED_CHECKSUM: usize = 65;
+const FOURTH_LETTERS: [u8; 29] = *b"BEFAARBEKEK R-URAR INAILICE R";
+
+/*
+    Palette id of each entry. Bits 0-4 pick a combination, bits 5-7 which of its palettes the objects
+    use: OBJ0 takes the first one with bit 5 and the BG one otherwise, OBJ1 takes the second one with
+    bit 7, the first one with bit 6 and the BG one otherwise.
+*/
+const PALETTE_IDS: [u8; 94] = [
+    0x7C, 0x08, 0x12, 0xA3, 0xA2, 0x07, 0x87, 0x4B, 0x20, 0x12, 0x65, 0xA8, 0x16,
+    0xA9, 0x86, 0xB1, 0x68, 0xA0, 0x87, 0x66, 0x12, 0xA1, 0x30, 0x3C, 0x12, 0x85,
+    0x12, 0x64, 0x1B, 0x07, 0x06, 0x6F, 0x6E, 0x6E, 0xAE, 0xAF, 0x6F, 0xB2, 0xAF,
+    0xB2, 0xA8, 0xAB, 0x6F, 0xAF, 0x86, 0xAE, 0xA2, 0xA2, 0x12, 0xAF, 0x13, 0x12,
+    0xA1, 0x6E, 0xAF, 0xAF, 0xAD, 0x06, 0x4C, 0x6E, 0xAF, 0xAF, 0x12, 0x7C, 0xAC,
+    0xA8, 0x6A, 0x6E, 0x13, 0xA0, 0x2D, 0xA8, 0x2B, 0xAC, 0x64, 0xAC, 0x6D, 0x87,
+    0xBC, 0x60, 0xB4, 0x13, 0x72, 0x7C, 0xB5, 0xAE, 0xAE, 0x7C, 0x7C, 0x65, 0xA2,
+    0x6C, 0x64, 0x85,
+];
+
+// First, second and BG palettes of a combination, as byte offsets into PALETTE_DATA
+const COMBINATIONS: [[u8; 3]; 29] = [
+    [0x80, 0xB0, 0x40], [0x88, 0x20, 0x68], [0xDE, 0x00, 0x70], [0xDE, 0x20, 0x78], [0x20, 0x20, 0x38],
+    [0x20, 0xB0, 0x90], [0x20, 0xB0, 0xA0], [0xE0, 0xB0, 0xC0], [0x98, 0xB6, 0x48], [0x80, 0xE0, 0x50],
+    [0x1E, 0x1E, 0x58], [0x20, 0xB8, 0xE0], [0x88, 0xB0, 0x10], [0x20, 0x00, 0x10], [0x20, 0xE0, 0x18],
+    [0xE0, 0x18, 0x00], [0x18, 0xE0, 0x20], [0xA8, 0xE0, 0x20], [0x18, 0xE0, 0x00], [0x20, 0x18, 0xD8],
+    [0xC8, 0x18, 0xE0], [0x00, 0xE0, 0x40], [0x28, 0x28, 0x28], [0x18, 0xE0, 0x60], [0x20, 0x18, 0xE0],
+    [0x00, 0x00, 0x08], [0xE0, 0x18, 0x30], [0xD0, 0xD0, 0xD0], [0x20, 0xE0, 0xE8],
+];
+
+const PALETTE_DATA: [u16; 126] = [
+    0x7FFF, 0x32BF, 0x00D0, 0x0000, 0x639F, 0x4279, 0x15B0, 0x04CB, 0x7FFF, 0x6E31,
+    0x454A, 0x0000, 0x7FFF, 0x1BEF, 0x0200, 0x0000, 0x7FFF, 0x421F, 0x1CF2, 0x0000,
+    0x7FFF, 0x5294, 0x294A, 0x0000, 0x7FFF, 0x03FF, 0x012F, 0x0000, 0x7FFF, 0x03EF,
+    0x01D6, 0x0000, 0x7FFF, 0x42B5, 0x3DC8, 0x0000, 0x7E74, 0x03FF, 0x0180, 0x0000,
+    0x67FF, 0x77AC, 0x1A13, 0x2D6B, 0x7ED6, 0x4BFF, 0x2175, 0x0000, 0x53FF, 0x4A5F,
+    0x7E52, 0x0000, 0x4FFF, 0x7ED2, 0x3A4C, 0x1CE0, 0x03ED, 0x7FFF, 0x255F, 0x0000,
+    0x036A, 0x021F, 0x03FF, 0x7FFF, 0x7FFF, 0x01DF, 0x0112, 0x0000, 0x231F, 0x035F,
+    0x00F2, 0x0009, 0x7FFF, 0x03EA, 0x011F, 0x0000, 0x299F, 0x001A, 0x000C, 0x0000,
+    0x7FFF, 0x027F, 0x001F, 0x0000, 0x7FFF, 0x03E0, 0x0206, 0x0120, 0x7FFF, 0x7EEB,
+    0x001F, 0x7C00, 0x7FFF, 0x3FFF, 0x7E00, 0x001F, 0x7FFF, 0x03FF, 0x001F, 0x0000,
+    0x03FF, 0x001F, 0x000C, 0x0000, 0x7FFF, 0x033F, 0x0193, 0x0000, 0x0000, 0x4200,
+    0x037F, 0x7FFF, 0x7FFF, 0x7E8C, 0x7C00, 0x0000, 0x7FFF, 0x1BEF, 0x6180, 0x0000,
+    0x7FFF, 0x7C00, 0x03E0, 0x7C1F, 0x001F, 0x03FF,
+];
+
+impl CompatPalette {
+    fn from_id(id: u8) -> Self {
+        let [first, second, bg] = COMBINATIONS[(id & 0x1F) as usize].map(|offset| {
+            let start = offset as usize / 2;
+            [PALETTE_DATA[start], PALETTE_DATA[start + 1], PALETTE_DATA[start + 2], PALETTE_DATA[start + 3]]
+        });
+
+        let obj0 = if id & 0x20 != 0 { first } else { bg };
+        let obj1 = if id & 0x80 != 0 {
+            second
+        } else if id & 0x40 != 0 {
+            first
+        } else {
+            bg
+        };
+        CompatPalette { bg, obj0, obj1 }
+    }
+}
+
+fn title_checksum(title: &[u8]) -> u8 {
+    title.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+impl PaletteChoice {
+    pub const ALL: [PaletteChoice; 14] = [
+        PaletteChoice::Off,
+        PaletteChoice::Auto,
+        PaletteChoice::Brown,
+        PaletteChoice::Red,
+        PaletteChoice::DarkBrown,
+        PaletteChoice::Blue,
+        PaletteChoice::DarkBlue,
+        PaletteChoice::Grayscale,
+        PaletteChoice::Pastel,
+        PaletteChoice::Orange,
+        PaletteChoice::Yellow,
+        PaletteChoice::Green,
+        PaletteChoice::DarkGreen,
+        PaletteChoice::Inverted,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PaletteChoice::Off => "Off",
+            PaletteChoice::Auto => "Auto",
+            PaletteChoice::Brown => "Brown",
+            PaletteChoice::Red => "Red",
+            PaletteChoice::DarkBrown => "Dark brown",
+            PaletteChoice::Blue => "Blue",
+            PaletteChoice::DarkBlue => "Dark blue",
+            PaletteChoice::Grayscale => "Grayscale",
+            PaletteChoice::Pastel => "Pastel",
+            PaletteChoice::Orange => "Orange",
+            PaletteChoice::Yellow => "Yellow",
+            PaletteChoice::Green => "Green",
+            PaletteChoice::DarkGreen => "Dark green",
+            PaletteChoice::Inverted => "Inverted",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|choice| choice.name().eq_ignore_ascii_case(name))
+    }
+
+    // The combo held on the boot logo to get the palette
+    pub fn button_combo(&self) -> Option<&'static str> {
+        match self {
+            PaletteChoice::Off | PaletteChoice::Auto => None,
+            PaletteChoice::Brown => Some("Up"),
+            PaletteChoice::Red => Some("Up + A"),
+            PaletteChoice::DarkBrown => Some("Up + B"),
+            PaletteChoice::Blue => Some("Left"),
+            PaletteChoice::DarkBlue => Some("Left + A"),
+            PaletteChoice::Grayscale => Some("Left + B"),
+            PaletteChoice::Pastel => Some("Down"),
+            PaletteChoice::Orange => Some("Down + A"),
+            PaletteChoice::Yellow => Some("Down + B"),
+            PaletteChoice::Green => Some("Right"),
+            PaletteChoice::DarkGreen => Some("Right + A"),
+            PaletteChoice::Inverted => Some("Right + B"),
+        }
+    }
+
+    // `header` is the start of the ROM, None means the DMG shades are kept
+    pub fn resolve(&self, header: &[u8]) -> Option<CompatPalette> {
+        // The palette ids the boot ROM gives to each button combo
+        let id = match self {
+            PaletteChoice::Off => return None,
+            PaletteChoice::Auto => palette_id_from_title(header),
+            PaletteChoice::Brown => 0x12,
+            PaletteChoice::Red => 0xB0,
+            PaletteChoice::DarkBrown => 0x79,
+            PaletteChoice::Blue => 0xB8,
+            PaletteChoice::DarkBlue => 0xAD,
+            PaletteChoice::Grayscale => 0x16,
+            PaletteChoice::Pastel => 0x17,
+            PaletteChoice::Orange => 0x07,
+            PaletteChoice::Yellow => 0xBA,
+            PaletteChoice::Green => 0x05,
+            PaletteChoice::DarkGreen => 0x7C,
+            PaletteChoice::Inverted => 0x13,
+        };
+        Some(CompatPalette::from_id(id))
+    }
+}
+
+// Only Nintendo titles are looked up, everything else gets the first entry, the Right + A palette
+fn palette_id_from_title(header: &[u8]) -> u8 {
+    let (Some(title), Some(&old_licensee), Some(new_licensee)) =
+        (header.get(0x134..0x144), header.get(0x14B), header.get(0x144..0x146))
+    else {
+        return PALETTE_IDS[0];
+    };
+    if old_licensee != 0x01 && !(old_licensee == 0x33 && new_licensee == b"01") {
+        return PALETTE_IDS[0];
+    }
+
+    let checksum = title_checksum(title);
+    let Some(index) = TITLE_CHECKSUMS.iter().position(|entry| *entry == checksum) else {
+        return PALETTE_IDS[0];
+    };
+    if index < FIRST_SHARED_CHECKSUM {
+        return PALETTE_IDS[index];
+    }
+
+    (index - FIRST_SHARED_CHECKSUM..FOURTH_LETTERS.len())
+        .step_by(TITLE_CHECKSUMS.len() - FIRST_SHARED_CHECKSUM)
+        .find(|letter| FOURTH_LETTERS[*letter] == title[3])
+        .map_or(PALETTE_IDS[0], |letter| PALETTE_IDS[FIRST_SHARED_CHECKSUM + letter])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(title: &str, old_licensee: u8) -> Vec<u8> {
+        let mut rom = vec![0; 0x150];
+        rom[0x134..0x134 + title.len()].copy_from_slice(title.as_bytes());
+        rom[0x14B] = old_licensee;
+        rom
+    }
+
+    #[test]
+    fn auto_uses_title_checksum_for_nintendo_games() {
+        let red = [0x7FFF, 0x421F, 0x1CF2, 0x0000];
+        let green = [0x7FFF, 0x1BEF, 0x0200, 0x0000];
+        let pokemon_red = CompatPalette { bg: red, obj0: green, obj1: red };
+        assert_eq!(PaletteChoice::Auto.resolve(&header("POKEMON RED", 0x01)), Some(pokemon_red));
+
+        // Same title from another licensee, and an unknown title, get the default palette
+        let default = PaletteChoice::DarkGreen.resolve(&[]);
+        assert_eq!(PaletteChoice::Auto.resolve(&header("POKEMON RED", 0x08)), default);
+        assert_eq!(PaletteChoice::Auto.resolve(&header("HELLO WORLD", 0x01)), default);
+        assert_eq!(PaletteChoice::Off.resolve(&header("POKEMON RED", 0x01)), None);
+    }
+
+    #[test]
+    fn auto_knows_more_than_pokemon() {
+        let orange = [0x7FFF, 0x03FF, 0x001F, 0x0000];
+        let tetris = PaletteChoice::Auto.resolve(&header("TETRIS", 0x01)).unwrap();
+        assert_eq!(tetris, CompatPalette { bg: orange, obj0: orange, obj1: orange });
+        assert_eq!(Some(tetris), PaletteChoice::Orange.resolve(&[]));
+
+        // New licensee code "01" is Nintendo too
+        let mut zelda = header("ZELDA", 0x33);
+        zelda[0x144..0x146].copy_from_slice(b"01");
+        assert_ne!(PaletteChoice::Auto.resolve(&zelda), PaletteChoice::DarkGreen.resolve(&[]));
+    }
+
+    #[test]
+    fn shared_checksum_is_told_apart_by_the_fourth_letter() {
+        assert_eq!(title_checksum(b"SUPER MARIOLAND"), title_checksum(b"METROID2"));
+
+        let mario = PaletteChoice::Auto.resolve(&header("SUPER MARIOLAND", 0x01)).unwrap();
+        assert_eq!(mario.bg, [0x7ED6, 0x4BFF, 0x2175, 0x0000]);
+        assert_eq!(mario.obj0, [0x0000, 0x7FFF, 0x421F, 0x1CF2]);
+
+        let metroid = PaletteChoice::Auto.resolve(&header("METROID2", 0x01)).unwrap();
+        assert_eq!(metroid.bg, [0x7FFF, 0x7E8C, 0x7C00, 0x0000]);
+        assert_eq!(metroid.obj0, [0x03FF, 0x001F, 0x000C, 0x0000]);
+        assert_eq!(metroid.obj1, [0x7FFF, 0x1BEF, 0x0200, 0x0000]);
+
+        // Same checksum with a 4th letter the table doesn't list
+        assert_eq!(PaletteChoice::Auto.resolve(&header("METORID2", 0x01)), PaletteChoice::DarkGreen.resolve(&[]));
+    }
+
+    #[test]
+    fn palette_colors_are_rgb555() {
+        let palette = PaletteChoice::Grayscale.resolve(&[]).unwrap();
+        assert_eq!(palette.bg[0], 0x7FFF);
+        assert_eq!(palette.bg[3], 0x0000);
+        assert_eq!(PaletteChoice::Red.resolve(&[]).unwrap().bg[2], 0x1CF2);
+        assert_eq!(PaletteChoice::from_name("dark green"), Some(PaletteChoice::DarkGreen));
+    }
+}
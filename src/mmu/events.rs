@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+
+use crate::mmu::interrupt::Interrupt;
+
+const MAX_EVENTS: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventKind {
+    TimaOverflow,
+    Requested(Interrupt),
+    Masked(Interrupt), // requested while its IE bit is off
+    Serviced(Interrupt),
+}
+
+// Events raised by a component during the current tick, they get their timestamp from the GameBoy
+#[derive(Default, Clone)]
+pub struct EventRecorder {
+    enabled: bool,
+    pending: Vec<EventKind>,
+}
+
+impl EventRecorder {
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.pending.clear();
+    }
+
+    pub fn record(&mut self, kind: EventKind) {
+        if self.enabled {
+            self.pending.push(kind);
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<EventKind> {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HardwareEvent {
+    pub kind: EventKind,
+    pub cycle: u64, // CPU cycles since power on
+    pub frame: u64,
+    pub line: u8,
+    pub dot: u32,
+}
+
+#[derive(Default)]
+pub struct EventLog {
+    enabled: bool,
+    frame: u64,
+    events: VecDeque<HardwareEvent>,
+}
+
+impl EventLog {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.events.clear();
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    pub fn push(&mut self, kind: EventKind, cycle: u64, line: u8, dot: u32) {
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(HardwareEvent { kind, cycle, frame: self.frame, line, dot });
+    }
+
+    // Events recorded since the last call
+    pub fn drain(&mut self) -> Vec<HardwareEvent> {
+        self.events.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorder_ignores_events_when_disabled() {
+        let mut recorder = EventRecorder::default();
+        recorder.record(EventKind::TimaOverflow);
+        assert!(recorder.take().is_empty());
+
+        recorder.set_enabled(true);
+        recorder.record(EventKind::Requested(Interrupt::Timer));
+        assert_eq!(recorder.take(), vec![EventKind::Requested(Interrupt::Timer)]);
+        assert!(recorder.take().is_empty());
+    }
+
+    #[test]
+    fn log_stamps_events_with_the_frame() {
+        let mut log = EventLog::default();
+        log.set_enabled(true);
+        log.push(EventKind::TimaOverflow, 10, 3, 100);
+        log.next_frame();
+        log.push(EventKind::Serviced(Interrupt::VBlank), 70300, 144, 4);
+
+        let events = log.drain();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].frame, 0);
+        assert_eq!(events[1].frame, 1);
+        assert_eq!(events[1].line, 144);
+        assert!(log.drain().is_empty());
+    }
+}
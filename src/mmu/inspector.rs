@@ -0,0 +1,9 @@
+/*
+    Debug access to memory for the debugger and other tools.
+    peek never changes the emulated state (no OAM bug, no boot rom overlay, no joypad refresh)
+    and poke only changes the targeted byte (no MBC register, DMA start or timer reset).
+*/
+pub trait MemoryInspector {
+    fn peek(&self, addr: u16) -> u8;
+    fn poke(&mut self, addr: u16, val: u8);
+}
@@ -1,3 +1,5 @@
+use crate::mmu::events::{EventKind, EventRecorder};
+
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Interrupt {
@@ -24,6 +26,7 @@ impl Interrupt {
 pub struct InterruptController {
     ienable: u8,
     iflag: u8,
+    pub events: EventRecorder,
 }
 
 impl InterruptController {
@@ -31,6 +34,7 @@ impl InterruptController {
         InterruptController {
             ienable: 0,
             iflag: 0,
+            events: EventRecorder::default(),
         }
     }
 
@@ -52,10 +56,17 @@ impl InterruptController {
 
     pub fn request(&mut self, interrupt: Interrupt) {
         self.iflag |= interrupt as u8;
+        if self.ienable & interrupt as u8 != 0 {
+            self.events.record(EventKind::Requested(interrupt));
+        } else {
+            self.events.record(EventKind::Masked(interrupt));
+        }
     }
 
+    // Only used by the CPU when it jumps to the interrupt vector
     pub fn clear_request(&mut self, interrupt: Interrupt) {
         self.iflag &= !(interrupt as u8);
+        self.events.record(EventKind::Serviced(interrupt));
     }
 
     pub fn next_request(&self) -> Option<Interrupt> {
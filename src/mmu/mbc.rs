@@ -1,14 +1,68 @@
 use std::cmp::min;
 
 use chrono::{Local, DateTime};
+
+use crate::mmu::inspector::MemoryInspector;
 const ONLY_ROM_SIZE: usize = 0xC000;
 const ROM_BANK_SIZE: usize = 0x4000;
 const RAM_BANK_SIZE: usize = 0x2000;
 
-pub trait Mbc {
+pub trait Mbc: MemoryInspector + 'static {
     fn new(rom_image: &[u8]) -> Result<Self, String> where Self: Sized;
     fn read(&self, addr: u16) -> u8;
     fn write(&mut self, addr: u16, val: u8);
+    fn write_rom_bank(&mut self, bank: usize, offset: usize, val: u8) -> Result<(), String>;
+    fn write_ram_bank(&mut self, bank: usize, offset: usize, val: u8) -> Result<(), String>;
+    fn rom_bank(&self, bank: usize) -> Option<&[u8]>;
+    fn ram_bank(&self, bank: usize) -> Option<&[u8]>;
+    fn rom_bank_count(&self) -> usize;
+    // The bank currently seen at 0x4000-0x7FFF
+    fn mapped_rom_bank(&self) -> usize;
+    fn ram_bank_count(&self) -> usize;
+}
+
+fn write_into_banks<const N: usize>(banks: &mut [[u8; N]], bank: usize, offset: usize, val: u8) -> Result<(), String> {
+    let bank_count = banks.len();
+    let data = banks
+        .get_mut(bank)
+        .ok_or(format!("Bank {} doesn't exist, cartridge has {} banks", bank, bank_count))?;
+    let byte = data
+        .get_mut(offset)
+        .ok_or(format!("Offset 0x{:04X} is outside of a 0x{:04X} bytes bank", offset, N))?;
+    *byte = val;
+    Ok(())
+}
+
+// Where a cartridge address currently lands, following the banking registers
+enum BankLocation {
+    Rom(usize, usize),
+    Ram(usize, usize),
+    Unmapped,
+}
+
+fn peek_bank_location<T: Mbc>(cart: &T, location: BankLocation) -> u8 {
+    let bank = match location {
+        BankLocation::Rom(bank, offset) => cart.rom_bank(bank).map(|data| (data, offset)),
+        BankLocation::Ram(bank, offset) => cart.ram_bank(bank).map(|data| (data, offset)),
+        BankLocation::Unmapped => None,
+    };
+    bank.and_then(|(data, offset)| data.get(offset).copied()).unwrap_or(0xFF)
+}
+
+fn mapped_rom_bank_of(location: BankLocation) -> usize {
+    match location {
+        BankLocation::Rom(bank, _) => bank,
+        _ => 1,
+    }
+}
+
+fn poke_bank_location<T: Mbc>(cart: &mut T, location: BankLocation, val: u8) {
+    // Poking outside of the cartridge memory is ignored like a write to open bus
+    let _ = match location {
+        BankLocation::Rom(bank, offset) => cart.write_rom_bank(bank, offset, val),
+        BankLocation::Ram(bank, offset) => cart.write_ram_bank(bank, offset, val),
+        BankLocation::Unmapped => Ok(()),
+    };
 }
 
 #[derive(Clone)]
@@ -73,6 +127,35 @@ fn map_ram_banks(rom_image: &[u8]) -> Result<Vec<[u8; RAM_BANK_SIZE]>, String> {
     Ok(vec![[0u8; RAM_BANK_SIZE]; supposed_ram_bank_size])
 }
 
+impl Mbc1 {
+    fn bank_location(&self, addr: u16) -> BankLocation {
+        match addr {
+            0x0000..0x4000 if self.mode_register => BankLocation::Rom(0, addr as usize),
+            0x0000..0x4000 => BankLocation::Rom((self.bank_register_2 << 5) as usize, addr as usize),
+            0x4000..0x8000 => BankLocation::Rom(
+                ((self.bank_register_2 << 5) + self.bank_register_1) as usize,
+                addr as usize - ROM_BANK_SIZE,
+            ),
+            0xA000..0xC000 => BankLocation::Ram(
+                self.mode_register as usize * self.bank_register_2 as usize,
+                addr as usize - 0xA000,
+            ),
+            _ => BankLocation::Unmapped,
+        }
+    }
+}
+
+impl MemoryInspector for Mbc1 {
+    fn peek(&self, addr: u16) -> u8 {
+        peek_bank_location(self, self.bank_location(addr))
+    }
+
+    fn poke(&mut self, addr: u16, val: u8) {
+        let location = self.bank_location(addr);
+        poke_bank_location(self, location, val);
+    }
+}
+
 impl Mbc for Mbc1 {
     fn new(rom_image: &[u8]) -> Result<Self, String> {
         println!("rom detected is Mbc1");
@@ -140,6 +223,34 @@ impl Mbc for Mbc1 {
             _ => unreachable!()
         }
     }
+
+    fn write_rom_bank(&mut self, bank: usize, offset: usize, val: u8) -> Result<(), String> {
+        write_into_banks(&mut self.banks, bank, offset, val)
+    }
+
+    fn write_ram_bank(&mut self, bank: usize, offset: usize, val: u8) -> Result<(), String> {
+        write_into_banks(&mut self.ram_banks, bank, offset, val)
+    }
+
+    fn rom_bank(&self, bank: usize) -> Option<&[u8]> {
+        self.banks.get(bank).map(|data| data.as_slice())
+    }
+
+    fn ram_bank(&self, bank: usize) -> Option<&[u8]> {
+        self.ram_banks.get(bank).map(|data| data.as_slice())
+    }
+
+    fn rom_bank_count(&self) -> usize {
+        self.banks.len()
+    }
+
+    fn mapped_rom_bank(&self) -> usize {
+        mapped_rom_bank_of(self.bank_location(0x4000))
+    }
+
+    fn ram_bank_count(&self) -> usize {
+        self.ram_banks.len()
+    }
 }
 
 pub struct Mbc2 {
@@ -149,6 +260,28 @@ pub struct Mbc2 {
     ram_banks: Vec<[u8; RAM_BANK_SIZE]>,
 }
 
+impl Mbc2 {
+    fn bank_location(&self, addr: u16) -> BankLocation {
+        match addr {
+            0x0000..0x4000 => BankLocation::Rom(0, addr as usize),
+            0x4000..0x8000 => BankLocation::Rom(self.rom_bank_register as usize, (addr - 0x4000) as usize),
+            0xA000..0xC000 => BankLocation::Ram(0, (addr & 0b1111_1111) as usize),
+            _ => BankLocation::Unmapped,
+        }
+    }
+}
+
+impl MemoryInspector for Mbc2 {
+    fn peek(&self, addr: u16) -> u8 {
+        peek_bank_location(self, self.bank_location(addr))
+    }
+
+    fn poke(&mut self, addr: u16, val: u8) {
+        let location = self.bank_location(addr);
+        poke_bank_location(self, location, val);
+    }
+}
+
 impl Mbc for Mbc2 {
     fn read(&self, addr: u16) -> u8 {
         match addr {
@@ -190,6 +323,34 @@ impl Mbc for Mbc2 {
         }
     }
 
+    fn write_rom_bank(&mut self, bank: usize, offset: usize, val: u8) -> Result<(), String> {
+        write_into_banks(&mut self.rom_banks, bank, offset, val)
+    }
+
+    fn write_ram_bank(&mut self, bank: usize, offset: usize, val: u8) -> Result<(), String> {
+        write_into_banks(&mut self.ram_banks, bank, offset, val)
+    }
+
+    fn rom_bank(&self, bank: usize) -> Option<&[u8]> {
+        self.rom_banks.get(bank).map(|data| data.as_slice())
+    }
+
+    fn ram_bank(&self, bank: usize) -> Option<&[u8]> {
+        self.ram_banks.get(bank).map(|data| data.as_slice())
+    }
+
+    fn rom_bank_count(&self) -> usize {
+        self.rom_banks.len()
+    }
+
+    fn mapped_rom_bank(&self) -> usize {
+        mapped_rom_bank_of(self.bank_location(0x4000))
+    }
+
+    fn ram_bank_count(&self) -> usize {
+        self.ram_banks.len()
+    }
+
     fn new(rom_image: &[u8]) -> Result<Self, String> where Self: Sized {
         println!("rom detected is Mbc2");
         let rom_banks = map_rom_into_bank(rom_image)?;
@@ -206,6 +367,21 @@ pub struct RomOnly {
     bank: [u8; ONLY_ROM_SIZE],
 }
 
+impl MemoryInspector for RomOnly {
+    fn peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..0x8000 | 0xA000..0xC000 => self.bank[addr as usize],
+            _ => 0xFF,
+        }
+    }
+
+    fn poke(&mut self, addr: u16, val: u8) {
+        if let 0x0000..0x8000 | 0xA000..0xC000 = addr {
+            self.bank[addr as usize] = val;
+        }
+    }
+}
+
 impl Mbc for RomOnly{
     fn new(rom_image: &[u8]) -> Result<Self, String> {
         println!("rom detected is romonly");
@@ -227,6 +403,48 @@ impl Mbc for RomOnly{
         }
 
     }
+
+    fn write_rom_bank(&mut self, bank: usize, offset: usize, val: u8) -> Result<(), String> {
+        if bank > 1 || offset >= ROM_BANK_SIZE {
+            return Err(format!("Rom only cartridge has no byte at bank {} offset 0x{:04X}", bank, offset));
+        }
+        self.bank[bank * ROM_BANK_SIZE + offset] = val;
+        Ok(())
+    }
+
+    fn write_ram_bank(&mut self, bank: usize, offset: usize, val: u8) -> Result<(), String> {
+        if bank > 0 || offset >= RAM_BANK_SIZE {
+            return Err(format!("Rom only cartridge has no ram at bank {} offset 0x{:04X}", bank, offset));
+        }
+        self.bank[0xA000 + offset] = val;
+        Ok(())
+    }
+
+    fn rom_bank(&self, bank: usize) -> Option<&[u8]> {
+        match bank {
+            0 | 1 => Some(&self.bank[bank * ROM_BANK_SIZE..(bank + 1) * ROM_BANK_SIZE]),
+            _ => None,
+        }
+    }
+
+    fn ram_bank(&self, bank: usize) -> Option<&[u8]> {
+        match bank {
+            0 => Some(&self.bank[0xA000..0xA000 + RAM_BANK_SIZE]),
+            _ => None,
+        }
+    }
+
+    fn rom_bank_count(&self) -> usize {
+        2
+    }
+
+    fn mapped_rom_bank(&self) -> usize {
+        1
+    }
+
+    fn ram_bank_count(&self) -> usize {
+        1
+    }
 }
 
 pub struct Mbc3 {
@@ -241,6 +459,18 @@ pub struct Mbc3 {
 }
 
 impl Mbc3 {
+    fn bank_location(&self, addr: u16) -> BankLocation {
+        match addr {
+            0x0000..0x4000 => BankLocation::Rom(0, addr as usize),
+            0x4000..0x8000 => BankLocation::Rom(self.rom_bank_nb as usize, (addr - 0x4000) as usize),
+            // The RTC registers are read from the host clock, there is no byte to inspect
+            0xA000..0xC000 if (0x00..0x07).contains(&self.ram_rtc_select) => {
+                BankLocation::Ram(self.ram_rtc_select as usize, (addr - 0xA000) as usize)
+            }
+            _ => BankLocation::Unmapped,
+        }
+    }
+
     fn get_time_value(&self, rtc_select: &u8) -> u8 {
         let time = if let Some(latched_time) = &self.latched_time_value {
             latched_time.clone()
@@ -263,6 +493,17 @@ impl Mbc3 {
     }
 }
 
+impl MemoryInspector for Mbc3 {
+    fn peek(&self, addr: u16) -> u8 {
+        peek_bank_location(self, self.bank_location(addr))
+    }
+
+    fn poke(&mut self, addr: u16, val: u8) {
+        let location = self.bank_location(addr);
+        poke_bank_location(self, location, val);
+    }
+}
+
 impl Mbc for Mbc3 {
     fn read(&self, addr: u16) -> u8 {
         match addr {
@@ -301,6 +542,32 @@ impl Mbc for Mbc3 {
         }
         
     }
+    fn write_rom_bank(&mut self, bank: usize, offset: usize, val: u8) -> Result<(), String> {
+        write_into_banks(&mut self.rom_banks, bank, offset, val)
+    }
+    fn write_ram_bank(&mut self, bank: usize, offset: usize, val: u8) -> Result<(), String> {
+        write_into_banks(&mut self.ram_banks, bank, offset, val)
+    }
+
+    fn rom_bank(&self, bank: usize) -> Option<&[u8]> {
+        self.rom_banks.get(bank).map(|data| data.as_slice())
+    }
+
+    fn ram_bank(&self, bank: usize) -> Option<&[u8]> {
+        self.ram_banks.get(bank).map(|data| data.as_slice())
+    }
+
+    fn rom_bank_count(&self) -> usize {
+        self.rom_banks.len()
+    }
+
+    fn mapped_rom_bank(&self) -> usize {
+        mapped_rom_bank_of(self.bank_location(0x4000))
+    }
+
+    fn ram_bank_count(&self) -> usize {
+        self.ram_banks.len()
+    }
     fn new(rom_image: &[u8]) -> Result<Self, String> where Self: Sized {
         let rom_banks = map_rom_into_bank(rom_image)?;
         let ram_banks = map_ram_banks(rom_image)?;
@@ -331,6 +598,28 @@ pub struct Mbc5 {
     
 }
 
+impl Mbc5 {
+    fn bank_location(&self, addr: u16) -> BankLocation {
+        match addr {
+            0x0000..0x4000 => BankLocation::Rom(0, addr as usize),
+            0x4000..0x8000 => BankLocation::Rom(self.rom_bank_register as usize % self.rom_banks.len(), (addr - 0x4000) as usize),
+            0xA000..0xC000 => BankLocation::Ram(self.ram_bank_register as usize, (addr - 0xA000) as usize),
+            _ => BankLocation::Unmapped,
+        }
+    }
+}
+
+impl MemoryInspector for Mbc5 {
+    fn peek(&self, addr: u16) -> u8 {
+        peek_bank_location(self, self.bank_location(addr))
+    }
+
+    fn poke(&mut self, addr: u16, val: u8) {
+        let location = self.bank_location(addr);
+        poke_bank_location(self, location, val);
+    }
+}
+
 impl Mbc for Mbc5 {
     fn new(rom_image: &[u8]) -> Result<Self, String> where Self: Sized {
         let rom_banks = map_rom_into_bank(rom_image)?;
@@ -341,7 +630,7 @@ impl Mbc for Mbc5 {
                 rom_banks,
                 ram_banks,
                 ram_gate_enable: false,
-                rom_bank_register: 0,
+                rom_bank_register: 1,
                 ram_bank_register: 0,
                 ramble: false,
             }
@@ -351,33 +640,113 @@ impl Mbc for Mbc5 {
         match addr {
             0x0000..0x4000 => self.rom_banks[0][addr as usize],
             0x4000..0x8000 => {
-                self.rom_banks[
-                    (self.rom_bank_register - 0x4000) as usize
-                ][
-                    (addr  - 0x4000) as usize
-                ]
+                // 9-bit register, carts smaller than 8 MiB ignore the high bits
+                let bank = self.rom_bank_register as usize % self.rom_banks.len();
+                self.rom_banks[bank][(addr - 0x4000) as usize]
             },
-            0xA000..0xC000 => {
-                self.ram_banks[
-                    self.ram_bank_register as usize
-                ][
-                    (addr - 0xA000) as usize
-                ]
+            0xA000..0xC000 => match self.ram_banks.get(self.ram_bank_register as usize) {
+                Some(bank) if self.ram_gate_enable => bank[(addr - 0xA000) as usize],
+                _ => 0xFF,
             },
             _ => unreachable!(),
         }
     }
     fn write(&mut self, addr: u16, val: u8) {
         match addr {
-            0x0000..0x2000 => self.ram_gate_enable = val == 0b0000_1010,
-            0x2000..0x3000 => self.rom_bank_register = self.rom_bank_register & 0x100 + val as u16,
-            0x3000..0x4000 => self.rom_bank_register = self.rom_bank_register & 0x0FF + val as u16 & 0x01 * 0x100,
+            0x0000..0x2000 => self.ram_gate_enable = val & 0x0F == 0b0000_1010,
+            0x2000..0x3000 => self.rom_bank_register = (self.rom_bank_register & 0x100) | val as u16,
+            0x3000..0x4000 => self.rom_bank_register = (self.rom_bank_register & 0x0FF) | ((val as u16 & 0x01) << 8),
             0x4000..0x6000 => {
                 self.ram_bank_register = val & 0x0F;
                 self.ramble = (val & 0x10) != 0;
             }
+            0x6000..0x8000 => {}, // no register there, unlike MBC1
+            0xA000..0xC000 => {
+                if let (true, Some(bank)) = (self.ram_gate_enable, self.ram_banks.get_mut(self.ram_bank_register as usize)) {
+                    bank[(addr - 0xA000) as usize] = val;
+                }
+            },
             _ => unreachable!(),
         }
     }
+    fn write_rom_bank(&mut self, bank: usize, offset: usize, val: u8) -> Result<(), String> {
+        write_into_banks(&mut self.rom_banks, bank, offset, val)
+    }
+    fn write_ram_bank(&mut self, bank: usize, offset: usize, val: u8) -> Result<(), String> {
+        write_into_banks(&mut self.ram_banks, bank, offset, val)
+    }
+
+    fn rom_bank(&self, bank: usize) -> Option<&[u8]> {
+        self.rom_banks.get(bank).map(|data| data.as_slice())
+    }
+
+    fn ram_bank(&self, bank: usize) -> Option<&[u8]> {
+        self.ram_banks.get(bank).map(|data| data.as_slice())
+    }
+
+    fn rom_bank_count(&self) -> usize {
+        self.rom_banks.len()
+    }
+
+    fn mapped_rom_bank(&self) -> usize {
+        self.rom_bank_register as usize % self.rom_banks.len()
+    }
+
+    fn ram_bank_count(&self) -> usize {
+        self.ram_banks.len()
+    }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mbc5_switches_nine_bit_rom_banks_and_gated_ram() {
+        let mut rom = vec![0u8; 512 * ROM_BANK_SIZE];
+        rom[0x0147] = 0x1B;
+        rom[0x0148] = 0x08;
+        rom[0x0149] = 0x03;
+        for bank in 0..512 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+            rom[bank * ROM_BANK_SIZE + 1] = (bank >> 8) as u8;
+        }
+        let mut cart = Mbc5::new(&rom).unwrap();
+        assert_eq!(cart.read(0x4000), 1);
+
+        cart.write(0x2000, 0x00); // bank 0 is allowed in the switchable area
+        assert_eq!(cart.read(0x4000), 0);
+        cart.write(0x3000, 0x01);
+        cart.write(0x2000, 0x05);
+        assert_eq!((cart.read(0x4000), cart.read(0x4001)), (0x05, 0x01));
+        assert_eq!(cart.mapped_rom_bank(), 0x105);
+
+        cart.write(0x4000, 0x02);
+        cart.write(0xA000, 0x42);
+        assert_eq!(cart.read(0xA000), 0xFF);
+        cart.write(0x0000, 0x0A);
+        cart.write(0xA000, 0x42);
+        assert_eq!(cart.read(0xA000), 0x42);
+        assert_eq!(cart.ram_bank(2).unwrap()[0], 0x42);
+        cart.write(0x6000, 0x01);
+    }
+
+    #[test]
+    fn mbc5_peek_wraps_the_rom_bank_like_read() {
+        let mut rom = vec![0u8; 4 * ROM_BANK_SIZE];
+        rom[0x0147] = 0x19;
+        rom[0x0148] = 0x01;
+        for bank in 0..4 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        let mut cart = Mbc5::new(&rom).unwrap();
+
+        cart.write(0x2000, 0x06); // past the 4 banks of the cart
+        assert_eq!(cart.read(0x4000), 0x02);
+        assert_eq!(cart.peek(0x4000), cart.read(0x4000));
+
+        cart.poke(0x4000, 0x42);
+        assert_eq!(cart.rom_bank(2).unwrap()[0], 0x42);
+    }
+}
@@ -1,4 +1,5 @@
 const OAM_BEGINNING: u16 = 0xFE00;
+const MAX_SPRITES_PER_LINE: usize = 10;
 
 #[derive(Clone, Copy)]
 pub struct Sprite {
@@ -93,6 +94,25 @@ impl Oam {
         self.write_raw(offset + 1, val_2);
     }
 
+    // Mode 2 checks one entry at a time, a visible sprite is kept while fewer than 10 were kept before it
+    pub fn selects(&self, index: u8, ly: u8, height: u8, selected_count: usize) -> bool {
+        selected_count < MAX_SPRITES_PER_LINE && self.sprites[index as usize].is_visible(ly, height)
+    }
+
+    // OAM indexes of the sprites on a scanline as mode 2 sees them, visible sprites it doesn't keep are dropped
+    pub fn scan_line(&self, ly: u8, height: u8) -> (Vec<u8>, Vec<u8>) {
+        let mut selected = Vec::new();
+        let mut dropped = Vec::new();
+        for index in 0..40u8 {
+            if self.selects(index, ly, height, selected.len()) {
+                selected.push(index);
+            } else if self.sprites[index as usize].is_visible(ly, height) {
+                dropped.push(index);
+            }
+        }
+        (selected, dropped)
+    }
+
     fn corrupt_words_if_write(&self, mut words: [u16; 4], prev_words: [u16; 4]) -> [u16; 4] {
         let original_value = words[0];
         let first_word_of_prev_row = prev_words[0];
@@ -268,6 +288,23 @@ mod tests {
         assert_eq!(oam.sprites[39].attributes, 0xFF);
     }
 
+    #[test]
+    fn test_scan_line_drops_sprites_after_ten() {
+        let mut oam = Oam::new();
+        for index in 0..12 {
+            oam.sprites[index * 2].y = 16; // lines 0 to 7
+        }
+        oam.sprites[1].y = 40; // lines 24 to 31
+
+        let (selected, dropped) = oam.scan_line(0, 8);
+        assert_eq!(selected, vec![0, 2, 4, 6, 8, 10, 12, 14, 16, 18]);
+        assert_eq!(dropped, vec![20, 22]);
+
+        let (selected, dropped) = oam.scan_line(24, 8);
+        assert_eq!(selected, vec![1]);
+        assert!(dropped.is_empty());
+    }
+
     #[test]
     fn test_read_sprite_0_y_position() {
         let mut oam = Oam::new();
@@ -0,0 +1,424 @@
+/*
+    Super Game Boy: the game talks to the SNES through P1 (0xFF00). Writing 0x00 resets, then each bit is
+    a pulse, P14 low (0x20) for a 0 and P15 low (0x10) for a 1, with 0x30 in between. A packet is 16 bytes
+    LSB first followed by a 0 stop bit, the low 3 bits of the first byte say how many packets the command
+    takes and the high 5 bits are the command.
+*/
+pub const BORDER_WIDTH: usize = 256;
+pub const BORDER_HEIGHT: usize = 224;
+pub const SCREEN_X: usize = 48; // where the Game Boy screen sits in the border
+pub const SCREEN_Y: usize = 40;
+
+const PACKET_SIZE: usize = 16;
+const CELLS_X: usize = 20;
+const CELLS_Y: usize = 18;
+
+const PAL01: u8 = 0x00;
+const PAL23: u8 = 0x01;
+const PAL03: u8 = 0x02;
+const PAL12: u8 = 0x03;
+const ATTR_BLK: u8 = 0x04;
+const ATTR_LIN: u8 = 0x05;
+const ATTR_DIV: u8 = 0x06;
+const ATTR_CHR: u8 = 0x07;
+const MLT_REQ: u8 = 0x11;
+const CHR_TRN: u8 = 0x13;
+const PCT_TRN: u8 = 0x14;
+const MASK_EN: u8 = 0x17;
+
+// Palette the SGB shows before a game sends its own
+const DEFAULT_PALETTE: [u16; 4] = [0x67BF, 0x265B, 0x10B5, 0x2866];
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Mask {
+    #[default]
+    Off,
+    Freeze,
+    Black,
+    Color0,
+}
+
+// CHR_TRN and PCT_TRN copy 4KB of what the Game Boy displays, the MMU fetches it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VramTransfer {
+    BorderTiles { high: bool },
+    BorderMap,
+}
+
+pub struct Sgb {
+    packet: [u8; PACKET_SIZE],
+    bit_index: Option<usize>, // None when no packet is being received
+    command: Vec<u8>,
+    last_p1: u8,
+    players: u8,
+    player: u8,
+    palettes: [[u16; 4]; 4],
+    attributes: [u8; CELLS_X * CELLS_Y],
+    mask: Mask,
+    border_tiles: Vec<u8>, // 256 SNES 4bpp tiles of 32 bytes
+    border_map: Vec<u8>, // 32x28 entries of 2 bytes then palettes 4-7
+    border_changed: bool,
+}
+
+impl Default for Sgb {
+    fn default() -> Self {
+        Sgb {
+            packet: [0; PACKET_SIZE],
+            bit_index: None,
+            command: Vec::new(),
+            last_p1: 0x30,
+            players: 1,
+            player: 0,
+            palettes: [DEFAULT_PALETTE; 4],
+            attributes: [0; CELLS_X * CELLS_Y],
+            mask: Mask::Off,
+            border_tiles: vec![0; 0x2000],
+            border_map: vec![0; 0x1000],
+            border_changed: false,
+        }
+    }
+}
+
+impl Sgb {
+    // Bits 4-5 of a P1 write, returns the transfer to do once a CHR_TRN/PCT_TRN command is complete
+    pub fn write_p1(&mut self, val: u8) -> Option<VramTransfer> {
+        let p1 = val & 0x30;
+        let last_p1 = self.last_p1;
+        self.last_p1 = p1;
+
+        // MLT_REQ: the next controller is selected each time P15 goes low
+        if p1 & 0x20 == 0 && last_p1 & 0x20 != 0 && self.players > 1 {
+            self.player = (self.player + 1) % self.players;
+        }
+
+        if p1 == 0x00 {
+            self.packet = [0; PACKET_SIZE];
+            self.bit_index = Some(0);
+            return None;
+        }
+        if last_p1 != 0x30 || p1 == 0x30 {
+            return None;
+        }
+
+        let bit_index = self.bit_index?;
+        let bit = (p1 == 0x10) as u8;
+        if bit_index < PACKET_SIZE * 8 {
+            self.packet[bit_index / 8] |= bit << (bit_index % 8);
+            self.bit_index = Some(bit_index + 1);
+            return None;
+        }
+
+        // Stop bit
+        self.bit_index = None;
+        self.receive_packet()
+    }
+
+    // Lower nibble of P1 when neither the buttons nor the d-pad are selected
+    pub fn joypad_id(&self) -> u8 {
+        0x0F - self.player
+    }
+
+    pub fn player(&self) -> u8 {
+        self.player
+    }
+
+    fn receive_packet(&mut self) -> Option<VramTransfer> {
+        self.command.extend_from_slice(&self.packet);
+
+        let packet_count = (self.command[0] & 0b111).max(1) as usize;
+        if self.command.len() < packet_count * PACKET_SIZE {
+            return None;
+        }
+
+        let command = std::mem::take(&mut self.command);
+        self.execute(&command)
+    }
+
+    fn execute(&mut self, data: &[u8]) -> Option<VramTransfer> {
+        match data[0] >> 3 {
+            PAL01 => self.set_palettes(0, 1, data),
+            PAL23 => self.set_palettes(2, 3, data),
+            PAL03 => self.set_palettes(0, 3, data),
+            PAL12 => self.set_palettes(1, 2, data),
+            ATTR_BLK => self.attribute_blocks(data),
+            ATTR_LIN => self.attribute_lines(data),
+            ATTR_DIV => self.attribute_division(data),
+            ATTR_CHR => self.attribute_characters(data),
+            MLT_REQ => {
+                self.players = match data[1] & 0b11 {
+                    1 => 2,
+                    3 => 4,
+                    _ => 1,
+                };
+                self.player = 0;
+            }
+            CHR_TRN => return Some(VramTransfer::BorderTiles { high: data[1] & 1 != 0 }),
+            PCT_TRN => return Some(VramTransfer::BorderMap),
+            MASK_EN => {
+                self.mask = match data[1] & 0b11 {
+                    1 => Mask::Freeze,
+                    2 => Mask::Black,
+                    3 => Mask::Color0,
+                    _ => Mask::Off,
+                };
+            }
+            _ => {} // sound, SNES code upload and the other commands aren't emulated
+        }
+        None
+    }
+
+    // Color 0 is shared by the 4 palettes
+    fn set_palettes(&mut self, first: usize, second: usize, data: &[u8]) {
+        let color = |i: usize| u16::from_le_bytes([data[1 + i * 2], data[2 + i * 2]]) & 0x7FFF;
+
+        for palette in self.palettes.iter_mut() {
+            palette[0] = color(0);
+        }
+        for color_index in 1..4 {
+            self.palettes[first][color_index] = color(color_index);
+            self.palettes[second][color_index] = color(color_index + 3);
+        }
+    }
+
+    fn set_cell(&mut self, x: usize, y: usize, palette: u8) {
+        if x < CELLS_X && y < CELLS_Y {
+            self.attributes[y * CELLS_X + x] = palette & 0b11;
+        }
+    }
+
+    fn attribute_blocks(&mut self, data: &[u8]) {
+        let count = (data[1] & 0x1F) as usize;
+
+        for block in data[2..].chunks_exact(6).take(count) {
+            let control = block[0] & 0b111;
+            let inside = block[1] & 0b11;
+            let mut line = (block[1] >> 2) & 0b11;
+            let outside = (block[1] >> 4) & 0b11;
+            // With only one side set, the border line takes its palette
+            match control {
+                0b001 => line = inside,
+                0b100 => line = outside,
+                _ => {}
+            }
+            let line_enabled = control & 0b010 != 0 || control == 0b001 || control == 0b100;
+            let (x1, y1, x2, y2) = (block[2] as usize, block[3] as usize, block[4] as usize, block[5] as usize);
+
+            for y in 0..CELLS_Y {
+                for x in 0..CELLS_X {
+                    let in_x = (x1..=x2).contains(&x);
+                    let in_y = (y1..=y2).contains(&y);
+                    let on_line = in_x && in_y && (x == x1 || x == x2 || y == y1 || y == y2);
+
+                    if on_line && line_enabled {
+                        self.set_cell(x, y, line);
+                    } else if in_x && in_y && !on_line && control & 0b001 != 0 {
+                        self.set_cell(x, y, inside);
+                    } else if !(in_x && in_y) && control & 0b100 != 0 {
+                        self.set_cell(x, y, outside);
+                    }
+                }
+            }
+        }
+    }
+
+    fn attribute_lines(&mut self, data: &[u8]) {
+        let count = data[1] as usize;
+
+        for &line in data[2..].iter().take(count) {
+            let index = (line & 0x1F) as usize;
+            let palette = (line >> 5) & 0b11;
+            for i in 0..CELLS_X.max(CELLS_Y) {
+                if line & 0x80 != 0 {
+                    self.set_cell(i, index, palette);
+                } else {
+                    self.set_cell(index, i, palette);
+                }
+            }
+        }
+    }
+
+    // Splits the screen at a row (or column), with its own palette on the split line
+    fn attribute_division(&mut self, data: &[u8]) {
+        let after = data[1] & 0b11;
+        let before = (data[1] >> 2) & 0b11;
+        let on_line = (data[1] >> 4) & 0b11;
+        let horizontal = data[1] & 0x40 != 0;
+        let split = data[2] as usize;
+
+        for y in 0..CELLS_Y {
+            for x in 0..CELLS_X {
+                let position = if horizontal { y } else { x };
+                let palette = match position.cmp(&split) {
+                    std::cmp::Ordering::Less => before,
+                    std::cmp::Ordering::Equal => on_line,
+                    std::cmp::Ordering::Greater => after,
+                };
+                self.set_cell(x, y, palette);
+            }
+        }
+    }
+
+    // 2 bits per cell, from (x, y) left to right or top to bottom
+    fn attribute_characters(&mut self, data: &[u8]) {
+        let (mut x, mut y) = (data[1] as usize, data[2] as usize);
+        let count = (u16::from_le_bytes([data[3], data[4]]) as usize).min(CELLS_X * CELLS_Y);
+        let vertical = data[5] & 1 != 0;
+
+        for i in 0..count {
+            let Some(byte) = data.get(6 + i / 4) else { break };
+            let palette = (byte >> (6 - (i % 4) * 2)) & 0b11;
+            self.set_cell(x, y, palette);
+
+            if vertical {
+                y += 1;
+                if y == CELLS_Y {
+                    y = 0;
+                    x += 1;
+                }
+            } else {
+                x += 1;
+                if x == CELLS_X {
+                    x = 0;
+                    y += 1;
+                }
+            }
+        }
+    }
+
+    pub fn finish_transfer(&mut self, transfer: VramTransfer, data: &[u8]) {
+        match transfer {
+            VramTransfer::BorderTiles { high } => {
+                let start = if high { 0x1000 } else { 0 };
+                self.border_tiles[start..start + 0x1000].copy_from_slice(data);
+            }
+            VramTransfer::BorderMap => self.border_map.copy_from_slice(data),
+        }
+        self.border_changed = true;
+    }
+
+    // None when MASK_EN freezes the screen, the last frame stays
+    pub fn color(&self, x: usize, y: usize, shade: u8) -> Option<u16> {
+        match self.mask {
+            Mask::Off => {
+                let palette = self.attributes[(y / 8) * CELLS_X + x / 8] as usize;
+                Some(self.palettes[palette][shade as usize])
+            }
+            Mask::Freeze => None,
+            Mask::Black => Some(0),
+            Mask::Color0 => Some(self.palettes[0][0]),
+        }
+    }
+
+    pub fn take_border_change(&mut self) -> bool {
+        std::mem::take(&mut self.border_changed)
+    }
+
+    // 256x224 RGB555, transparent pixels get the SGB color 0 like on the SNES backdrop
+    pub fn render_border(&self) -> Vec<u16> {
+        let mut pixels = vec![self.palettes[0][0]; BORDER_WIDTH * BORDER_HEIGHT];
+
+        for map_y in 0..BORDER_HEIGHT / 8 {
+            for map_x in 0..BORDER_WIDTH / 8 {
+                let entry_offset = (map_y * 32 + map_x) * 2;
+                let entry = u16::from_le_bytes([self.border_map[entry_offset], self.border_map[entry_offset + 1]]);
+                let tile = (entry & 0xFF) as usize;
+                let palette = ((entry >> 10) & 0b111) as usize;
+                let x_flip = entry & 0x4000 != 0;
+                let y_flip = entry & 0x8000 != 0;
+
+                for row in 0..8 {
+                    let tile_row = if y_flip { 7 - row } else { row };
+                    let bytes = &self.border_tiles[tile * 32..tile * 32 + 32];
+                    let planes = [bytes[tile_row * 2], bytes[tile_row * 2 + 1], bytes[16 + tile_row * 2], bytes[17 + tile_row * 2]];
+
+                    for column in 0..8 {
+                        let bit = if x_flip { column } else { 7 - column };
+                        let color_index = planes.iter().enumerate().fold(0, |index, (plane, byte)| index | (((byte >> bit) & 1) << plane));
+                        if color_index == 0 {
+                            continue;
+                        }
+                        pixels[(map_y * 8 + row) * BORDER_WIDTH + map_x * 8 + column] = self.border_color(palette, color_index as usize);
+                    }
+                }
+            }
+        }
+        pixels
+    }
+
+    // PCT_TRN only carries palettes 4-7, stored after the map
+    fn border_color(&self, palette: usize, color_index: usize) -> u16 {
+        let offset = 0x800 + (palette.saturating_sub(4) % 4) * 32 + color_index * 2;
+        u16::from_le_bytes([self.border_map[offset], self.border_map[offset + 1]])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send_packet(sgb: &mut Sgb, packet: [u8; 16]) -> Option<VramTransfer> {
+        sgb.write_p1(0x00);
+        sgb.write_p1(0x30);
+        for bit in (0..128).map(|i| (packet[i / 8] >> (i % 8)) & 1) {
+            sgb.write_p1(if bit == 1 { 0x10 } else { 0x20 });
+            sgb.write_p1(0x30);
+        }
+        let transfer = sgb.write_p1(0x20);
+        sgb.write_p1(0x30);
+        transfer
+    }
+
+    #[test]
+    fn pal01_sets_shared_color_0() {
+        let mut sgb = Sgb::default();
+        let mut packet = [0; 16];
+        packet[0] = (PAL01 << 3) | 1;
+        packet[1..3].copy_from_slice(&0x7FFFu16.to_le_bytes());
+        packet[3..5].copy_from_slice(&0x001Fu16.to_le_bytes());
+        packet[9..11].copy_from_slice(&0x03E0u16.to_le_bytes());
+        send_packet(&mut sgb, packet);
+
+        assert_eq!(sgb.palettes[0][1], 0x001F);
+        assert_eq!(sgb.palettes[1][1], 0x03E0);
+        assert_eq!(sgb.palettes[3][0], 0x7FFF);
+        assert_eq!(sgb.color(0, 0, 1), Some(0x001F));
+    }
+
+    #[test]
+    fn attr_blk_and_mask_change_cell_colors() {
+        let mut sgb = Sgb::default();
+        sgb.palettes[2] = [0, 1, 2, 3];
+        let mut packet = [0; 16];
+        packet[0] = (ATTR_BLK << 3) | 1;
+        packet[1] = 1;
+        packet[2..8].copy_from_slice(&[0b001, 0b10, 1, 1, 3, 3]);
+        send_packet(&mut sgb, packet);
+        assert_eq!(sgb.color(8, 8, 3), Some(3));
+        assert_eq!(sgb.color(0, 0, 3), Some(DEFAULT_PALETTE[3]));
+
+        let mut packet = [0; 16];
+        packet[0] = (MASK_EN << 3) | 1;
+        packet[1] = 1;
+        send_packet(&mut sgb, packet);
+        assert_eq!(sgb.color(8, 8, 3), None);
+    }
+
+    #[test]
+    fn mlt_req_cycles_joypad_ids() {
+        let mut sgb = Sgb::default();
+        let mut packet = [0; 16];
+        packet[0] = (MLT_REQ << 3) | 1;
+        packet[1] = 1;
+        send_packet(&mut sgb, packet);
+        assert_eq!(sgb.joypad_id(), 0x0F);
+
+        sgb.write_p1(0x10);
+        sgb.write_p1(0x30);
+        assert_eq!(sgb.joypad_id(), 0x0E);
+
+        let mut packet = [0; 16];
+        packet[0] = (CHR_TRN << 3) | 1;
+        packet[1] = 1;
+        assert_eq!(send_packet(&mut sgb, packet), Some(VramTransfer::BorderTiles { high: true }));
+    }
+}
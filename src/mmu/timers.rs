@@ -1,3 +1,5 @@
+use crate::mmu::events::{EventKind, EventRecorder};
+
 #[derive(Default)]
 pub struct Timers {
     div: u16,
@@ -5,6 +7,7 @@ pub struct Timers {
     tma: u8,
     tac: u8,
     previous_and_result: bool,
+    pub events: EventRecorder,
 }
 
 const DIV_ADDR: u16 = 0xFF04;
@@ -33,6 +36,7 @@ impl Timers {
             let result = self.tima.wrapping_add(1);
             if result == 0 {
                 self.tima = self.tma;
+                self.events.record(EventKind::TimaOverflow);
                 overflowed = true
             } else {
                 self.tima = result;
@@ -51,6 +55,14 @@ impl Timers {
             _ => unreachable!(),
         }
     }
+    // Debug write: DIV takes the value instead of being reset
+    pub fn poke(&mut self, addr: u16, value: u8) {
+        match addr {
+            DIV_ADDR => self.div = (value as u16) << 8,
+            _ => self.write_byte(addr, value),
+        }
+    }
+
     pub fn read_byte(&self, addr: u16) -> u8 {
         let a_box = Box::new(18);
         match addr {
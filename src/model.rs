@@ -0,0 +1,146 @@
+use std::path::{Path, PathBuf};
+
+use crate::mmu::cgb::HardwareMode;
+
+// The console the cart runs on, it decides the boot ROM and the state left when it's skipped
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Model {
+    Dmg0,
+    #[default]
+    Dmg,
+    Mgb,
+    Sgb,
+    Sgb2,
+    Cgb0,
+    Cgb,
+    Agb,
+}
+
+// The console to emulate and the boot ROM it runs first, None to start straight at 0x100
+#[derive(Debug, Clone, Default)]
+pub struct BootSetup {
+    pub model: Model,
+    pub boot_rom: Option<PathBuf>,
+}
+
+impl Model {
+    pub const ALL: [Model; 8] = [
+        Model::Dmg0,
+        Model::Dmg,
+        Model::Mgb,
+        Model::Sgb,
+        Model::Sgb2,
+        Model::Cgb0,
+        Model::Cgb,
+        Model::Agb,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Model::Dmg0 => "dmg0",
+            Model::Dmg => "dmg",
+            Model::Mgb => "mgb",
+            Model::Sgb => "sgb",
+            Model::Sgb2 => "sgb2",
+            Model::Cgb0 => "cgb0",
+            Model::Cgb => "cgb",
+            Model::Agb => "agb",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|model| model.name().eq_ignore_ascii_case(name))
+            .ok_or(format!("Unknown model {name}, expected one of dmg0, dmg, mgb, sgb, sgb2, cgb0, cgb, agb"))
+    }
+
+    // The files shipped in boot-roms/ are named after the model
+    pub fn boot_rom_path(&self, dir: &Path) -> PathBuf {
+        dir.join(format!("{}.bin", self.name()))
+    }
+
+    pub fn is_sgb(&self) -> bool {
+        matches!(self, Model::Sgb | Model::Sgb2)
+    }
+
+    pub fn is_cgb(&self) -> bool {
+        matches!(self, Model::Cgb0 | Model::Cgb | Model::Agb)
+    }
+
+    // A DMG cart on a CGB starts in CGB mode, the boot ROM (or its simulation) then switches to compatibility
+    pub fn hardware_mode(&self) -> HardwareMode {
+        match self {
+            Model::Dmg0 | Model::Dmg | Model::Mgb => HardwareMode::Dmg,
+            Model::Sgb | Model::Sgb2 => HardwareMode::Sgb,
+            Model::Cgb0 | Model::Cgb | Model::Agb => HardwareMode::Cgb,
+        }
+    }
+
+    /*
+        A, F, B, C, D, E, H, L after the boot ROM. On DMG and MGB, H and C are set when the header
+        checksum isn't 0. In compatibility mode B, H and L depend on the title, we keep the values
+        of an unknown title.
+    */
+    pub fn post_boot_registers(&self, mode: HardwareMode, header_checksum: u8) -> [u8; 8] {
+        let dmg_flags = if header_checksum == 0 { 0x80 } else { 0xB0 };
+        let compat = mode == HardwareMode::CgbCompat;
+
+        match self {
+            Model::Dmg0 => [0x01, 0x00, 0xFF, 0x13, 0x00, 0xC1, 0x84, 0x03],
+            Model::Dmg => [0x01, dmg_flags, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            Model::Mgb => [0xFF, dmg_flags, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            Model::Sgb => [0x01, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60],
+            Model::Sgb2 => [0xFF, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60],
+            Model::Cgb0 | Model::Cgb if compat => [0x11, 0x80, 0x00, 0x00, 0x00, 0x08, 0x00, 0x7C],
+            Model::Cgb0 | Model::Cgb => [0x11, 0x80, 0x00, 0x00, 0xFF, 0x56, 0x00, 0x0D],
+            // The AGB boot ROM ends with an extra INC B
+            Model::Agb if compat => [0x11, 0x00, 0x01, 0x00, 0x00, 0x08, 0x00, 0x7C],
+            Model::Agb => [0x11, 0x00, 0x01, 0x00, 0xFF, 0x56, 0x00, 0x0D],
+        }
+    }
+
+    /*
+        DIV, SC and NR52 after the boot ROM, the IO registers that differ between models. Pan Docs
+        lists DIV as unpredictable on SGB and CGB, the boot ROM takes a different time on each
+        cart, we start those at 0.
+    */
+    pub fn post_boot_io(&self) -> [(u16, u8); 3] {
+        let div = match self {
+            Model::Dmg0 => 0x18,
+            Model::Dmg | Model::Mgb => 0xAB,
+            Model::Sgb | Model::Sgb2 | Model::Cgb0 | Model::Cgb | Model::Agb => 0x00,
+        };
+        let sc = if self.is_cgb() { 0x7F } else { 0x7E };
+        let nr52 = if self.is_sgb() { 0xF0 } else { 0xF1 };
+
+        [(0xFF04, div), (0xFF02, sc), (0xFF26, nr52)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn models_parse_and_find_their_boot_rom() {
+        assert_eq!(Model::from_name("SGB2"), Ok(Model::Sgb2));
+        assert!(Model::from_name("gba").is_err());
+        assert_eq!(Model::Cgb0.boot_rom_path(Path::new("boot-roms")), PathBuf::from("boot-roms/cgb0.bin"));
+        assert_eq!(Model::Mgb.hardware_mode(), HardwareMode::Dmg);
+    }
+
+    #[test]
+    fn dmg_flags_follow_header_checksum() {
+        assert_eq!(Model::Dmg.post_boot_registers(HardwareMode::Dmg, 0x00)[1], 0x80);
+        assert_eq!(Model::Dmg.post_boot_registers(HardwareMode::Dmg, 0x3B)[1], 0xB0);
+        assert_eq!(Model::Cgb.post_boot_registers(HardwareMode::CgbCompat, 0x3B)[5], 0x08);
+    }
+
+    #[test]
+    fn post_boot_div_depends_on_model() {
+        assert_eq!(Model::Dmg0.post_boot_io()[0], (0xFF04, 0x18));
+        assert_eq!(Model::Mgb.post_boot_io()[0], (0xFF04, 0xAB));
+        assert_eq!(Model::Sgb2.post_boot_io()[2], (0xFF26, 0xF0));
+    }
+}
@@ -1,7 +1,7 @@
 #![allow(unused_variables)]
 #![allow(dead_code)]
 
-mod colors_palette;
+pub mod colors_palette;
 mod lcd_control;
 mod lcd_status;
 mod pixel;
@@ -9,6 +9,8 @@ mod pixel_fifo;
 mod obj_piso;
 mod pixel_fetcher;
 mod oam_fetcher;
+pub mod timeline;
+pub mod layers;
 
 use std::sync::Mutex;
 use std::sync::Arc;
@@ -16,6 +18,7 @@ use std::sync::Arc;
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use crate::mmu::cgb::HardwareMode;
 use crate::mmu::mbc::Mbc;
 use crate::mmu::MemoryRegion;
 use crate::mmu::Mmu;
@@ -30,6 +33,8 @@ use crate::ppu::pixel_fifo::PixelFifo;
 use crate::ppu::obj_piso::ObjPiso;
 use crate::ppu::pixel_fetcher::PixelFetcher;
 use crate::ppu::oam_fetcher::OamFetcher;
+use crate::ppu::timeline::Timeline;
+use crate::ppu::layers::{Layer, LayerToggles};
 
 pub const WIN_SIZE_X: usize = 160; // Window size in X direction
 pub const WIN_SIZE_Y: usize = 144; // Window size in Y direction
@@ -48,9 +53,7 @@ const WX_ADDR: u16 = 0xFF4B; // Window X Position
 const LCD_CONTROL_ADDR: u16 = 0xFF40; // LCDC Control
 
 const OAM_DOTS: u32 = 80; // always 80
-const PIXEL_TRANSFER_DOTS: u32 = 172; // can change between 172 and 289, to handle later
-const HBLANK_DOTS: u32 = 204; // can change between 87 and 204, to handle later
-const SCANLINE_DOTS: u32 = 456; // always 456
+const SCANLINE_DOTS: u32 = 456; // always 456, mode 3 ends when the 160th pixel is out and HBlank takes the rest
 
 pub struct Ppu<T: Mbc> {
     pub bus: Rc<RefCell<Mmu<T>>>,
@@ -78,7 +81,9 @@ pub struct Ppu<T: Mbc> {
     lcd_was_enabled: bool, // for the LCD on/off quirk. We need to detect if the ppu is on for the first time since it was off.
     is_first_scanline_after_lcd_on: bool, // for the LCD on/off quirk. If first scanline since the ppu is on, the cycle is shorter.
     stat_interrupt_line: bool,
-    stall_dots: u8, // to handle the sprite penalty in mode pixel transfer
+    waiting_for_sprite: bool, // a sprite is reached but the BG fetcher hasn't finished its tile yet
+    pub timeline: Timeline, // debugger only, records mode durations and LCD register writes
+    pub layers: LayerToggles, // debugger only, hides or tints layers without touching LCDC
 }
 
 impl<T: Mbc> Ppu<T> {
@@ -109,7 +114,9 @@ impl<T: Mbc> Ppu<T> {
             lcd_was_enabled: false,
             is_first_scanline_after_lcd_on: false,
             stat_interrupt_line: false,
-            stall_dots: 0,
+            waiting_for_sprite: false,
+            timeline: Timeline::default(),
+            layers: LayerToggles::default(),
         }
     }
 
@@ -235,7 +242,7 @@ impl<T: Mbc> Ppu<T> {
         let tile_number = self
             .bus
             .borrow_mut()
-            .read_byte(tilemap_base.start + offset);
+            .read_vram(0, tilemap_base.start + offset);
         match self.read_lcdc().bg_window_tile_data_area() {
             // Unsigned mode: simple multiplication
             lcd_control::TILE_DATA_1 => 0x8000 + (tile_number as u16) * 16,
@@ -250,44 +257,27 @@ impl<T: Mbc> Ppu<T> {
     }
 
 
-    fn oam_search(&mut self) {
-        // Select max 10 visible sprites on the scanline
-
-        let height:u8 = if self.read_lcdc().is_obj_size_8x16() {
-            16
-        } else {
-            8
-        };
-        let mmu = self.bus.borrow_mut();
-        let oam  = mmu.get_oam();
-        let mut i: usize = 0;
-        for (oam_index, sprite) in oam.sprites.iter().enumerate() {
-            if sprite.is_visible(self.ly, height) {
-
-                let mut s = *sprite;
-
-                s.oam_index = oam_index as u8;
-
+    fn apply_background_palette(&self, color_index: u8) -> Color {
+        let bus = self.bus.borrow();
+        let palette = bus.read_byte(BGP_ADDR);
 
-                self.visible_sprites[i] = Some(s);
+        let index = (palette >> (color_index * 2)) & 0b11;
 
-                i += 1;
-                if i >= 10 {
-                    break;
-                }
-            }
+        match bus.mode() {
+            HardwareMode::CgbCompat => Color::Rgb555(bus.bg_color(0, index)),
+            _ => Color::from_index(index),
         }
     }
 
-    fn apply_background_palette(&self, color_index: u8) -> Color {
-        let palette = self.bus.borrow_mut().read_byte(BGP_ADDR);
 
-        let index = (palette >> (color_index * 2)) & 0b11;
-
-        Color::from_index(index)
+    fn background_color_0(&self) -> Color {
+        let mode = self.bus.borrow().mode();
+        match mode {
+            HardwareMode::Cgb => Color::Rgb555(self.bus.borrow().bg_color(0, 0)),
+            HardwareMode::Dmg | HardwareMode::CgbCompat | HardwareMode::Sgb => self.apply_background_palette(0),
+        }
     }
 
-
     fn extract_attributes(&self, attributes: u8) -> (bool, bool, bool, bool) {
         (
             ((attributes >> 7) & 1) != 0,
@@ -353,8 +343,7 @@ impl<T: Mbc> Ppu<T> {
 
             let mut sprite = oam.sprites[self.oam_scan_index as usize];
 
-            if sprite.is_visible(self.ly, self.current_obj_height)
-                && self.visible_sprites_count < 10 {
+            if oam.selects(self.oam_scan_index, self.ly, self.current_obj_height, self.visible_sprites_count as usize) {
                 sprite.oam_index = self.oam_scan_index;
 
                 self.visible_sprites[self.visible_sprites_count as usize] = Some(sprite);
@@ -385,6 +374,11 @@ impl<T: Mbc> Ppu<T> {
     }
 
     fn handle_window_switch(&mut self, use_window: bool) {
+        // The window can only start once the first BG tile is in the FIFO
+        if self.bg_fifo.is_empty() {
+            return;
+        }
+
         // check if window is activated in the middle of scanline
         if !self.use_window && use_window {
             self.pixel_fetcher.reset_for_window();
@@ -401,7 +395,7 @@ impl<T: Mbc> Ppu<T> {
         if self.use_window && wx != self.wx_at_window_start
             && self.x + 7 >= wx as usize
             && !self.is_wx_glitch_happened {
-                let glitched_pixel = Pixel::new_bg(self.apply_background_palette(0),  0);
+                let glitched_pixel = Pixel::new_bg(self.background_color_0(),  0);
 
                 self.bg_fifo.push(glitched_pixel);
                 self.is_wx_glitch_happened = true;
@@ -417,35 +411,54 @@ impl<T: Mbc> Ppu<T> {
 
                 let bg_color_index: u8;
                 let bg_color: Color;
+                let bg_layer = if self.use_window { Layer::Window } else { Layer::Background };
+                let is_cgb = self.bus.borrow().mode() == HardwareMode::Cgb;
+                let bg_enabled = self.read_lcdc().is_bg_window_enabled();
 
-                // If BG is disabled, color 0 everywhere
-                if !self.read_lcdc().is_bg_window_enabled() {
+                // If BG is disabled, color 0 everywhere. On CGB, LCDC bit 0 only removes the BG priority
+                if (!bg_enabled && !is_cgb) || self.layers.is_hidden(bg_layer) {
                     bg_color_index = 0;
-                    bg_color = self.apply_background_palette(0);
+                    bg_color = self.background_color_0();
                 }
                 else {
                     bg_color_index = bg_pixel.get_color_index();
                     bg_color = *bg_pixel.get_color();
                 }
 
-                let obj_color_index = obj_pixel.get_color_index();
+                let obj_color_index = if self.layers.is_sprite_hidden(obj_pixel.get_oam_index()) {
+                    0
+                } else {
+                    obj_pixel.get_color_index()
+                };
 
-                let final_color = if obj_color_index == 0 {
-                    bg_color
+                let (final_layer, final_color) = if obj_color_index == 0 {
+                    (bg_layer, bg_color)
                 } else {
-                    let priority = obj_pixel.get_priority();
+                    let priority = if is_cgb {
+                        bg_enabled && (obj_pixel.get_priority() || bg_pixel.get_priority())
+                    } else {
+                        obj_pixel.get_priority()
+                    };
 
                     if priority && bg_color_index != 0 {
-                        bg_color
+                        (bg_layer, bg_color)
                     } else {
-                        *obj_pixel.get_color()
+                        (Layer::Sprites, *obj_pixel.get_color())
                     }
                 };
 
                 let ly = self.ly as usize;
 
-                let offset = (ly * WIN_SIZE_X + self.x) * 3; // * 3 for each pixels (3 bytes (RGB))
-                self.set_pixel_color(frame, offset, final_color);
+                // The SGB colors the final shade with the palette of its 8x8 cell
+                let final_color = match self.bus.borrow().mode() {
+                    HardwareMode::Sgb => self.bus.borrow().sgb_color(self.x, ly, final_color.to_index()).map(Color::Rgb555),
+                    _ => Some(final_color),
+                };
+
+                if let Some(final_color) = final_color {
+                    let offset = (ly * WIN_SIZE_X + self.x) * 3; // * 3 for each pixels (3 bytes (RGB))
+                    frame[offset..offset + 3].copy_from_slice(&self.layers.rgb(final_layer, final_color));
+                }
 
                 self.x += 1;
             }
@@ -468,76 +481,47 @@ impl<T: Mbc> Ppu<T> {
     }
 
 
-    fn step_oam_fetcher(&mut self) {
-        let height:u8 = if self.read_lcdc().is_obj_size_8x16() {
-            16
-        } else {
-            8
-        };
-
-        if self.fetching_sprite {
-            if let Some(index) = self.current_sprite_to_fetch {
-                if let Some(sprite) = self.visible_sprites[index] {
-                    let lcdc = self.read_lcdc();
-
-                    self.fetching_sprite = !self.oam_fetcher.tick(
-                        &self.bus,
-                        &sprite,
-                        &mut self.obj_piso,
-                        self.ly,
-                        &lcdc,
-                        height,
-                        self.x,
-                    );
-
-                    if !self.fetching_sprite {
-                        self.visible_sprites[index] = None;
-
-                        let remaining_pixels = self.bg_fifo.len() as u8;
-                        if remaining_pixels < 6 {
-                            self.stall_dots = 6 - remaining_pixels;
-                        }
-                    }
-                }
-            };
+    // Sprites are sorted by X, the first one not drawn yet is the next to fetch
+    fn pending_sprite(&self) -> Option<usize> {
+        if !self.read_lcdc().is_obj_enabled() {
+            return None;
         }
-        else {
-            if !self.read_lcdc().is_obj_enabled() { return; }
-
-            let lcdc = self.read_lcdc();
 
-            for (index, sprite_opt) in self.visible_sprites.iter_mut().enumerate() {
-                if let Some(sprite) = sprite_opt {
-
-                    if sprite.x as usize <= self.x + 8 {
-                        let spritex = sprite.x;
-                        let selfx = self.x;
-
-                        self.current_sprite_to_fetch = Some(index);
-                        self.pixel_fetcher.reset_to_state_1();
-
-                        self.fetching_sprite = !self.oam_fetcher.tick(
-                            &self.bus,
-                            sprite,
-                            &mut self.obj_piso,
-                            self.ly,
-                            &lcdc,
-                            height,
-                            self.x,
-                        );
-
-                        if !self.fetching_sprite {
-                            *sprite_opt = None;
-                        }
+        // Sprites hidden on the left are fetched before the SCX pixels are thrown away
+        self.visible_sprites.iter().position(|sprite| {
+            sprite.is_some_and(|sprite| {
+                sprite.x as usize <= self.x + 8 && (self.pixels_to_discard == 0 || sprite.x < 8)
+            })
+        })
+    }
 
-                        break;
-                    }
-                }
-            }
+    fn step_oam_fetcher(&mut self) {
+        let Some(index) = self.current_sprite_to_fetch else { return };
+        let Some(sprite) = self.visible_sprites[index] else { return };
+
+        let lcdc = self.read_lcdc();
+        let height: u8 = if lcdc.is_obj_size_8x16() { 16 } else { 8 };
+
+        self.fetching_sprite = !self.oam_fetcher.tick(
+            &self.bus,
+            &sprite,
+            &mut self.obj_piso,
+            self.ly,
+            &lcdc,
+            height,
+            self.x,
+        );
+
+        if !self.fetching_sprite {
+            self.visible_sprites[index] = None;
         }
     }
 
-
+    /*
+        Mode 3 lasts as long as the FIFO takes to put 160 pixels out: 172 dots, plus the SCX % 8
+        pixels thrown away, 6 dots to restart the fetcher on the window, and for each sprite the wait
+        for the BG fetcher to finish its tile then 6 dots of sprite fetch.
+    */
     fn mode_pixel_transfer(&mut self, image: &mut Arc<Mutex<Vec<u8>>>) -> bool {
         if self.ly < WIN_SIZE_Y as u8 {
             let wx = self.read_wx();
@@ -546,22 +530,33 @@ impl<T: Mbc> Ppu<T> {
                 && self.wy_equal_ly_condition_met
                 && (self.x + 7 >= wx as usize);
 
-            self.step_oam_fetcher();
+            let pending_sprite = if self.fetching_sprite { None } else { self.pending_sprite() };
+            if let Some(index) = pending_sprite
+                && self.pixel_fetcher.has_tile_ready()
+                && !self.bg_fifo.is_empty()
+            {
+                self.current_sprite_to_fetch = Some(index);
+                self.oam_fetcher = OamFetcher::default();
+                self.fetching_sprite = true;
+            }
+            self.waiting_for_sprite = !self.fetching_sprite && pending_sprite.is_some();
 
-            if !self.fetching_sprite {
-                self.step_pixel_fetcher(use_window);
-                if self.stall_dots > 0 {
-                    self.stall_dots -= 1;
-                } else {
+            if self.fetching_sprite {
+                self.step_oam_fetcher();
+            } else {
+                // Pixels stay in the FIFO while a sprite waits for the BG fetcher
+                if !self.waiting_for_sprite {
                     let mut frame = image.lock().unwrap();
                     self.handle_window_switch(use_window);
                     self.push_pixel_to_screen(&mut frame, use_window);
                 }
+                self.step_pixel_fetcher(self.use_window);
             }
         }
 
         if self.x == 160 {
             self.update_ppu_mode(PpuMode::HBlank);
+            self.bus.borrow_mut().start_hdma_block();
         }
 
         false
@@ -573,10 +568,13 @@ impl<T: Mbc> Ppu<T> {
         self.obj_piso.reset();
         self.pixel_fetcher.reset_for_scanline();       
         self.pixels_to_discard = self.read_scx() % 8;
+        self.timeline.set_scx_discard(self.internal_ly, self.pixels_to_discard);
         self.use_window = false;
         self.is_wx_glitch_happened = false;
         self.is_first_scanline_after_lcd_on = false;
-        self.stall_dots = 0;
+        self.fetching_sprite = false;
+        self.current_sprite_to_fetch = None;
+        self.waiting_for_sprite = false;
     }
 
     fn advance_to_next_scanline(&mut self) {
@@ -638,6 +636,7 @@ impl<T: Mbc> Ppu<T> {
         self.wly = 0;
         self.reset_for_new_scanline();
         self.wy_equal_ly_condition_met = false;
+        self.timeline.end_frame();
 
         self.update_ppu_mode(PpuMode::OamSearch);
     }
@@ -700,6 +699,10 @@ impl<T: Mbc> Ppu<T> {
 
         if wy == self.ly { self.wy_equal_ly_condition_met = true; }
 
+        if self.timeline.is_enabled() {
+            self.record_timeline_dot();
+        }
+
         let was_updated = match self.lcd_status.get_ppu_mode() {
             PpuMode::OamSearch => self.mode_oam_search(),
             PpuMode::PixelTransfer => self.mode_pixel_transfer(image),
@@ -712,6 +715,22 @@ impl<T: Mbc> Ppu<T> {
         was_updated
     }
 
+    fn record_timeline_dot(&mut self) {
+        let writes = self.bus.borrow_mut().take_lcd_writes();
+        for (address, value) in writes {
+            self.timeline.record_write(self.internal_ly, self.dots as u16, address, value);
+        }
+
+        let mode = self.lcd_status.get_ppu_mode();
+        let is_sprite_dot = mode == PpuMode::PixelTransfer && (self.fetching_sprite || self.waiting_for_sprite);
+        self.timeline.record_dot(self.internal_ly, mode, is_sprite_dot);
+    }
+
+    pub fn set_timeline_enabled(&mut self, enabled: bool) {
+        self.timeline.set_enabled(enabled);
+        self.bus.borrow_mut().record_lcd_writes(enabled);
+    }
+
     fn check_lyc_equals_ly(&mut self) {
         /*
             LYC == LY is an hardware condition:
@@ -734,7 +753,12 @@ impl<T: Mbc> Ppu<T> {
     }
 
     fn evaluate_stat_interrupt(&mut self) {
-        let current_line = self.lcd_status.stat_interrupt_line();
+        // Entering VBlank also raises the line through the mode 2 source, for a single dot
+        let vblank_oam_quirk = self.lcd_status.get_ppu_mode() == PpuMode::VBlank
+            && self.internal_ly == WIN_SIZE_Y as u8
+            && self.dots == 0
+            && self.lcd_status.is_mode_2_int_selected();
+        let current_line = self.lcd_status.stat_interrupt_line() || vblank_oam_quirk;
 
         if !self.stat_interrupt_line && current_line {
             self.bus.borrow_mut().interrupts_request(Interrupt::LcdStat);
@@ -795,3 +819,116 @@ impl<T: Mbc> Ppu<T> {
         bus.set_stat_byte_from_ppu(self.lcd_status.struct_to_byte());
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mmu::mbc::RomOnly;
+
+    // Dots STAT reads mode 3 on line 2, `setup` runs before the LCD is turned on
+    fn mode_3_dots(setup: impl Fn(&mut Mmu<RomOnly>)) -> u32 {
+        let bus = Rc::new(RefCell::new(Mmu::<RomOnly>::new(&[]).unwrap()));
+        {
+            let mut mmu = bus.borrow_mut();
+            mmu.write_byte(SCX_ADDR, 0);
+            mmu.write_byte(SCY_ADDR, 0);
+            mmu.write_byte(LCD_CONTROL_ADDR, 0x03);
+            setup(&mut mmu);
+            let lcdc = mmu.read_byte(LCD_CONTROL_ADDR);
+            mmu.write_byte(LCD_CONTROL_ADDR, lcdc | 0x80);
+        }
+
+        let mut ppu = Ppu::new(bus);
+        let mut image = Arc::new(Mutex::new(vec![0; WIN_SIZE_X * WIN_SIZE_Y * 3]));
+        while ppu.ly != 2 {
+            ppu.tick(&mut image);
+        }
+
+        let mut dots = 0;
+        while ppu.ly == 2 {
+            ppu.tick(&mut image);
+            if ppu.lcd_status.get_ppu_mode() == PpuMode::PixelTransfer {
+                dots += 1;
+            }
+        }
+        dots
+    }
+
+    fn sprites_at(xs: &[u8]) -> impl Fn(&mut Mmu<RomOnly>) {
+        move |mmu| {
+            for (i, x) in xs.iter().enumerate() {
+                mmu.write_byte(0xFE00 + i as u16 * 4, 16 + 2);
+                mmu.write_byte(0xFE01 + i as u16 * 4, *x);
+            }
+        }
+    }
+
+    #[test]
+    fn mode_3_grows_with_scx_fine_scroll() {
+        for scx in 0..16u8 {
+            assert_eq!(mode_3_dots(|mmu| mmu.write_byte(SCX_ADDR, scx)), 172 + (scx % 8) as u32);
+        }
+    }
+
+    #[test]
+    fn window_start_restarts_the_fetcher() {
+        for wx in [7, 8, 87, 166] {
+            let dots = mode_3_dots(|mmu| {
+                mmu.write_byte(LCD_CONTROL_ADDR, 0x23);
+                mmu.write_byte(WY_ADDR, 0);
+                mmu.write_byte(WX_ADDR, wx);
+            });
+            assert_eq!(dots, 178, "WX={wx}");
+        }
+    }
+
+    #[test]
+    fn sprites_wait_for_the_bg_fetcher() {
+        // 6 dots per sprite, plus what the BG fetcher needs to finish the tile under its left edge
+        assert_eq!(mode_3_dots(sprites_at(&[0])), 172 + 11);
+        assert_eq!(mode_3_dots(sprites_at(&[8])), 172 + 11);
+        assert_eq!(mode_3_dots(sprites_at(&[9])), 172 + 10);
+        assert_eq!(mode_3_dots(sprites_at(&[12])), 172 + 7);
+        assert_eq!(mode_3_dots(sprites_at(&[15])), 172 + 6);
+        assert_eq!(mode_3_dots(sprites_at(&[167])), 172 + 6);
+
+        // Only the first sprite of a tile waits
+        assert_eq!(mode_3_dots(sprites_at(&[8; 10])), 172 + 11 + 9 * 6);
+        assert_eq!(mode_3_dots(sprites_at(&[8, 16])), 172 + 11 + 11);
+
+        // SCX moves the tile boundaries, but a sprite at X=0 always costs 11 dots
+        let scrolled = |xs: &'static [u8]| move |mmu: &mut Mmu<RomOnly>| {
+            mmu.write_byte(SCX_ADDR, 3);
+            sprites_at(xs)(mmu);
+        };
+        assert_eq!(mode_3_dots(scrolled(&[8])), 172 + 3 + 8);
+        assert_eq!(mode_3_dots(scrolled(&[0])), 172 + 3 + 11);
+
+        // Hidden sprites don't cost anything
+        let hidden = |mmu: &mut Mmu<RomOnly>| {
+            mmu.write_byte(LCD_CONTROL_ADDR, 0x01);
+            sprites_at(&[8])(mmu);
+        };
+        assert_eq!(mode_3_dots(hidden), 172);
+    }
+
+    #[test]
+    fn vblank_start_fires_the_mode_2_interrupt() {
+        let bus = Rc::new(RefCell::new(Mmu::<RomOnly>::new(&[]).unwrap()));
+        bus.borrow_mut().write_byte(STAT_ADDR, 0x20);
+        bus.borrow_mut().write_byte(LCD_CONTROL_ADDR, 0x91);
+
+        let mut ppu = Ppu::new(bus.clone());
+        let mut image = Arc::new(Mutex::new(vec![0; WIN_SIZE_X * WIN_SIZE_Y * 3]));
+        while ppu.ly != 143 || ppu.lcd_status.get_ppu_mode() != PpuMode::HBlank {
+            ppu.tick(&mut image);
+        }
+
+        bus.borrow_mut().write_byte(0xFF0F, 0x00);
+        while ppu.ly != 144 {
+            ppu.tick(&mut image);
+        }
+        assert_eq!(bus.borrow().read_byte(0xFF0F) & 0x03, 0x03);
+    }
+}
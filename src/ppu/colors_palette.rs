@@ -7,6 +7,7 @@ pub enum Color {
     LightGray,
     DarkGray,
     Black,
+    Rgb555(u16), // CGB color from the palette RAM
 }
 
 impl Color {
@@ -16,6 +17,14 @@ impl Color {
             Color::LightGray => [192, 192, 192],
             Color::DarkGray => [96, 96, 96],
             Color::Black => [0, 0, 0],
+            Color::Rgb555(color) => {
+                // 5 bits per channel, the top bits are repeated to reach full white
+                let channel = |shift: u16| {
+                    let value = ((color >> shift) & 0x1F) as u8;
+                    (value << 3) | (value >> 2)
+                };
+                [channel(0), channel(5), channel(10)]
+            }
         }
     }
 
@@ -45,6 +54,7 @@ impl Color {
             Color::LightGray => 1,
             Color::DarkGray => 2,
             Color::Black => 3,
+            Color::Rgb555(_) => unreachable!("CGB colors have no DMG shade"),
         }
     }
 
@@ -0,0 +1,90 @@
+use crate::ppu::colors_palette::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layer {
+    Background,
+    Window,
+    Sprites,
+}
+
+/*
+    Debug switches applied when a pixel reaches the screen, LCDC and the rest of the emulated state are left untouched.
+    A hidden background or window is drawn with color 0, like when LCDC bit 0 is off.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LayerToggles {
+    pub hide_background: bool,
+    pub hide_window: bool,
+    pub hide_sprites: bool,
+    pub hidden_sprites: u64, // one bit per OAM entry
+    pub false_colors: bool,
+}
+
+impl LayerToggles {
+    pub fn is_hidden(&self, layer: Layer) -> bool {
+        match layer {
+            Layer::Background => self.hide_background,
+            Layer::Window => self.hide_window,
+            Layer::Sprites => self.hide_sprites,
+        }
+    }
+
+    pub fn is_sprite_hidden(&self, oam_index: u8) -> bool {
+        self.hide_sprites || (oam_index < 64 && self.hidden_sprites & (1 << oam_index) != 0)
+    }
+
+    pub fn set_sprite_hidden(&mut self, oam_index: u8, hidden: bool) {
+        if hidden {
+            self.hidden_sprites |= 1 << oam_index;
+        } else {
+            self.hidden_sprites &= !(1 << oam_index);
+        }
+    }
+
+    pub fn rgb(&self, layer: Layer, color: Color) -> [u8; 3] {
+        if !self.false_colors {
+            return color.to_rgb();
+        }
+
+        // keep the shade readable while tinting it with the color of the layer
+        let tint: [u32; 3] = match layer {
+            Layer::Background => [255, 90, 90],
+            Layer::Window => [90, 255, 90],
+            Layer::Sprites => [90, 140, 255],
+        };
+        let light = color.to_rgb().iter().map(|channel| *channel as u32).sum::<u32>() / 3 + 64;
+        tint.map(|channel| (channel * light / 319) as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sprites_can_be_hidden_one_by_one() {
+        let mut toggles = LayerToggles::default();
+        toggles.set_sprite_hidden(39, true);
+
+        assert!(toggles.is_sprite_hidden(39));
+        assert!(!toggles.is_sprite_hidden(0));
+
+        toggles.set_sprite_hidden(39, false);
+        assert!(!toggles.is_sprite_hidden(39));
+
+        toggles.hide_sprites = true;
+        assert!(toggles.is_sprite_hidden(0));
+    }
+
+    #[test]
+    fn false_colors_tint_each_layer() {
+        let mut toggles = LayerToggles::default();
+        assert_eq!(toggles.rgb(Layer::Window, Color::White), Color::White.to_rgb());
+
+        toggles.false_colors = true;
+        assert_eq!(toggles.rgb(Layer::Background, Color::White), [255, 90, 90]);
+        assert_eq!(toggles.rgb(Layer::Sprites, Color::White), [90, 140, 255]);
+        let dark = toggles.rgb(Layer::Window, Color::Black);
+        assert!(dark[1] > dark[0] && dark[1] < 90);
+    }
+}
@@ -94,6 +94,10 @@ impl LcdStatus {
         // Bits 2, 1, 0 are read-only
     }
 
+    pub fn is_mode_2_int_selected(&self) -> bool {
+        self.mode_2_int_select
+    }
+
     pub fn stat_interrupt_line(&self) -> bool {
         let mut line = false;
         if self.lyc_int_select && self.lyc_equals_ly {
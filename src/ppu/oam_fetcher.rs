@@ -2,11 +2,13 @@
 #![allow(dead_code)]
 
 use crate::mmu::Mmu;
+use crate::mmu::cgb::HardwareMode;
 use crate::mmu::mbc::Mbc;
 use crate::mmu::MemoryRegion;
 use crate::mmu::oam::Sprite;
 use crate::ppu::lcd_control::LcdControl;
-use crate::ppu::obj_piso::ObjPiso;
+use crate::ppu::colors_palette::Color;
+use crate::ppu::obj_piso::{ObjPiso, ObjRow};
 
 use std::{cell::RefCell, rc::Rc};
 
@@ -15,18 +17,19 @@ const OBP1_ADDR: u16 = 0xFF49; // Object Palette 1
 const VRAM: MemoryRegion = MemoryRegion::Vram; // Start of VRAM
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[allow(clippy::enum_variant_names)] // named like the BG fetcher steps
 pub enum FetcherState {
     #[default]
     GetTileId = 0,
     GetLowData = 1,
     GetHighData = 2,
-    PushPixel = 3,
 }
 
 #[derive(Default)]
 pub struct OamFetcher {
     fetcher_state: FetcherState,
     tile_id: u8,
+    attributes: u8,
     tile_data_low: u8,
     tile_data_high: u8,
     dot_counter: u32,
@@ -51,13 +54,9 @@ impl OamFetcher {
                     
                     return false;
                 },
+                // The sprite is merged as soon as its data is there, a fetch takes 6 dots
                 FetcherState::GetHighData => {
                     self.tile_data_high = self.get_tile_data_high(bus);
-                    self.fetcher_state = FetcherState::PushPixel;
-
-                    return false;
-                },
-                FetcherState::PushPixel => {
                     self.push_pixel(bus, piso, sprite, scanline_x);
                     self.fetcher_state = FetcherState::GetTileId;
 
@@ -80,17 +79,25 @@ impl OamFetcher {
         let tile_index = if height == 16 && actual_sprite_line >= 8 { tile_always_pair + 1 } else { tile_always_pair }; // offset if 8x16 because of end of tile index
 
         self.actual_sprite_line = actual_sprite_line;
+        self.attributes = sprite.attributes;
         tile_index
     }
 
+    // OAM attribute bit 3 picks the VRAM bank on CGB, it's unused on DMG
+    fn tile_bank<T: Mbc>(&self, bus: &Rc<RefCell<Mmu<T>>>) -> usize {
+        match bus.borrow().mode() {
+            HardwareMode::Cgb => ((self.attributes >> 3) & 1) as usize,
+            HardwareMode::Dmg | HardwareMode::CgbCompat | HardwareMode::Sgb => 0,
+        }
+    }
+
     fn get_tile_data_low<T: Mbc>(&mut self, bus: &Rc<RefCell<Mmu<T>>>) -> u8 {
         let tile_address = VRAM.to_address()
             + (self.tile_id as u16 * 16)
             + (self.actual_sprite_line % 8 * 2) as u16;
 
-        let data = bus.borrow_mut().read_byte(tile_address as u16);
-
-        data
+        let bank = self.tile_bank(bus);
+        bus.borrow().read_vram(bank, tile_address)
     }
 
     fn get_tile_data_high<T: Mbc>(&mut self, bus: &Rc<RefCell<Mmu<T>>>) -> u8 {
@@ -98,9 +105,8 @@ impl OamFetcher {
             + (self.tile_id as u16 * 16)
             + (self.actual_sprite_line % 8 * 2) as u16;
 
-        let data = bus.borrow_mut().read_byte(tile_address as u16 + 1);
-
-        data
+        let bank = self.tile_bank(bus);
+        bus.borrow().read_vram(bank, tile_address + 1)
     }
 
     fn extract_attributes(&self, attributes: u8) -> (bool, bool, bool, bool) {
@@ -114,10 +120,38 @@ impl OamFetcher {
 
     fn push_pixel<T: Mbc>(&mut self, bus: &Rc<RefCell<Mmu<T>>>, piso: &mut ObjPiso, sprite: &Sprite, scanline_x: usize) {
         let (priority, _, x_flip, palette_attribute) = self.extract_attributes(sprite.attributes);
+        let row = ObjRow {
+            tile_data_low: self.tile_data_low,
+            tile_data_high: self.tile_data_high,
+            sprite_x: sprite.x,
+            x_flip,
+            oam_index: sprite.oam_index,
+            priority,
+        };
+
+        if bus.borrow().mode() == HardwareMode::Cgb {
+            let bus = bus.borrow();
+            let colors = [0, 1, 2, 3].map(|color_index| Color::Rgb555(bus.obj_color(sprite.attributes & 0b111, color_index)));
+
+            piso.set_oam_index_priority(true);
+            piso.merge_colors(row, colors, scanline_x);
+            return;
+        }
 
         let palette_addr = if palette_attribute { OBP1_ADDR } else { OBP0_ADDR };
         let palette = bus.borrow_mut().read_byte(palette_addr);
 
+        // DMG cart on CGB: the OBP shade picks a color in OBJ palette 0 or 1
+        if bus.borrow().mode() == HardwareMode::CgbCompat {
+            let bus = bus.borrow();
+            let colors = [0, 1, 2, 3].map(|color_index| {
+                Color::Rgb555(bus.obj_color(palette_attribute as u8, (palette >> (color_index * 2)) & 0b11))
+            });
+
+            piso.merge_colors(row, colors, scanline_x);
+            return;
+        }
+
         piso.merge(self.tile_data_low, self.tile_data_high, sprite.x, x_flip, palette, sprite.oam_index, priority, scanline_x);
     }
 }
@@ -157,15 +191,9 @@ mod tests {
         assert_eq!(fetcher.tick(&bus, &sprite, &mut piso, 0, &lcd, 8, 0), false);
         assert_eq!(fetcher.fetcher_state, FetcherState::GetHighData);
 
-        // tick 6 -> GetHighData
-        fetcher.tick(&bus, &sprite, &mut piso, 0, &lcd, 8, 0);
-        assert_eq!(fetcher.fetcher_state, FetcherState::GetHighData);
+        // tick 6 -> GetHighData, the sprite is merged → return true
         assert_eq!(fetcher.tick(&bus, &sprite, &mut piso, 0, &lcd, 8, 0), false);
-        assert_eq!(fetcher.fetcher_state, FetcherState::PushPixel);
-
-        // tick 8 -> PushPixel → return false
-        assert_eq!(fetcher.tick(&bus, &sprite, &mut piso, 0, &lcd, 8, 0), false);
-        assert_eq!(fetcher.fetcher_state, FetcherState::PushPixel);
+        assert_eq!(fetcher.fetcher_state, FetcherState::GetHighData);
         assert_eq!(fetcher.tick(&bus, &sprite, &mut piso, 0, &lcd, 8, 0), true);
         assert_eq!(fetcher.fetcher_state, FetcherState::GetTileId);
     }
@@ -7,15 +7,28 @@
 use crate::ppu::pixel::Pixel;
 use crate::ppu::colors_palette::Color;
 
+// One sprite row as fetched, ready to be merged into the PISO
+#[derive(Clone, Copy)]
+pub struct ObjRow {
+    pub tile_data_low: u8,
+    pub tile_data_high: u8,
+    pub sprite_x: u8,
+    pub x_flip: bool,
+    pub oam_index: u8,
+    pub priority: bool,
+}
+
 #[derive(Default)]
 pub struct ObjPiso {
     pixels: [Pixel; 8],
+    oam_index_priority: bool, // CGB: overlapping sprites are ordered by OAM index instead of X
 }
 
 impl ObjPiso {
     pub fn new() -> Self {
         ObjPiso {
             pixels: [Pixel::default(); 8],
+            oam_index_priority: false,
         }
     }
 
@@ -30,11 +43,27 @@ impl ObjPiso {
         priority: bool,
         scanline_x: usize,
     ) {
+        let colors = [0, 1, 2, 3].map(|color_index| Color::from_index((palette >> (color_index * 2)) & 0b11));
+        let row = ObjRow { tile_data_low, tile_data_high, sprite_x, x_flip, oam_index, priority };
+
+        self.merge_colors(row, colors, scanline_x);
+    }
+
+    pub fn set_oam_index_priority(&mut self, enabled: bool) {
+        self.oam_index_priority = enabled;
+    }
+
+    /*
+        Same as merge with the palette already resolved.
+        On DMG the first sprite fetched (smallest X) keeps the pixel, on CGB the smallest OAM index wins.
+    */
+    pub fn merge_colors(&mut self, row: ObjRow, colors: [Color; 4], scanline_x: usize) {
+        let ObjRow { tile_data_low, tile_data_high, sprite_x, x_flip, oam_index, priority } = row;
         for i in 0..8 {
             let pos = (sprite_x as i16 + i as i16 - 8) - scanline_x as i16;
 
             // Check if the pixel is outside the fifo
-            if pos < 0 || pos >= 8 {
+            if !(0..8).contains(&pos) {
                 continue;
             }
 
@@ -60,11 +89,10 @@ impl ObjPiso {
 
             // The current fifo index is set to the new pixel if the current pixel
             // is transparent
-            if current_pixel_color_index == 0 {
-                let color_pixel = (palette >> (color_index * 2)) & 0b11;
-
+            let wins_by_index = self.oam_index_priority && oam_index < current_pixel.get_oam_index();
+            if current_pixel_color_index == 0 || wins_by_index {
                 self.pixels[pos as usize] = Pixel::new_obj(
-                    Color::from_index(color_pixel),
+                    colors[color_index as usize],
                     color_index,
                     priority,
                     oam_index
@@ -192,6 +220,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn merge_colors_gives_cgb_priority_to_the_smallest_oam_index() {
+        let mut piso = ObjPiso::new();
+        piso.set_oam_index_priority(true);
+        let colors = [Color::Rgb555(0), Color::Rgb555(0x001F), Color::Rgb555(0x03E0), Color::Rgb555(0x7C00)];
+
+        let row = |tile_data_high, oam_index| ObjRow {
+            tile_data_low: 0b1000_0000,
+            tile_data_high,
+            sprite_x: 8,
+            x_flip: false,
+            oam_index,
+            priority: false,
+        };
+
+        piso.merge_colors(row(0, 5), colors, 0);
+        piso.merge_colors(row(0b1000_0000, 2), colors, 0);
+        assert_eq!(piso.pixels[0].get_oam_index(), 2);
+        assert_eq!(piso.pixels[0].get_color().to_rgb(), [0, 0, 255]);
+
+        piso.merge_colors(row(0, 9), colors, 0);
+        assert_eq!(piso.pixels[0].get_oam_index(), 2);
+    }
+
     #[test]
     fn shift_out_shifts_correctly() {
         let mut piso = ObjPiso::new();
@@ -29,6 +29,17 @@ impl Pixel {
         }
     }
 
+    // CGB background pixel, priority is bit 7 of the tile attributes
+    pub fn new_cgb_bg(color: Color, color_index: u8, priority: bool) -> Self {
+        Pixel {
+            color,
+            is_sprite: false,
+            color_index,
+            priority,
+            oam_index: u8::MAX,
+        }
+    }
+
     pub fn new_obj(color: Color, color_index: u8, priority: bool, oam_index: u8) -> Self {
         let is_sprite = true;
 
@@ -2,6 +2,7 @@
 #![allow(dead_code)]
 
 use crate::mmu::Mmu;
+use crate::mmu::cgb::HardwareMode;
 use crate::mmu::mbc::Mbc;
 use crate::ppu::lcd_control::LcdControl;
 use crate::ppu::pixel::Pixel;
@@ -28,6 +29,7 @@ pub enum FetcherState {
 pub struct PixelFetcher {
     fetcher_state: FetcherState,
     tile_id: u8,
+    attributes: u8, // CGB tile attributes from VRAM bank 1, always 0 on DMG
     tile_data_low: u8,
     tile_data_high: u8,
     fetcher_x: u8,
@@ -39,11 +41,11 @@ impl PixelFetcher {
     pub fn tick<T: Mbc>(&mut self, bus: &Rc<RefCell<Mmu<T>>>, fifo: &PixelFifo, ly: u8, scx: u8, scy: u8, wly: u8, lcd_control: &LcdControl, use_window: bool) -> Option<[Pixel; 8]> {
         self.dot_counter = self.dot_counter.wrapping_add(1);
 
-        if self.fetcher_state == FetcherState::PushPixel && fifo.is_empty() {
+        if self.has_tile_ready() && fifo.is_empty() {
             let tile: Option<[Pixel; 8]> = self.push_pixel(bus);
 
             self.fetcher_x += 1;
-            self.fetcher_state = FetcherState::GetTileId;
+            self.start_next_fetch();
 
             tile
         } else if self.dot_counter % 2 == 0 {
@@ -68,7 +70,7 @@ impl PixelFetcher {
                             let tile: Option<[Pixel; 8]> = self.push_pixel(bus);
 
                             self.fetcher_x += 1;
-                            self.fetcher_state = FetcherState::GetTileId;
+                            self.start_next_fetch();
 
                             return tile;
                         } else {
@@ -92,6 +94,17 @@ impl PixelFetcher {
         }
     }
 
+    // The next tile id is read on the dot after the push, whatever the dot parity
+    fn start_next_fetch(&mut self) {
+        self.fetcher_state = FetcherState::GetTileId;
+        self.dot_counter = 1;
+    }
+
+    // A tile is fetched and waits for the FIFO to be empty
+    pub fn has_tile_ready(&self) -> bool {
+        matches!(self.fetcher_state, FetcherState::Sleep | FetcherState::PushPixel)
+    }
+
     fn reset_internal(&mut self, first_fetch_done: bool) {
         self.fetcher_state = FetcherState::GetTileId;
         self.fetcher_x = 0;
@@ -100,16 +113,14 @@ impl PixelFetcher {
 
     pub fn reset_for_scanline(&mut self) {
         self.reset_internal(false);
+        self.dot_counter = 0;
     } 
 
     pub fn reset_for_window(&mut self) {
         self.reset_internal(true);
+        self.dot_counter = 0;
     } 
 
-    pub fn reset_to_state_1(&mut self) {
-        self.fetcher_state = FetcherState::GetTileId;
-    }
-
     fn get_tile_id<T: Mbc>(&mut self, bus: &Rc<RefCell<Mmu<T>>>, ly: u8, scx: u8, scy: u8, wly: u8, lcd_control: &LcdControl, use_window: bool) -> u8 {
         let tilemap_base: std::ops::Range<u16> = if use_window {
             lcd_control.window_tile_map_area()
@@ -132,13 +143,25 @@ impl PixelFetcher {
 
         let offset = (y * 32 + x) as u16;
 
-        let tile_number = bus
-            .borrow_mut()
-            .read_byte(tilemap_base.start + offset);
+        let bus = bus.borrow();
+        let tile_number = bus.read_vram(0, tilemap_base.start + offset);
+        self.attributes = match bus.mode() {
+            HardwareMode::Cgb => bus.read_vram(1, tilemap_base.start + offset),
+            HardwareMode::Dmg | HardwareMode::CgbCompat | HardwareMode::Sgb => 0,
+        };
 
         tile_number
     }
 
+    fn tile_bank(&self) -> usize {
+        ((self.attributes >> 3) & 1) as usize
+    }
+
+    fn tile_row(&self, y: usize) -> usize {
+        let y_flip = self.attributes & 0x40 != 0;
+        if y_flip { 7 - y % 8 } else { y % 8 }
+    }
+
     fn get_tile_data_low<T: Mbc>(&mut self, bus: &Rc<RefCell<Mmu<T>>>, ly: u8, scy: u8, wly: u8, lcd_control: &LcdControl, use_window: bool) -> u8 {
         let y = if use_window {
             wly as usize
@@ -146,14 +169,14 @@ impl PixelFetcher {
             ly as usize + scy as usize
         };
 
-        let correct_byte = (y % 8) * 2;
+        let correct_byte = self.tile_row(y) * 2;
 
         if lcd_control.bg_window_tile_data_area().start == TILE_DATA_1_START {
             let tilemap_base = TILE_DATA_1_START + (self.tile_id as u16) * 16;
 
             let tile_low = bus
                 .borrow_mut()
-                .read_byte(tilemap_base + correct_byte as u16);
+                .read_vram(self.tile_bank(), tilemap_base + correct_byte as u16);
 
             tile_low
             
@@ -164,7 +187,7 @@ impl PixelFetcher {
 
             let tile_low = bus
                 .borrow_mut()
-                .read_byte(tilemap_base + correct_byte as u16);
+                .read_vram(self.tile_bank(), tilemap_base + correct_byte as u16);
 
             tile_low
         } else {
@@ -180,12 +203,12 @@ impl PixelFetcher {
             ly as usize + scy as usize
         };
 
-        let correct_byte = ((y % 8) * 2) + 1;
+        let correct_byte = (self.tile_row(y) * 2) + 1;
 
         if lcd_control.bg_window_tile_data_area().start == TILE_DATA_1_START {
             let tilemap_base = TILE_DATA_1_START + (self.tile_id as u16) * 16;
 
-            let tile_low = bus.borrow_mut().read_byte(tilemap_base + correct_byte as u16);
+            let tile_low = bus.borrow_mut().read_vram(self.tile_bank(), tilemap_base + correct_byte as u16);
 
             tile_low
             
@@ -194,7 +217,7 @@ impl PixelFetcher {
             let offset = (self.tile_id as i8) as i16 * 16;
             let tilemap_base = base.wrapping_add_signed(offset);
 
-            let tile_low = bus.borrow_mut().read_byte(tilemap_base + correct_byte as u16);
+            let tile_low = bus.borrow_mut().read_vram(self.tile_bank(), tilemap_base + correct_byte as u16);
 
             tile_low
         } else {
@@ -203,26 +226,36 @@ impl PixelFetcher {
     }
 
     fn apply_background_palette<T: Mbc>(&self, bus: &Rc<RefCell<Mmu<T>>>, color_index: u8) -> Color {
-        let palette = bus.borrow_mut().read_byte(BGP_ADDR);
+        let bus = bus.borrow();
+        let palette = bus.read_byte(BGP_ADDR);
 
         let index = (palette >> (color_index * 2)) & 0b11;
 
-        Color::from_index(index)
+        match bus.mode() {
+            HardwareMode::CgbCompat => Color::Rgb555(bus.bg_color(0, index)),
+            _ => Color::from_index(index),
+        }
     }
 
     fn push_pixel<T: Mbc>(&mut self, bus: &Rc<RefCell<Mmu<T>>>) -> Option<[Pixel; 8]> {
         let mut tile_pixels = [Pixel::default(); 8];
+        let is_cgb = bus.borrow().mode() == HardwareMode::Cgb;
+        let x_flip = self.attributes & 0x20 != 0;
 
         for i in 0..8 {
-            let bit_index = 7 - i;
+            let bit_index = if x_flip { i } else { 7 - i };
 
             let low_weight_bit = (self.tile_data_low >> bit_index) & 1;
             let high_weight_bit = (self.tile_data_high >> bit_index) & 1;
 
             let color_index = (high_weight_bit << 1) | low_weight_bit;
-            let bgp = self.apply_background_palette(&bus, color_index);
 
-            let pixel = Pixel::new_bg(bgp, color_index);
+            let pixel = if is_cgb {
+                let color = bus.borrow().bg_color(self.attributes & 0b111, color_index);
+                Pixel::new_cgb_bg(Color::Rgb555(color), color_index, self.attributes & 0x80 != 0)
+            } else {
+                Pixel::new_bg(self.apply_background_palette(bus, color_index), color_index)
+            };
             
             tile_pixels[i as usize] = pixel;
         }
@@ -383,6 +416,28 @@ mod tests {
         assert_eq!(fetcher.fetcher_x, 1);
     }
 
+    #[test]
+    fn test_cgb_attributes_flip_and_color_the_tile() {
+        let mut rom = vec![0; 0x150];
+        rom[0x0143] = 0x80;
+        let bus: Rc<RefCell<Mmu<RomOnly>>> = Mmu::<RomOnly>::new(&rom).unwrap().into();
+        write(bus.clone(), 0xFF68, 0x80 | 0x0A); // palette 1, color 1
+        write(bus.clone(), 0xFF69, 0x1F);
+        write(bus.clone(), 0xFF69, 0x00);
+
+        let mut fetcher = PixelFetcher {
+            attributes: 0x80 | 0x20 | 0x01, // BG priority, x flip, palette 1
+            tile_data_low: 0b1000_0000,
+            ..Default::default()
+        };
+
+        let pixels = fetcher.push_pixel(&bus).unwrap();
+        assert_eq!(pixels[0].get_color_index(), 0);
+        assert_eq!(pixels[7].get_color_index(), 1);
+        assert_eq!(pixels[7].get_color().to_rgb(), [255, 0, 0]);
+        assert!(pixels[7].get_priority());
+    }
+
     #[test]
     fn test_first_fetch_resets_and_does_not_push() {
         let (mut fetcher, fifo, lcd) = setup_fetcher();
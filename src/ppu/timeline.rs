@@ -0,0 +1,127 @@
+use crate::ppu::lcd_status::PpuMode;
+
+pub const LINES_PER_FRAME: usize = 154;
+
+#[derive(Clone, Copy, Default)]
+pub struct ScanlineTiming {
+    pub mode_dots: [u16; 4], // indexed like the STAT mode bits
+    pub sprite_dots: u16,    // dots of mode 3 spent fetching sprites or stalled after a fetch
+    pub scx_discard: u8,     // pixels thrown away at the start of the line because of SCX % 8
+}
+
+impl ScanlineTiming {
+    pub fn dots_in(&self, mode: PpuMode) -> u16 {
+        self.mode_dots[mode as usize]
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct RegisterWrite {
+    pub line: u8,
+    pub dot: u16,
+    pub address: u16,
+    pub value: u8,
+}
+
+#[derive(Clone)]
+pub struct FrameTimeline {
+    pub lines: Vec<ScanlineTiming>,
+    pub writes: Vec<RegisterWrite>,
+}
+
+impl Default for FrameTimeline {
+    fn default() -> Self {
+        Self {
+            lines: vec![ScanlineTiming::default(); LINES_PER_FRAME],
+            writes: Vec::new(),
+        }
+    }
+}
+
+/*
+    Records how the PPU spends each dot of a frame and when the CPU writes to the LCD registers.
+    Disabled by default, the debugger turns it on when the timeline is shown.
+*/
+#[derive(Default)]
+pub struct Timeline {
+    enabled: bool,
+    current: FrameTimeline,
+    last_frame: Option<FrameTimeline>,
+}
+
+impl Timeline {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.current = FrameTimeline::default();
+        self.last_frame = None;
+    }
+
+    pub fn record_dot(&mut self, line: u8, mode: PpuMode, is_sprite_dot: bool) {
+        if let Some(timing) = self.current.lines.get_mut(line as usize) {
+            timing.mode_dots[mode as usize] += 1;
+            if is_sprite_dot {
+                timing.sprite_dots += 1;
+            }
+        }
+    }
+
+    pub fn record_write(&mut self, line: u8, dot: u16, address: u16, value: u8) {
+        self.current.writes.push(RegisterWrite { line, dot, address, value });
+    }
+
+    pub fn set_scx_discard(&mut self, line: u8, pixels: u8) {
+        if let Some(timing) = self.current.lines.get_mut(line as usize) {
+            timing.scx_discard = pixels;
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        if self.enabled {
+            self.last_frame = Some(std::mem::take(&mut self.current));
+        }
+    }
+
+    // Last complete frame, the one being drawn is still missing its lines
+    pub fn last_frame(&self) -> Option<&FrameTimeline> {
+        self.last_frame.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_is_available_once_complete() {
+        let mut timeline = Timeline::default();
+        timeline.set_enabled(true);
+
+        for _ in 0..80 {
+            timeline.record_dot(3, PpuMode::OamSearch, false);
+        }
+        for dot in 0..180 {
+            timeline.record_dot(3, PpuMode::PixelTransfer, dot < 8);
+        }
+        timeline.set_scx_discard(3, 5);
+        timeline.record_write(3, 120, 0xFF43, 0x10);
+        assert!(timeline.last_frame().is_none());
+
+        timeline.end_frame();
+        let frame = timeline.last_frame().unwrap();
+        let line = frame.lines[3];
+        assert_eq!(line.dots_in(PpuMode::OamSearch), 80);
+        assert_eq!(line.dots_in(PpuMode::PixelTransfer), 180);
+        assert_eq!(line.sprite_dots, 8);
+        assert_eq!(line.scx_discard, 5);
+        assert_eq!(frame.writes.len(), 1);
+        assert_eq!(frame.writes[0].dot, 120);
+
+        // the next frame starts empty
+        timeline.end_frame();
+        assert_eq!(timeline.last_frame().unwrap().lines[3].dots_in(PpuMode::OamSearch), 0);
+    }
+}
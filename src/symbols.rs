@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::mmu::MemoryRegion;
+
+pub struct Symbol {
+    pub bank: u16,
+    pub address: u16,
+    pub name: String,
+}
+
+/*
+    Labels from a `.sym` file, RGBDS and no$gmb both write one `bank:address label` per line.
+    Labels in 0x4000-0x7FFF only apply while their bank is the one mapped by the MBC.
+*/
+#[derive(Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>, // sorted by address
+    by_name: HashMap<String, u16>,
+}
+
+impl SymbolTable {
+    pub fn parse(content: &str) -> Self {
+        let mut symbols: Vec<Symbol> = content.lines().filter_map(parse_line).collect();
+        symbols.sort_by_key(|symbol| (symbol.address, symbol.bank));
+
+        let by_name = symbols
+            .iter()
+            .map(|symbol| (symbol.name.clone(), symbol.address))
+            .collect();
+
+        SymbolTable { symbols, by_name }
+    }
+
+    // Looks for `game.sym` next to `game.gb`, a missing file just means no labels
+    pub fn load_for_rom(rom_path: &str) -> Result<Self, String> {
+        let sym_path = Path::new(rom_path).with_extension("sym");
+        if !sym_path.exists() {
+            return Ok(SymbolTable::default());
+        }
+
+        let content = fs::read_to_string(&sym_path)
+            .map_err(|e| format!("Cannot read {}: {}", sym_path.display(), e))?;
+        let table = SymbolTable::parse(&content);
+        if table.is_empty() {
+            eprintln!("No symbol found in {}", sym_path.display());
+        } else {
+            println!("{} symbols loaded from {}", table.len(), sym_path.display());
+        }
+        Ok(table)
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn address_of(&self, name: &str) -> Option<u16> {
+        self.by_name.get(name).copied()
+    }
+
+    // Closest label at or before the address in the same memory region, as `label` or `label+offset`
+    pub fn describe(&self, address: u16, rom_bank: u16) -> Option<String> {
+        let region = MemoryRegion::from(address);
+        let index = self.symbols.partition_point(|symbol| symbol.address <= address);
+        let symbol = self.symbols[..index]
+            .iter()
+            .rev()
+            .take_while(|symbol| MemoryRegion::from(symbol.address) == region)
+            .find(|symbol| !(0x4000..0x8000).contains(&address) || symbol.bank == rom_bank)?;
+
+        let offset = address - symbol.address;
+        if offset == 0 {
+            Some(symbol.name.clone())
+        } else {
+            Some(format!("{}+{:X}", symbol.name, offset))
+        }
+    }
+
+    /*
+        Address typed by the user: `label` / `label+offset`, or hex with an optional `0x` or `$` prefix.
+        Labels are looked up first, `Add` or `cafe` are valid label names that also read as hex.
+    */
+    pub fn parse_address(&self, text: &str) -> Result<u16, String> {
+        let text = text.trim();
+        if let Some(address) = self.parse_label(text)? {
+            return Ok(address);
+        }
+
+        let hex = text
+            .strip_prefix("0x")
+            .or_else(|| text.strip_prefix('$'))
+            .unwrap_or(text);
+        u16::from_str_radix(hex, 16).map_err(|_| format!("Unknown address or label: {text}"))
+    }
+
+    fn parse_label(&self, text: &str) -> Result<Option<u16>, String> {
+        let (name, offset) = match text.split_once('+') {
+            Some((name, offset)) => (name.trim(), Some(offset.trim())),
+            None => (text, None),
+        };
+        let Some(address) = self.address_of(name) else {
+            return Ok(None);
+        };
+
+        let offset = match offset {
+            Some(offset) => u16::from_str_radix(offset.trim_start_matches("0x"), 16)
+                .map_err(|_| format!("Invalid offset: {offset}"))?,
+            None => 0,
+        };
+        Ok(Some(address.wrapping_add(offset)))
+    }
+}
+
+fn parse_line(line: &str) -> Option<Symbol> {
+    let line = line.split(';').next()?.trim();
+    if line.is_empty() || line.starts_with('[') {
+        return None;
+    }
+
+    let mut fields = line.split_whitespace();
+    let (bank, address) = fields.next()?.split_once(':')?;
+    let name = fields.next()?;
+
+    Some(Symbol {
+        bank: u16::from_str_radix(bank, 16).ok()?,
+        address: u16::from_str_radix(address, 16).ok()?,
+        name: name.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYM_FILE: &str = "; File generated by rgblink
+00:0150 Main
+00:0160 Main.loop
+01:4000 LoadTiles
+02:4000 Music
+00:c000 wPlayerX
+; no$gmb files can have sections
+[labels]
+0000:ff80 hDMARoutine
+garbage line
+";
+
+    #[test]
+    fn parse_rgbds_and_nogmb_lines() {
+        let table = SymbolTable::parse(SYM_FILE);
+
+        assert_eq!(table.len(), 6);
+        assert_eq!(table.address_of("Main.loop"), Some(0x0160));
+        assert_eq!(table.address_of("hDMARoutine"), Some(0xFF80));
+        assert_eq!(table.address_of("garbage"), None);
+    }
+
+    #[test]
+    fn describe_uses_closest_label_in_region() {
+        let table = SymbolTable::parse(SYM_FILE);
+
+        assert_eq!(table.describe(0x0150, 1), Some("Main".to_string()));
+        assert_eq!(table.describe(0x0153, 1), Some("Main+3".to_string()));
+        assert_eq!(table.describe(0xC010, 1), Some("wPlayerX+10".to_string()));
+        // nothing before it in the same region
+        assert_eq!(table.describe(0x8000, 1), None);
+        assert_eq!(table.describe(0x0100, 1), None);
+    }
+
+    #[test]
+    fn describe_follows_the_mapped_rom_bank() {
+        let table = SymbolTable::parse(SYM_FILE);
+
+        assert_eq!(table.describe(0x4010, 1), Some("LoadTiles+10".to_string()));
+        assert_eq!(table.describe(0x4010, 2), Some("Music+10".to_string()));
+        assert_eq!(table.describe(0x4010, 3), None);
+        // bank 0 and RAM labels don't depend on the mapped bank
+        assert_eq!(table.describe(0x0160, 3), Some("Main.loop".to_string()));
+    }
+
+    #[test]
+    fn parse_address_accepts_hex_and_labels() {
+        let table = SymbolTable::parse(SYM_FILE);
+
+        assert_eq!(table.parse_address("C000"), Ok(0xC000));
+        assert_eq!(table.parse_address("0xff80"), Ok(0xFF80));
+        assert_eq!(table.parse_address("$0150"), Ok(0x0150));
+        assert_eq!(table.parse_address("wPlayerX"), Ok(0xC000));
+        assert_eq!(table.parse_address("wPlayerX+1"), Ok(0xC001));
+        assert!(table.parse_address("Unknown").is_err());
+    }
+
+    #[test]
+    fn parse_address_prefers_labels_that_read_as_hex() {
+        let table = SymbolTable::parse("00:0200 Add\n00:0300 cafe\n01:4100 BEEF\n");
+
+        assert_eq!(table.parse_address("Add"), Ok(0x0200));
+        assert_eq!(table.parse_address("cafe+2"), Ok(0x0302));
+        assert_eq!(table.parse_address("BEEF"), Ok(0x4100));
+        // a prefix always means hex
+        assert_eq!(table.parse_address("$BEEF"), Ok(0xBEEF));
+        assert_eq!(table.parse_address("FACE"), Ok(0xFACE));
+        assert!(table.parse_address("Add+zz").is_err());
+    }
+}